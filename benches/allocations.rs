@@ -0,0 +1,93 @@
+//! Counts the allocations made by `set`, `set_raw` and `delete` on a 1 MB
+//! document, next to serializing the same tree with `serde_json::to_string`,
+//! which grows its buffer from empty, and those of the optimistic fast path.
+//!
+//! Run with `cargo bench --bench allocations`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static REALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        REALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Run `f` and return the number of allocations and reallocations it made
+fn count<T>(f: impl FnOnce() -> T) -> (usize, usize) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let reallocations = REALLOCATIONS.load(Ordering::Relaxed);
+    let result = f();
+    let counts = (
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        REALLOCATIONS.load(Ordering::Relaxed) - reallocations,
+    );
+    drop(result);
+    counts
+}
+
+/// Print the allocations of `f`, with the reallocations parsing already needs
+/// subtracted
+fn report(name: &str, parse_reallocs: usize, f: impl FnOnce() -> String) {
+    let (allocs, reallocs) = count(f);
+    println!(
+        "{}: {} allocations, {} reallocations ({} more than parsing alone)",
+        name,
+        allocs,
+        reallocs,
+        reallocs.saturating_sub(parse_reallocs),
+    );
+}
+
+/// Print the allocations of `f`, which doesn't parse the document
+fn report_optimistic(name: &str, f: impl FnOnce() -> String) {
+    let (allocs, reallocs) = count(f);
+    println!("optimistic {}: {} allocations, {} reallocations", name, allocs, reallocs);
+}
+
+fn main() {
+    let mut json = String::from(r#"{"name":"Tom","items":["#);
+    let mut i = 0;
+    while json.len() < 1 << 20 {
+        if i > 0 {
+            json.push(',');
+        }
+        json.push_str(&format!(r#"{{"id":{},"label":"item {}"}}"#, i, i));
+        i += 1;
+    }
+    json.push_str("]}");
+
+    let parsed = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+    let (_, parse_reallocs) = count(|| serde_json::from_str::<serde_json::Value>(&json).unwrap());
+    let (_, to_string_reallocs) = count(|| serde_json::to_string(&parsed).unwrap());
+    println!("document: {} bytes", json.len());
+    println!("serde_json::to_string: {} reallocations", to_string_reallocs);
+
+    // A new key, as replacing an existing scalar splices without parsing
+    report("set", parse_reallocs, || sjson::set(&json, "title", "Jerry").unwrap());
+    report("set_raw", parse_reallocs, || sjson::set_raw(&json, "tags", r#"["a","b"]"#).unwrap());
+    report("delete", parse_reallocs, || sjson::delete(&json, "name").unwrap());
+
+    let opts = sjson::Options { optimistic: true, ..Default::default() };
+    report_optimistic("set", || sjson::set_options(&json, "name", "Jerry", Some(&opts)).unwrap());
+    report_optimistic("delete", || sjson::delete_options(&json, "name", Some(&opts)).unwrap());
+    report_optimistic("delete element", || sjson::delete_options(&json, "items.1000", Some(&opts)).unwrap());
+}
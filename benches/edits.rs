@@ -0,0 +1,63 @@
+//! Times `set` and `delete` on a small and a 1 MB document, with and
+//! without the optimistic fast path. Without it, replacing an existing
+//! scalar is still spliced, while inserting a key goes through the parser.
+//!
+//! Run with `cargo bench --bench edits`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use sjson::Options;
+
+fn documents() -> [(&'static str, String); 2] {
+    let small = String::from(r#"{"name":"Tom","age":37,"items":[{"id":0},{"id":1},{"id":2}]}"#);
+
+    let mut large = String::from(r#"{"name":"Tom","age":37,"items":["#);
+    let mut i = 0;
+    while large.len() < 1 << 20 {
+        if i > 0 {
+            large.push(',');
+        }
+        large.push_str(&format!(r#"{{"id":{},"label":"item {}"}}"#, i, i));
+        i += 1;
+    }
+    large.push_str("]}");
+
+    [("small", small), ("large", large)]
+}
+
+fn modes() -> [(&'static str, Options); 2] {
+    let optimistic = Options { optimistic: true, ..Default::default() };
+    [("optimistic", optimistic), ("default", Options::default())]
+}
+
+fn bench_set(c: &mut Criterion) {
+    let mut group = c.benchmark_group("set");
+    for (size, json) in documents() {
+        for (mode, opts) in modes() {
+            group.bench_with_input(BenchmarkId::new(format!("{}/replace", mode), size), &json, |b, json| {
+                b.iter(|| sjson::set_options(json, "name", "Jerry", Some(&opts)).unwrap())
+            });
+            group.bench_with_input(BenchmarkId::new(format!("{}/insert", mode), size), &json, |b, json| {
+                b.iter(|| sjson::set_options(json, "title", "Jerry", Some(&opts)).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_delete(c: &mut Criterion) {
+    let mut group = c.benchmark_group("delete");
+    for (size, json) in documents() {
+        for (mode, opts) in modes() {
+            group.bench_with_input(BenchmarkId::new(format!("{}/key", mode), size), &json, |b, json| {
+                b.iter(|| sjson::delete_options(json, "age", Some(&opts)).unwrap())
+            });
+            group.bench_with_input(BenchmarkId::new(format!("{}/element", mode), size), &json, |b, json| {
+                b.iter(|| sjson::delete_options(json, "items.1", Some(&opts)).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_set, bench_delete);
+criterion_main!(benches);
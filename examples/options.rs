@@ -1,3 +1,5 @@
+#![allow(clippy::field_reassign_with_default)]
+
 use sjson::{set_options, set_raw_options, delete_options, Options};
 
 fn main() {
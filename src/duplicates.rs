@@ -0,0 +1,185 @@
+//! Parsing documents that repeat a key within one object, see
+//! `Options::on_duplicate_key`.
+//!
+//! serde_json keeps the last of the repeated members. The visitors here
+//! either report the first repeated key or build the tree from the first
+//! member of each key instead.
+
+use alloc::collections::BTreeSet;
+use alloc::string::String;
+use core::cell::RefCell;
+use core::fmt;
+use core::marker::PhantomData;
+
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde_json::{Number, Value as JsonValue};
+
+use crate::{Node, Tree, TreeMap};
+
+/// The key serde_json uses to pass numbers through as text when built with
+/// `arbitrary_precision`
+#[cfg(feature = "arbitrary_precision")]
+const NUMBER_TOKEN: &str = "$serde_json::private::Number";
+
+/// Find the first key that is repeated within one object of `json`, or
+/// `None` when every object has distinct keys
+pub(crate) fn find_duplicate(json: &[u8]) -> Result<Option<String>, serde_json::Error> {
+    let duplicate = RefCell::new(None);
+    let mut deserializer = serde_json::Deserializer::from_slice(json);
+    let checked = Check { duplicate: &duplicate }.deserialize(&mut deserializer).and_then(|()| deserializer.end());
+    match (checked, duplicate.into_inner()) {
+        (_, Some(key)) => Ok(Some(key)),
+        (Ok(()), None) => Ok(None),
+        (Err(e), None) => Err(e),
+    }
+}
+
+/// Parse `json` keeping the first member of each repeated key
+pub(crate) fn parse_first_wins<V: Tree>(json: &[u8]) -> Result<V, serde_json::Error> {
+    let mut deserializer = serde_json::Deserializer::from_slice(json);
+    let value = FirstWins(PhantomData).deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+/// Walks a value without building it, stopping at the first repeated key
+struct Check<'a> {
+    duplicate: &'a RefCell<Option<String>>,
+}
+
+impl<'de> DeserializeSeed<'de> for Check<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Check<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("any valid json value")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_unit<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while seq.next_element_seed(Check { duplicate: self.duplicate })?.is_some() {}
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<(), A::Error> {
+        let mut keys = BTreeSet::new();
+        while let Some(key) = access.next_key::<String>()? {
+            if keys.contains(&key) {
+                *self.duplicate.borrow_mut() = Some(key);
+                return Err(de::Error::custom("duplicate key"));
+            }
+            access.next_value_seed(Check { duplicate: self.duplicate })?;
+            keys.insert(key);
+        }
+        Ok(())
+    }
+}
+
+/// Builds a tree in which the first member of a repeated key wins
+struct FirstWins<V>(PhantomData<V>);
+
+impl<'de, V: Tree> DeserializeSeed<'de> for FirstWins<V> {
+    type Value = V;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<V, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, V: Tree> Visitor<'de> for FirstWins<V> {
+    type Value = V;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("any valid json value")
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<V, E> {
+        Ok(V::from_json(JsonValue::Bool(value)))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<V, E> {
+        Ok(V::from_json(JsonValue::Number(value.into())))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<V, E> {
+        Ok(V::from_json(JsonValue::Number(value.into())))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<V, E> {
+        Ok(V::from_json(Number::from_f64(value).map_or(JsonValue::Null, JsonValue::Number)))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<V, E> {
+        Ok(V::from_json(JsonValue::String(value.into())))
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<V, E> {
+        Ok(V::from_json(JsonValue::String(value)))
+    }
+
+    fn visit_unit<E>(self) -> Result<V, E> {
+        Ok(V::null())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<V, A::Error> {
+        let mut value = V::array();
+        let Node::Array(arr) = value.node_mut() else {
+            unreachable!("V::array() is an array");
+        };
+        while let Some(element) = seq.next_element_seed(FirstWins(PhantomData))? {
+            arr.push(element);
+        }
+        Ok(value)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<V, A::Error> {
+        let mut value = V::object();
+        let Node::Object(map) = value.node_mut() else {
+            unreachable!("V::object() is an object");
+        };
+        while let Some(key) = access.next_key::<String>()? {
+            #[cfg(feature = "arbitrary_precision")]
+            if key == NUMBER_TOKEN {
+                let text: String = access.next_value()?;
+                return text.parse::<Number>().map(|n| V::from_json(JsonValue::Number(n))).map_err(de::Error::custom);
+            }
+            if map.contains_key(&key) {
+                access.next_value::<IgnoredAny>()?;
+            } else {
+                let member = access.next_value_seed(FirstWins(PhantomData))?;
+                map.insert(key, member);
+            }
+        }
+        Ok(value)
+    }
+}
@@ -0,0 +1,89 @@
+//! Escaping more of the output than serde_json does, see
+//! `Options::escape_non_ascii` and `Options::escape_slashes`.
+//!
+//! serde_json only escapes quotes, backslashes and control characters. The
+//! formatter here wraps the compact or pretty formatter and escapes the
+//! rest of each string as it is written, so the layout is left untouched.
+
+use std::io;
+
+use serde_json::ser::Formatter;
+
+/// A formatter that writes strings like `inner` but with `/` written as
+/// `\/` and characters outside of ascii as `\uXXXX` escapes when asked to
+pub(crate) struct Escaping<F> {
+    inner: F,
+    non_ascii: bool,
+    slashes: bool,
+}
+
+impl<F> Escaping<F> {
+    pub(crate) fn new(inner: F, non_ascii: bool, slashes: bool) -> Self {
+        Escaping { inner, non_ascii, slashes }
+    }
+}
+
+impl<F: Formatter> Formatter for Escaping<F> {
+    fn write_string_fragment<W: ?Sized + io::Write>(&mut self, writer: &mut W, fragment: &str) -> io::Result<()> {
+        let mut start = 0;
+        for (i, c) in fragment.char_indices() {
+            let escape_slash = self.slashes && c == '/';
+            if !escape_slash && (c.is_ascii() || !self.non_ascii) {
+                continue;
+            }
+
+            self.inner.write_string_fragment(writer, &fragment[start..i])?;
+            if escape_slash {
+                writer.write_all(b"\\/")?;
+            } else {
+                // Characters beyond the basic plane take a surrogate pair
+                let mut units = [0; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    write!(writer, "\\u{:04x}", unit)?;
+                }
+            }
+            start = i + c.len_utf8();
+        }
+        self.inner.write_string_fragment(writer, &fragment[start..])
+    }
+
+    fn begin_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.begin_array(writer)
+    }
+
+    fn end_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_array(writer)
+    }
+
+    fn begin_array_value<W: ?Sized + io::Write>(&mut self, writer: &mut W, first: bool) -> io::Result<()> {
+        self.inner.begin_array_value(writer, first)
+    }
+
+    fn end_array_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_array_value(writer)
+    }
+
+    fn begin_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.begin_object(writer)
+    }
+
+    fn end_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_object(writer)
+    }
+
+    fn begin_object_key<W: ?Sized + io::Write>(&mut self, writer: &mut W, first: bool) -> io::Result<()> {
+        self.inner.begin_object_key(writer, first)
+    }
+
+    fn end_object_key<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_object_key(writer)
+    }
+
+    fn begin_object_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.begin_object_value(writer)
+    }
+
+    fn end_object_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_object_value(writer)
+    }
+}
@@ -0,0 +1,98 @@
+//! Input cleanup for the `lenient` feature.
+//!
+//! Documents may contain `//` and `/* */` comments and trailing commas in
+//! objects and arrays. Both are blanked out with spaces before the document
+//! reaches serde_json, so the output is always standard json and parse errors
+//! still point at the right line and column.
+
+use alloc::borrow::Cow;
+
+/// Blank out comments and trailing commas in `json`, borrowing it when there
+/// is nothing to remove
+pub(crate) fn strip(json: &[u8]) -> Cow<'_, [u8]> {
+    if !json.iter().any(|&b| b == b'/' || b == b',') {
+        return Cow::Borrowed(json);
+    }
+
+    let mut out = json.to_vec();
+    let mut changed = false;
+    let mut i = 0;
+    while i < out.len() {
+        match out[i] {
+            b'"' => i = string_end(&out, i),
+            b'/' => match comment_end(&out, i) {
+                Some(end) => {
+                    blank(&mut out[i..end]);
+                    changed = true;
+                    i = end;
+                }
+                None => i += 1,
+            },
+            b',' => {
+                // Comments between the comma and the bracket are blanked
+                // when the loop reaches them
+                if is_trailing_comma(&out, i + 1) {
+                    out[i] = b' ';
+                    changed = true;
+                }
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+
+    if changed {
+        Cow::Owned(out)
+    } else {
+        Cow::Borrowed(json)
+    }
+}
+
+/// Replace everything but line breaks with spaces
+fn blank(bytes: &mut [u8]) {
+    for b in bytes.iter_mut().filter(|b| !matches!(b, b'\n' | b'\r')) {
+        *b = b' ';
+    }
+}
+
+/// Whether only whitespace and comments separate `start` from a closing
+/// `}` or `]`
+fn is_trailing_comma(json: &[u8], start: usize) -> bool {
+    let mut i = start;
+    while i < json.len() {
+        match json[i] {
+            b if b.is_ascii_whitespace() => i += 1,
+            b'}' | b']' => return true,
+            _ => match comment_end(json, i) {
+                Some(end) => i = end,
+                None => return false,
+            },
+        }
+    }
+    false
+}
+
+/// Position just past the comment starting at `start`, if there is one.
+/// A line comment ends before its newline; an unterminated block comment
+/// runs to the end of the input.
+fn comment_end(json: &[u8], start: usize) -> Option<usize> {
+    match json.get(start..start + 2)? {
+        b"//" => Some(json[start..].iter().position(|&b| b == b'\n').map_or(json.len(), |n| start + n)),
+        b"/*" => Some(json[start + 2..].windows(2).position(|w| w == b"*/").map_or(json.len(), |n| start + n + 4)),
+        _ => None,
+    }
+}
+
+/// Position just past the string starting at `start`, or the end of the
+/// input when the string isn't terminated
+fn string_end(json: &[u8], start: usize) -> usize {
+    let mut i = start + 1;
+    while i < json.len() {
+        match json[i] {
+            b'\\' => i += 2,
+            b'"' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    json.len()
+}
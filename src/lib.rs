@@ -1,1098 +1,7311 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
 use serde_json::Value as JsonValue;
 
+mod duplicates;
+#[cfg(feature = "std")]
+mod escape;
+#[cfg(feature = "lenient")]
+mod lenient;
+#[cfg(feature = "ordered")]
+mod ordered;
+
+/// The tree that documents given to the string and byte functions are
+/// parsed into. With the `ordered` feature it keeps keys in their original
+/// order independently of how serde_json is configured.
+#[cfg(feature = "ordered")]
+type Parsed = ordered::Value;
+#[cfg(not(feature = "ordered"))]
+type Parsed = JsonValue;
+
 /// Parse array index, supporting negative indices
 fn parse_array_index(part: &str, arr_len: usize) -> Result<usize, SjsonError> {
     let index: i64 = part.parse()
-        .map_err(|_| SjsonError::InvalidPath)?;
-    
+        .map_err(|_| SjsonError::CannotSetArrayElementForNonNumericKey(part.to_string()))?;
+    resolve_array_index(index, arr_len)
+}
+
+/// Resolve a possibly negative array index against the array length.
+/// Negative indices count from the end, -1 being the last element. The
+/// magnitude is taken unsigned, so even `i64::MIN` is simply out of bounds.
+fn resolve_array_index(index: i64, arr_len: usize) -> Result<usize, SjsonError> {
+    let out_of_bounds = SjsonError::ArrayIndexOutOfBounds { index, len: arr_len };
     if index >= 0 {
-        Ok(index as usize)
-    } else {
-        // Handle negative indices: -1 means last element, -2 means second to last, etc.
-        let abs_index = (-index) as usize;
-        if abs_index > arr_len {
-            Err(SjsonError::InvalidPath)
-        } else {
-            Ok(arr_len - abs_index)
-        }
+        return usize::try_from(index).map_err(|_| out_of_bounds);
     }
+    usize::try_from(index.unsigned_abs())
+        .ok()
+        .and_then(|abs_index| arr_len.checked_sub(abs_index))
+        .ok_or(out_of_bounds)
 }
 
 /// Options represents additional options for the Set and Delete functions.
-#[derive(Default, Clone)]
+#[derive(Clone)]
 pub struct Options {
     /// Optimistic is a hint that the value likely exists which
     /// allows for the sjson to perform a fast-track search and replace.
     pub optimistic: bool,
+    /// GoCompatibleAppend makes a final `-1` path segment append a new
+    /// element to the array, like tidwall/sjson does, instead of
+    /// overwriting the last existing element.
+    pub go_compatible_append: bool,
+    /// GoSjsonCompat switches to the semantics of tidwall/sjson: a final
+    /// `-1` appends like `go_compatible_append`, and the value given to the
+    /// set functions is always a string, as with a Go `string` passed to
+    /// `sjson.Set`, so `"1"` or `"true"` are no longer read as a number or
+    /// bool. Raw and typed setters such as `set_raw` and `set_int` still write
+    /// json. An empty path is an error either way.
+    pub go_sjson_compat: bool,
+    /// ReplaceInPlace lets `set_in_place` edit the caller's buffer directly
+    /// when the optimistic splice succeeds and the new value is no longer
+    /// than the old one. Other edits still build a new string.
+    pub replace_in_place: bool,
+    /// ErrorOnNoChange makes set operations return `SjsonError::NoChange`
+    /// when the value at the path already equals the new value, so callers
+    /// can skip writing back an unchanged document.
+    pub error_on_nochange: bool,
+    /// OnlyIfAbsent makes set operations return `SjsonError::NoChange`
+    /// instead of overwriting a value that already exists, even a `null`.
+    pub only_if_absent: bool,
+    /// Pretty prints the result instead of writing compact json. The
+    /// optimistic path can't control formatting, so it is skipped when set.
+    #[cfg(feature = "std")]
+    pub pretty: Option<PrettyOptions>,
+    /// Compact writes the result without any whitespace, which is the
+    /// default. Turning it off pretty prints like `pretty` set to
+    /// `PrettyOptions::default()`, unless `pretty` gives a layout of its own.
+    /// Edits that splice the original text, such as replacing an existing
+    /// scalar, keep its layout either way unless it is turned off.
+    #[cfg(feature = "std")]
+    pub compact: bool,
+    /// SortKeys sorts the keys of every object in the result, for canonical
+    /// output regardless of the input order. Like `pretty` it needs the
+    /// parser, so the optimistic path is skipped when set.
+    #[cfg(feature = "std")]
+    pub sort_keys: bool,
+    /// EscapeNonAscii writes every character outside of ascii in strings
+    /// and keys as a `\uXXXX` escape, for consumers that only accept ascii.
+    /// It applies to the whole document, so the optimistic path is skipped.
+    #[cfg(feature = "std")]
+    pub escape_non_ascii: bool,
+    /// EscapeSlashes writes `/` in strings and keys as `\/`, which some
+    /// parsers expect, for example when json is embedded in html. Like
+    /// `escape_non_ascii` the optimistic path is skipped.
+    #[cfg(feature = "std")]
+    pub escape_slashes: bool,
+    /// TreatNullAsDelete makes `set_options` remove the path when the value
+    /// is `null`, like a JSON merge patch, instead of storing null.
+    pub treat_null_as_delete: bool,
+    /// ErrorOnNonObject makes `set_all` fail with
+    /// `SjsonError::JsonMustBeObjectOrArray` on array elements that aren't
+    /// objects instead of skipping them.
+    pub error_on_non_object: bool,
+    /// TrimTrailingNulls makes deleting an array element also remove the
+    /// nulls left at the end of the array, such as the padding added when
+    /// setting an index past the end.
+    pub trim_trailing_nulls: bool,
+    /// Separator splits the path into segments, `.` by default. With another
+    /// separator such as `/` a `.` is an ordinary key character, and the
+    /// separator itself can be escaped with a backslash instead. A character
+    /// that means something else in a path, the backslash escape or one of
+    /// `#`, `*`, `:`, `@`, `$`, `(`, `)` and `=`, can't be used, and paths
+    /// split on it are `SjsonError::InvalidPath`.
+    pub separator: char,
+    /// Verify re-parses the result of the optimistic fast path and falls
+    /// back to the full parser when it isn't well-formed json, trading some
+    /// speed for a guard against the byte scan misreading the document.
+    pub verify: bool,
+    /// IgnoreMissing makes `delete_many` skip paths that don't exist instead
+    /// of failing with `SjsonError::EditFailed`.
+    pub ignore_missing: bool,
+    /// MaxDepth caps the number of segments a path may have, so untrusted
+    /// paths can't make set build arbitrarily deep documents. Longer paths
+    /// fail with `SjsonError::PathTooDeep`. Input documents are limited
+    /// separately, serde_json refuses to parse nesting deeper than 128.
+    pub max_depth: Option<usize>,
+    /// NoClobber makes a path that steps through a string, number, bool or
+    /// null fail with `SjsonError::PathTypeMismatch` instead of replacing
+    /// that value with a new object or array.
+    pub no_clobber: bool,
+    /// PreserveUntouched makes edits splice the original text like
+    /// `optimistic`, and fail with `SjsonError::SpliceUnavailable` instead of
+    /// falling back to the parser, which would reformat every number and
+    /// string in the document.
+    pub preserve_untouched: bool,
+    /// AssumeValidRaw makes the optimistic path splice raw values verbatim
+    /// without checking that they are json. Garbage in, garbage out: an
+    /// invalid fragment produces an invalid document unless `verify` is set.
+    /// The parser path always parses the value and still rejects it.
+    pub assume_valid_raw: bool,
+    /// OnDuplicateKey decides which member counts when an object in the
+    /// input repeats a key, see [`DuplicateKeys`].
+    pub on_duplicate_key: DuplicateKeys,
+    /// CreateMissing lets set operations create the parents of the target
+    /// that don't exist yet, which is the default. When turned off, a
+    /// missing parent fails with `SjsonError::PathNotFound` instead.
+    pub create_missing: bool,
+    /// StrictIndices makes a numeric segment such as `0` that lands on an
+    /// object fail with `SjsonError::PathTypeMismatch`, instead of
+    /// addressing the member whose key is `"0"`.
+    pub strict_indices: bool,
+    /// AllowScalarRootReplace lets set replace a document that is a single
+    /// string, number, bool or null with the object or array the path steps
+    /// into. Otherwise such a document fails with
+    /// `SjsonError::JsonMustBeObjectOrArray`.
+    pub allow_scalar_root_replace: bool,
+    /// AllowScalarIndexReplace lets a numeric segment such as `0` that steps
+    /// into a string, number or bool replace it with a new array. Otherwise
+    /// it fails with `SjsonError::PathTypeMismatch`, as `a.0` on
+    /// `{"a":"hello"}` is rarely meant to discard the string. Null is always
+    /// replaced, and `no_clobber` still refuses every scalar.
+    pub allow_scalar_index_replace: bool,
+    /// StrictWildcards makes a `*` segment fail with
+    /// `SjsonError::PathTypeMismatch` for a child the rest of the path can't
+    /// step into, such as a number under `servers.*.enabled`. Otherwise such
+    /// children are skipped and the others are still edited.
+    pub strict_wildcards: bool,
+    /// PruneEmpty makes a delete that leaves its parent object or array
+    /// empty delete that parent too, and so on up to, but not including, the
+    /// root. A parent that still has other members stops the pruning.
+    pub prune_empty: bool,
+    /// OnFallback is called with the reason, such as "key not found at
+    /// segment 2", whenever `optimistic` is set but the fast path can't make
+    /// an edit and it goes through the parser after all. Segments are
+    /// counted from 1. It helps tell which
+    /// paths are worth marking optimistic; the reason is only worked out
+    /// when a callback is set.
+    pub on_fallback: Option<fn(&str)>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            optimistic: false,
+            go_compatible_append: false,
+            go_sjson_compat: false,
+            replace_in_place: false,
+            error_on_nochange: false,
+            only_if_absent: false,
+            #[cfg(feature = "std")]
+            pretty: None,
+            #[cfg(feature = "std")]
+            compact: true,
+            #[cfg(feature = "std")]
+            sort_keys: false,
+            #[cfg(feature = "std")]
+            escape_non_ascii: false,
+            #[cfg(feature = "std")]
+            escape_slashes: false,
+            treat_null_as_delete: false,
+            error_on_non_object: false,
+            trim_trailing_nulls: false,
+            separator: DEFAULT_SEPARATOR,
+            verify: false,
+            ignore_missing: false,
+            max_depth: None,
+            no_clobber: false,
+            preserve_untouched: false,
+            assume_valid_raw: false,
+            on_duplicate_key: DuplicateKeys::KeepLast,
+            create_missing: true,
+            strict_indices: false,
+            allow_scalar_root_replace: false,
+            allow_scalar_index_replace: false,
+            strict_wildcards: false,
+            prune_empty: false,
+            on_fallback: None,
+        }
+    }
+}
+
+/// DuplicateKeys is the policy for input objects that repeat a key, such as
+/// `{"a":1,"a":2}`. Both the parser and the optimistic path follow it: an
+/// edit never lands on a member that the policy hides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    /// The last member wins, like serde_json. The others are dropped when
+    /// the document goes through the parser.
+    KeepLast,
+    /// The first member wins and the others are dropped when the document
+    /// goes through the parser.
+    KeepFirst,
+    /// The document is rejected with `SjsonError::DuplicateKey`.
+    Error,
+}
+
+/// PrettyOptions controls how pretty printed output is laid out.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+pub struct PrettyOptions {
+    /// Indent is written once per nesting level, two spaces by default.
+    pub indent: String,
+    /// SortKeys sorts the keys of every object alphabetically.
+    pub sort_keys: bool,
+}
+
+#[cfg(feature = "std")]
+impl Default for PrettyOptions {
+    fn default() -> Self {
+        PrettyOptions {
+            indent: "  ".to_string(),
+            sort_keys: false,
+        }
+    }
 }
 
-#[derive(Debug)]
+/// SjsonError is the error returned by every fallible function of the crate.
+/// It is `Clone` and comparable, the serde_json and io errors it carries are
+/// shared behind an `Arc` and compared by their message.
+#[derive(Debug, Clone)]
 pub enum SjsonError {
     EmptyPath,
     InvalidPath,
     NoChange,
     ComplexPathNotSupported,
+    /// The path has more segments than `Options::max_depth` allows
+    PathTooDeep,
+    /// With `Options::preserve_untouched`, the edit can't be made by
+    /// splicing the original text
+    SpliceUnavailable,
+    /// With `Options::no_clobber`, `segment` would have to step into a
+    /// value that isn't an object or array, or a `#` segment would append to
+    /// something other than an array. An index steps into a string, number
+    /// or bool without `Options::allow_scalar_index_replace`, and with
+    /// `Options::strict_indices` an index would step into an object. `found`
+    /// names the value's type
+    PathTypeMismatch { segment: String, found: &'static str },
     JsonMustBeObjectOrArray,
+    /// The value set at a `start:end` slice of an array isn't an array
+    SliceValueMustBeArray,
     CannotSetArrayElementForNonNumericKey(String),
+    /// With `Options::create_missing` turned off, the parent named by the
+    /// segment doesn't exist
+    PathNotFound(String),
+    /// A negative array index reaches before the first element
+    ArrayIndexOutOfBounds { index: i64, len: usize },
+    /// An edit of a batch failed, `index` is its position in the batch
+    EditFailed { index: usize, source: Box<SjsonError> },
+    /// With `DuplicateKeys::Error`, an object in the input repeats a key
+    DuplicateKey(String),
+    /// A number set through the parser path doesn't fit an `i64`, `u64` or
+    /// `f64` without the `arbitrary_precision` feature
+    NumberOutOfRange(String),
+    /// The input json or a raw value could not be parsed
+    InvalidJson(Arc<serde_json::Error>),
+    /// The result could not be serialized
+    Serialize(Arc<serde_json::Error>),
+    /// The result could not be written by one of the `*_to_writer` functions
+    #[cfg(feature = "std")]
+    Io(Arc<std::io::Error>),
     Custom(String),
 }
 
-impl std::fmt::Display for SjsonError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl fmt::Display for SjsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             SjsonError::EmptyPath => write!(f, "path cannot be empty"),
             SjsonError::InvalidPath => write!(f, "invalid path"),
             SjsonError::NoChange => write!(f, "no change"),
             SjsonError::ComplexPathNotSupported => write!(f, "complex path not supported"),
+            SjsonError::PathTooDeep => write!(f, "path is too deep"),
+            SjsonError::SpliceUnavailable => write!(f, "edit requires reserializing the document"),
+            SjsonError::PathTypeMismatch { segment, found } => {
+                let article = if found.starts_with(['a', 'o']) { "an" } else { "a" };
+                write!(f, "path segment '{}' steps into {} {}", segment, article, found)
+            }
             SjsonError::JsonMustBeObjectOrArray => write!(f, "json must be an object or array"),
+            SjsonError::SliceValueMustBeArray => write!(f, "slice value must be an array"),
             SjsonError::CannotSetArrayElementForNonNumericKey(key) => {
                 write!(f, "cannot set array element for non-numeric key '{}'", key)
             }
+            SjsonError::PathNotFound(segment) => write!(f, "path segment '{}' not found", segment),
+            SjsonError::ArrayIndexOutOfBounds { index, len } => {
+                write!(f, "array index {} out of bounds for length {}", index, len)
+            }
+            SjsonError::EditFailed { index, source } => write!(f, "edit {} failed: {}", index, source),
+            SjsonError::DuplicateKey(key) => write!(f, "duplicate key '{}'", key),
+            SjsonError::NumberOutOfRange(number) => write!(f, "number {} is out of range", number),
+            SjsonError::InvalidJson(e) => write!(f, "invalid json: {}", e),
+            SjsonError::Serialize(e) => write!(f, "failed to serialize: {}", e),
+            #[cfg(feature = "std")]
+            SjsonError::Io(e) => write!(f, "failed to write: {}", e),
             SjsonError::Custom(msg) => write!(f, "{}", msg),
         }
     }
 }
 
-impl std::error::Error for SjsonError {}
-
-/// Check if a path is optimistic (simple characters only)
-fn is_optimistic_path(path: &str) -> bool {
-    path.chars().all(|ch| {
-        ch >= '.' && ch <= 'z' && !(ch > '9' && ch < 'A') && ch <= 'z'
-    })
-}
-
-/// Find the position of a value in JSON string for optimistic replacement
-fn find_value_position(json: &str, path: &str) -> Option<(usize, usize)> {
-    // Simple implementation to find value position
-    // This is a basic version - a full implementation would need more sophisticated parsing
-    
-    let mut current_pos = 0;
-    let parts: Vec<&str> = path.split('.').collect();
-    
-    for (i, part) in parts.iter().enumerate() {
-        // Find the key
-        let key_pattern = format!("\"{}\":", part);
-        if let Some(key_pos) = json[current_pos..].find(&key_pattern) {
-            let key_start = current_pos + key_pos;
-            let value_start = key_start + key_pattern.len();
-            
-            // Skip whitespace
-            let value_start = value_start + json[value_start..]
-                .chars()
-                .take_while(|c| c.is_whitespace())
-                .map(|c| c.len_utf8())
-                .sum::<usize>();
-            
-            if i == parts.len() - 1 {
-                // This is the final part, find the end of the value
-                let value_end = find_value_end(&json[value_start..]);
-                return Some((value_start, value_start + value_end));
-            } else {
-                // Continue to next part
-                current_pos = value_start;
+impl PartialEq for SjsonError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (SjsonError::CannotSetArrayElementForNonNumericKey(a), SjsonError::CannotSetArrayElementForNonNumericKey(b))
+            | (SjsonError::PathNotFound(a), SjsonError::PathNotFound(b))
+            | (SjsonError::DuplicateKey(a), SjsonError::DuplicateKey(b))
+            | (SjsonError::NumberOutOfRange(a), SjsonError::NumberOutOfRange(b)) => a == b,
+            (
+                SjsonError::ArrayIndexOutOfBounds { index: a, len: a_len },
+                SjsonError::ArrayIndexOutOfBounds { index: b, len: b_len },
+            ) => a == b && a_len == b_len,
+            (
+                SjsonError::PathTypeMismatch { segment: a, found: a_found },
+                SjsonError::PathTypeMismatch { segment: b, found: b_found },
+            ) => a == b && a_found == b_found,
+            (
+                SjsonError::EditFailed { index: a, source: a_source },
+                SjsonError::EditFailed { index: b, source: b_source },
+            ) => a == b && a_source == b_source,
+            (SjsonError::InvalidJson(a), SjsonError::InvalidJson(b))
+            | (SjsonError::Serialize(a), SjsonError::Serialize(b)) => a.to_string() == b.to_string(),
+            #[cfg(feature = "std")]
+            (SjsonError::Io(a), SjsonError::Io(b)) => a.kind() == b.kind() && a.to_string() == b.to_string(),
+            (SjsonError::Custom(a), SjsonError::Custom(b)) => a == b,
+            // Every variant with data is compared above, what is left are
+            // the unit variants and mismatched pairs
+            (a, b) => {
+                core::mem::discriminant(a) == core::mem::discriminant(b)
+                    && matches!(a, SjsonError::EmptyPath | SjsonError::InvalidPath | SjsonError::NoChange
+                        | SjsonError::ComplexPathNotSupported | SjsonError::PathTooDeep
+                        | SjsonError::SpliceUnavailable | SjsonError::JsonMustBeObjectOrArray
+                        | SjsonError::SliceValueMustBeArray)
             }
-        } else {
-            return None;
         }
     }
-    
-    None
 }
 
-/// Find the end of a JSON value
-fn find_value_end(json: &str) -> usize {
-    let mut depth = 0;
-    let mut in_string = false;
-    let mut escape_next = false;
-    
-    for (i, ch) in json.chars().enumerate() {
-        if escape_next {
-            escape_next = false;
-            continue;
-        }
-        
-        match ch {
-            '"' if !escape_next => in_string = !in_string,
-            '\\' if in_string => escape_next = true,
-            '{' | '[' if !in_string => depth += 1,
-            '}' | ']' if !in_string => {
-                if depth > 0 {
-                    depth -= 1;
-                } else {
-                    return i + 1;
-                }
-            }
-            ',' if !in_string && depth == 0 => return i,
-            _ => {}
+impl Eq for SjsonError {}
+
+impl From<serde_json::Error> for SjsonError {
+    fn from(e: serde_json::Error) -> Self {
+        SjsonError::InvalidJson(Arc::new(e))
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SjsonError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SjsonError::EditFailed { source, .. } => Some(source.as_ref()),
+            SjsonError::InvalidJson(e) | SjsonError::Serialize(e) => Some(e.as_ref()),
+            SjsonError::Io(e) => Some(e.as_ref()),
+            _ => None,
         }
     }
-    
-    json.len()
 }
 
-/// Set sets a json value for the specified path.
-/// A path is in dot syntax, such as "name.last" or "age".
-/// This function expects that the json is well-formed, and does not validate.
-/// Invalid json will not panic, but it may return back unexpected results.
-/// An error is returned if the path is not valid.
-///
-/// A path is a series of keys separated by a dot.
-///
-/// ```json
-/// {
-///   "name": {"first": "Tom", "last": "Anderson"},
-///   "age": 37,
-///   "children": ["Sara","Alex","Jack"],
-///   "friends": [
-///     {"first": "James", "last": "Murphy"},
-///     {"first": "Roger", "last": "Craig"}
-///   ]
-/// }
-/// ```
-/// "name.last"          >> "Anderson"
-/// "age"                >> 37
-/// "children.1"         >> "Alex"
-pub fn set(json: &str, path: &str, value: &str) -> Result<String, SjsonError> {
-    set_options(json, path, value, None)
+/// ROOT_PATH addresses the whole document, so setting it replaces the root
+/// value, while the root can't be deleted and deleting it is
+/// `SjsonError::InvalidPath`. To address a key literally named "@this"
+/// escape it as `\@this`. A bare `$`, the JSONPath root, means the same and
+/// is escaped as `\$` for a key named "$".
+pub const ROOT_PATH: &str = "@this";
+
+/// The path separator used unless [`Options::separator`] says otherwise
+const DEFAULT_SEPARATOR: char = '.';
+
+/// The utf-8 byte order mark that editors on Windows may write at the start
+/// of a file
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// The path separator configured by `opts`
+fn separator(opts: Option<&Options>) -> char {
+    opts.map(|o| o.separator).unwrap_or(DEFAULT_SEPARATOR)
 }
 
-/// SetOptions sets a json value for the specified path with options.
-pub fn set_options(
-    json: &str,
-    path: &str,
-    value: &str,
-    opts: Option<&Options>,
-) -> Result<String, SjsonError> {
+/// Check if `c` has a meaning of its own in a path, so that it can't also
+/// be the separator
+fn is_path_syntax(c: char) -> bool {
+    matches!(c, '\\' | '#' | '*' | ':' | '@' | '$' | '(' | ')' | '=')
+}
+
+/// Split a path into its segments on `sep`.
+/// A backslash makes the next character literal, so with the default
+/// separator `user\.name` addresses the single key "user.name" and `\\` is
+/// a literal backslash.
+/// An empty segment, as left by a stray dot in `a.`, `.a` or `a..b`, is
+/// `SjsonError::InvalidPath` rather than silently addressing a "" key.
+/// Within a `#(key=value)` match the separator is part of the match, so
+/// `#(version=1.5)` stays a single segment.
+/// A leading `$` is a JSONPath style root and is dropped together with the
+/// separator after it, so `$.a.b` and `$a` split like `a.b` and `a`, and a
+/// bare `$` has no segments. A key starting with `$` is written `\$key`.
+fn split_path(path: &str, sep: char) -> Result<Vec<String>, SjsonError> {
     if path.is_empty() {
         return Err(SjsonError::EmptyPath);
     }
+    if is_path_syntax(sep) {
+        return Err(SjsonError::InvalidPath);
+    }
 
-    let optimistic = opts.map(|o| o.optimistic).unwrap_or(false);
+    let path = match path.strip_prefix('$') {
+        Some("") => return Ok(Vec::new()),
+        Some(rest) => rest.strip_prefix(sep).unwrap_or(rest),
+        None => path,
+    };
 
-    // Try optimistic path replacement if enabled
-    if optimistic && is_optimistic_path(path) {
-        if let Some((start, end)) = find_value_position(json, path) {
-            let mut result = String::with_capacity(json.len() - (end - start) + value.len() + 2);
-            result.push_str(&json[..start]);
-            
-            // Add quotes if the value is not already quoted and looks like a string
-            if !value.starts_with('"') && !value.starts_with('{') && !value.starts_with('[') 
-               && !value.parse::<f64>().is_ok() && value != "true" && value != "false" && value != "null" {
-                result.push('"');
-                result.push_str(value);
-                result.push('"');
-            } else {
-                result.push_str(value);
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut chars = path.chars();
+    let mut in_match = false;
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.next() {
+                Some(escaped) => current.push(escaped),
+                None => current.push('\\'),
+            },
+            '(' if current == "#" => {
+                in_match = true;
+                current.push(ch);
             }
-            
-            result.push_str(&json[end..]);
-            return Ok(result);
+            ')' if in_match => {
+                in_match = false;
+                current.push(ch);
+            }
+            _ if ch == sep && !in_match => parts.push(core::mem::take(&mut current)),
+            _ => current.push(ch),
         }
     }
+    parts.push(current);
 
-    // Fall back to full JSON parsing approach
-    let parsed = serde_json::from_str::<JsonValue>(json)
-        .map_err(|e| SjsonError::Custom(format!("Invalid JSON: {}", e)))?;
-
-    match set_simple_path(&parsed, path, value) {
-        Ok(new_value) => serde_json::to_string(&new_value)
-            .map_err(|e| SjsonError::Custom(format!("Failed to serialize: {}", e))),
-        Err(e) => Err(e),
+    if parts.iter().any(|part| part.is_empty()) {
+        return Err(SjsonError::InvalidPath);
     }
+
+    Ok(parts)
 }
 
-fn set_simple_path(json: &JsonValue, path: &str, value: &str) -> Result<JsonValue, SjsonError> {
-    let parts: Vec<&str> = path.split('.').collect();
-    if parts.is_empty() {
-        return Err(SjsonError::EmptyPath);
-    }
+/// Segment is a single step of a [`Path`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// An object key
+    Key(String),
+    /// An array index, negative indices count from the end. On an object it
+    /// is used as a key in its decimal form.
+    Index(i64),
+    /// The first element of an array that is an object whose `key` holds
+    /// `value`, written `#(key=value)`. An unquoted numeric `value` also
+    /// matches the number, a quoted one only matches the string.
+    Match { key: String, value: String },
+    /// The position after the last element of an array, written `#`.
+    /// Setting it appends, creating the array when the value is missing or
+    /// null.
+    Append,
+    /// The elements from `start` up to but not including `end`, written
+    /// `start:end`. Only the last segment of a path is read as a slice.
+    /// Setting it replaces those elements with the ones of an array value
+    /// and deleting it removes them. An end past the last element is
+    /// clamped to the length of the array. On an object it is used as a key
+    /// in its written form.
+    Slice { start: usize, end: usize },
+    /// Every child of an object or array, written `*`. The rest of the path
+    /// is applied to each of them, see `Options::strict_wildcards` for the
+    /// children it can't step into.
+    Wildcard,
+}
 
-    let mut result = json.clone();
-    let mut current = &mut result;
+impl Segment {
+    /// Classify a split path part. Only the canonical decimal form of an
+    /// integer becomes an index, so "007" keeps addressing the key "007".
+    /// A `start:end` slice is only recognized when the part is `terminal`.
+    fn from_part(part: String, terminal: bool) -> Segment {
+        if part == "#" {
+            return Segment::Append;
+        }
+        if part == "*" {
+            return Segment::Wildcard;
+        }
 
-    // Navigate to the parent of the target
-    for i in 0..parts.len() - 1 {
-        let part = parts[i];
-        match current {
-            JsonValue::Object(map) => {
-                if !map.contains_key(part) {
-                    map.insert(part.to_string(), JsonValue::Object(serde_json::Map::new()));
-                }
-                current = map.get_mut(part).unwrap();
-            }
-            JsonValue::Array(arr) => {
-                let index = parse_array_index(part, arr.len())?;
-                if index >= arr.len() {
-                    // Extend array with null values
-                    while arr.len() <= index {
-                        arr.push(JsonValue::Null);
-                    }
-                }
-                current = &mut arr[index];
-            }
-            _ => {
-                // Convert to object if needed
-                *current = JsonValue::Object(serde_json::Map::new());
-                if let JsonValue::Object(map) = current {
-                    map.insert(part.to_string(), JsonValue::Object(serde_json::Map::new()));
-                    current = map.get_mut(part).unwrap();
-                }
+        if let Some((start, end)) = part.split_once(':').filter(|_| terminal) {
+            let bound = |b: &str| b.parse::<usize>().ok().filter(|n| n.to_string() == b);
+            if let (Some(start), Some(end)) = (bound(start), bound(end)) {
+                return Segment::Slice { start, end };
             }
         }
+
+        let matcher = part.strip_prefix("#(").and_then(|p| p.strip_suffix(')'));
+        if let Some((key, value)) = matcher.and_then(|m| m.split_once('=')) {
+            return Segment::Match { key: key.to_string(), value: value.to_string() };
+        }
+
+        match part.parse::<i64>() {
+            Ok(index) if index.to_string() == part => Segment::Index(index),
+            _ => Segment::Key(part),
+        }
     }
 
-    // Set the final value
-    let final_part = parts.last().unwrap();
-    let json_value = parse_value(value);
-    
-    match current {
-        JsonValue::Object(map) => {
-            map.insert(final_part.to_string(), json_value);
+    /// Classify a literal key given by the caller. Nothing is unescaped or
+    /// treated as a match or append, only a canonical integer becomes an index.
+    fn literal(part: &str) -> Segment {
+        match part.parse::<i64>() {
+            Ok(index) if index.to_string() == part => Segment::Index(index),
+            _ => Segment::Key(part.to_string()),
         }
-        JsonValue::Array(arr) => {
-            let index = parse_array_index(final_part, arr.len())?;
-            if index >= arr.len() {
-                // Extend array with null values
-                while arr.len() <= index {
-                    arr.push(JsonValue::Null);
-                }
+    }
+
+    /// The object key this segment addresses. A match only selects array
+    /// elements and a wildcard is fanned out before it gets here, so on an
+    /// object there is nothing to change, and an append
+    /// can't be applied to an object at all.
+    fn key(&self) -> Result<Cow<'_, str>, SjsonError> {
+        match self {
+            Segment::Key(key) => Ok(Cow::Borrowed(key)),
+            Segment::Index(index) => Ok(Cow::Owned(index.to_string())),
+            Segment::Slice { .. } => Ok(Cow::Owned(self.to_string())),
+            Segment::Match { .. } | Segment::Wildcard => Err(SjsonError::NoChange),
+            Segment::Append => Err(SjsonError::PathTypeMismatch { segment: self.to_string(), found: "object" }),
+        }
+    }
+
+    /// Like `key`, but with `strict_indices` an index is refused instead of
+    /// being taken as the key of an object member.
+    fn object_key(&self, strict_indices: bool) -> Result<Cow<'_, str>, SjsonError> {
+        match self {
+            Segment::Index(_) | Segment::Slice { .. } if strict_indices => {
+                Err(SjsonError::PathTypeMismatch { segment: self.to_string(), found: "object" })
             }
-            arr[index] = json_value;
+            _ => self.key(),
         }
-        _ => {
-            // Convert to object if needed
-            *current = JsonValue::Object(serde_json::Map::new());
-            if let JsonValue::Object(map) = current {
-                map.insert(final_part.to_string(), json_value);
+    }
+
+    /// The element this segment addresses in `arr`. An index may point past
+    /// the end and an append always does, while a match without a matching
+    /// element is `SjsonError::NoChange`. A slice addresses a range rather
+    /// than an element, see `slice_range`.
+    fn element_index<V: Tree>(&self, arr: &[V]) -> Result<usize, SjsonError> {
+        match self {
+            Segment::Key(key) => parse_array_index(key, arr.len()),
+            Segment::Index(index) => resolve_array_index(*index, arr.len()),
+            Segment::Match { key, value } => {
+                let candidates = match_candidates::<V>(value);
+                arr.iter()
+                    .position(|element| match element.node() {
+                        Node::Object(map) => map.get(key).is_some_and(|v| candidates.contains(v)),
+                        _ => false,
+                    })
+                    .ok_or(SjsonError::NoChange)
             }
+            Segment::Append => Ok(arr.len()),
+            Segment::Slice { .. } | Segment::Wildcard => Err(SjsonError::InvalidPath),
         }
     }
 
-    Ok(result)
+    /// The range of elements a slice addresses in an array of `len`, with
+    /// both bounds clamped to `len`. A start after the end is
+    /// `SjsonError::InvalidPath`.
+    fn slice_range(start: usize, end: usize, len: usize) -> Result<core::ops::Range<usize>, SjsonError> {
+        if start > end {
+            return Err(SjsonError::InvalidPath);
+        }
+        Ok(start.min(len)..end.min(len))
+    }
 }
 
-fn parse_value(value: &str) -> JsonValue {
-    // Try to parse as different types
-    if value == "true" {
-        JsonValue::Bool(true)
-    } else if value == "false" {
-        JsonValue::Bool(false)
-    } else if value == "null" {
-        JsonValue::Null
-    } else if let Ok(num) = value.parse::<i64>() {
-        JsonValue::Number(serde_json::Number::from(num))
-    } else if let Ok(num) = value.parse::<f64>() {
-        if let Some(n) = serde_json::Number::from_f64(num) {
-            JsonValue::Number(n)
-        } else {
-            JsonValue::String(value.to_string())
-        }
-    } else {
-        // Try to parse as JSON if it looks like JSON
-        if (value.starts_with('[') && value.ends_with(']')) || 
-           (value.starts_with('{') && value.ends_with('}')) {
-            if let Ok(json_value) = serde_json::from_str::<JsonValue>(value) {
-                return json_value;
-            }
+impl fmt::Display for Segment {
+    /// Write the segment the way it is written in a path
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Segment::Key(key) => write!(f, "{}", key),
+            Segment::Index(index) => write!(f, "{}", index),
+            Segment::Match { key, value } => write!(f, "#({}={})", key, value),
+            Segment::Append => write!(f, "#"),
+            Segment::Slice { start, end } => write!(f, "{}:{}", start, end),
+            Segment::Wildcard => write!(f, "*"),
         }
-        JsonValue::String(value.to_string())
     }
 }
 
-/// SetRaw sets a raw json value for the specified path.
-/// This function works the same as Set except that the value is set as a
-/// raw block of json. This allows for setting premarshalled json objects.
-pub fn set_raw(json: &str, path: &str, value: &str) -> Result<String, SjsonError> {
-    set_raw_options(json, path, value, None)
+/// The values a `#(key=value)` literal is equal to: the string, without
+/// its quotes if it has them, and for an unquoted number the tree can hold
+/// also the number
+fn match_candidates<V: Tree>(literal: &str) -> Vec<V> {
+    if let Some(unquoted) = literal.strip_prefix('"').and_then(|l| l.strip_suffix('"')) {
+        return vec![V::from_json(JsonValue::String(unquoted.to_string()))];
+    }
+
+    let mut candidates = vec![V::from_json(JsonValue::String(literal.to_string()))];
+    if let Some(number) = is_json_number(literal).then(|| parse_number(literal).ok()).flatten() {
+        candidates.push(V::from_json(number));
+    }
+    candidates
 }
 
-/// SetRawOptions sets a raw json value for the specified path with options.
-pub fn set_raw_options(
-    json: &str,
-    path: &str,
-    value: &str,
-    opts: Option<&Options>,
-) -> Result<String, SjsonError> {
-    let optimistic = opts.map(|o| o.optimistic).unwrap_or(false);
+/// Path is a parsed path that can be reused across calls without splitting
+/// and validating the string again, see [`set_path`] and [`delete_path`].
+/// It is `Send` and `Sync`, so parsed paths can be cached and shared
+/// between threads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path {
+    segments: Vec<Segment>,
+}
 
-    // Try optimistic path replacement if enabled
-    if optimistic && is_optimistic_path(path) {
-        if let Some((start, end)) = find_value_position(json, path) {
-            let mut result = String::with_capacity(json.len() - (end - start) + value.len());
-            result.push_str(&json[..start]);
-            result.push_str(value);
-            result.push_str(&json[end..]);
-            return Ok(result);
+impl Path {
+    /// Parse a path using the same syntax as [`set`]. [`ROOT_PATH`] parses to
+    /// a path without segments. An empty path is `SjsonError::EmptyPath` and
+    /// a path with an empty segment, such as `a..b`, is
+    /// `SjsonError::InvalidPath`.
+    pub fn parse(path: &str) -> Result<Path, SjsonError> {
+        if path == ROOT_PATH {
+            return Ok(Path { segments: Vec::new() });
         }
+
+        Ok(Path { segments: path_segments(path, DEFAULT_SEPARATOR)? })
     }
 
-    // Parse the raw value as JSON
-    let json_value = serde_json::from_str::<JsonValue>(value)
-        .map_err(|e| SjsonError::Custom(format!("Invalid JSON value: {}", e)))?;
+    /// The segments of the path, empty for [`ROOT_PATH`]
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+}
 
-    // Parse the original JSON
-    let parsed = serde_json::from_str::<JsonValue>(json)
-        .map_err(|e| SjsonError::Custom(format!("Invalid JSON: {}", e)))?;
+/// Split a path string into segments on `sep`
+fn path_segments(path: &str, sep: char) -> Result<Vec<Segment>, SjsonError> {
+    let parts = split_path(path, sep)?;
+    let last = parts.len().saturating_sub(1);
+    Ok(parts.into_iter().enumerate().map(|(i, part)| Segment::from_part(part, i == last)).collect())
+}
 
-    // Set the value
-    let parts: Vec<&str> = path.split('.').collect();
-    if parts.is_empty() {
+/// Turn already split keys into segments. Like a path string, no keys is
+/// `SjsonError::EmptyPath` and an empty key is `SjsonError::InvalidPath`.
+fn literal_segments(keys: &[&str]) -> Result<Vec<Segment>, SjsonError> {
+    if keys.is_empty() {
         return Err(SjsonError::EmptyPath);
     }
+    if keys.iter().any(|key| key.is_empty()) {
+        return Err(SjsonError::InvalidPath);
+    }
+    Ok(keys.iter().map(|key| Segment::literal(key)).collect())
+}
 
-    let mut result = parsed.clone();
-    let mut current = &mut result;
+/// Check if a path is optimistic (simple characters only).
+/// Only ascii letters, digits, `_`, `-`, `.` and the `sep` separator are safe
+/// for the byte scan, anything else such as escapes goes through the parser.
+/// Paths with empty segments are left to the parser to reject.
+fn is_optimistic_path(path: &str, sep: char) -> bool {
+    !is_path_syntax(sep)
+        && path.chars().all(|c| c == sep || c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        && !path.split(sep).any(str::is_empty)
+}
 
-    // Navigate to the parent of the target
-    for i in 0..parts.len() - 1 {
-        let part = parts[i];
-        match current {
-            JsonValue::Object(map) => {
-                if !map.contains_key(part) {
-                    map.insert(part.to_string(), JsonValue::Object(serde_json::Map::new()));
-                }
-                current = map.get_mut(part).unwrap();
-            }
-            JsonValue::Array(arr) => {
-                let index = parse_array_index(part, arr.len())?;
-                if index >= arr.len() {
-                    while arr.len() <= index {
-                        arr.push(JsonValue::Null);
-                    }
-                }
-                current = &mut arr[index];
-            }
-            _ => {
-                *current = JsonValue::Object(serde_json::Map::new());
-                if let JsonValue::Object(map) = current {
-                    map.insert(part.to_string(), JsonValue::Object(serde_json::Map::new()));
-                    current = map.get_mut(part).unwrap();
-                }
-            }
-        }
+/// Check if the optimistic fast path may be used for `path`
+fn use_optimistic(path: &str, opts: Option<&Options>) -> bool {
+    opts.is_some_and(|o| o.optimistic || o.preserve_untouched) && splice_possible(path, opts)
+}
+
+/// Check if an edit of `path` could be made by splicing the document text,
+/// whether or not the optimistic fast path was asked for
+fn splice_possible(path: &str, opts: Option<&Options>) -> bool {
+    // Byte offsets found in a document with comments don't survive stripping
+    if cfg!(feature = "lenient") {
+        return false;
     }
 
-    // Set the final value
-    let final_part = parts.last().unwrap();
-    
-    match current {
-        JsonValue::Object(map) => {
-            map.insert(final_part.to_string(), json_value);
-        }
-        JsonValue::Array(arr) => {
-            let index = parse_array_index(final_part, arr.len())?;
-            if index >= arr.len() {
-                while arr.len() <= index {
-                    arr.push(JsonValue::Null);
-                }
-            }
-            arr[index] = json_value;
-        }
-        _ => {
-            *current = JsonValue::Object(serde_json::Map::new());
-            if let JsonValue::Object(map) = current {
-                map.insert(final_part.to_string(), json_value);
-            }
-        }
+    #[cfg(feature = "std")]
+    if opts.is_some_and(|o| o.pretty.is_some() || !o.compact || o.sort_keys || o.escape_non_ascii || o.escape_slashes) {
+        return false;
     }
 
-    serde_json::to_string(&result)
-        .map_err(|e| SjsonError::Custom(format!("Failed to serialize: {}", e)))
+    // A path that is too deep is left to the parser path to reject
+    let within_depth = |o: &Options| o.max_depth.is_none_or(|max| path.split(o.separator).count() <= max);
+    // The byte scan takes any segment as a key, so strict indices need the
+    // parser to tell objects from arrays
+    let strict = |o: &Options| o.strict_indices && path.split(o.separator).any(|p| matches!(Segment::literal(p), Segment::Index(_)));
+    match opts {
+        Some(o) => is_optimistic_path(path, o.separator) && within_depth(o) && !strict(o),
+        None => is_optimistic_path(path, DEFAULT_SEPARATOR),
+    }
 }
 
-/// Delete deletes a value from json for the specified path.
-pub fn delete(json: &str, path: &str) -> Result<String, SjsonError> {
-    delete_options(json, path, None)
+/// Try to replace an existing string, number, bool or null with the scalar
+/// `text`, the json of a scalar from [`scalar_text`], by splicing, so that
+/// an edit without `optimistic` keeps the order and layout of the document
+/// like one with it. The value is written exactly as the parser path would
+/// write it, and the result is parsed in full, so `None` leaves anything the
+/// splice can't do soundly, including a document with repeated keys, to the
+/// parser.
+fn scalar_splice(json: &[u8], path: &str, text: &str, opts: Option<&Options>) -> Option<Vec<u8>> {
+    let result = scalar_splice_at(json, path, text, opts)?.apply_bytes(json);
+    splice_sound(&result).then_some(result)
 }
 
-/// DeleteOptions deletes a value from json for the specified path with options.
-pub fn delete_options(json: &str, path: &str, opts: Option<&Options>) -> Result<String, SjsonError> {
-    if path.is_empty() {
-        return Err(SjsonError::EmptyPath);
+/// The splice [`scalar_splice`] makes, before its result is checked with
+/// [`splice_sound`]
+fn scalar_splice_at(json: &[u8], path: &str, text: &str, opts: Option<&Options>) -> Option<Splice> {
+    if !splice_possible(path, opts) || is_append_path(path, opts) {
+        return None;
     }
 
-    let optimistic = opts.map(|o| o.optimistic).unwrap_or(false);
+    let sep = separator(opts);
+    let (start, end) = match lookup_member(json, path, sep) {
+        Some(KeyLookup::Found { value_start, value_end, .. }) => (value_start, value_end),
+        Some(KeyLookup::Missing { .. }) => return None,
+        None => find_element_span(json, path, sep)?,
+    };
+    if matches!(json[start], b'{' | b'[') {
+        return None;
+    }
 
-    // Try optimistic path deletion if enabled
-    if optimistic && is_optimistic_path(path) {
-        if let Some((start, end)) = find_value_position(json, path) {
-            // Find the key start position
-            let key_pattern = format!("\"{}\":", path.split('.').last().unwrap());
-            let key_start = json[..start].rfind(&key_pattern).unwrap_or(start);
-            
-            // Check if we need to remove a comma before the key
-            let mut result = String::with_capacity(json.len() - (end - key_start));
-            
-            // Check if there's a comma before the key that we need to remove
-            let mut actual_start = key_start;
-            if key_start > 0 {
-                // Look backwards for comma and whitespace
-                let mut pos = key_start - 1;
-                while pos > 0 && json[pos..].chars().next().map_or(false, |c| c.is_whitespace()) {
-                    pos -= 1;
-                }
-                if pos > 0 && json[pos..].starts_with(',') {
-                    actual_start = pos;
-                    // Also remove whitespace before comma
-                    while actual_start > 0 && json[actual_start-1..].chars().next().map_or(false, |c| c.is_whitespace()) {
-                        actual_start -= 1;
-                    }
-                }
-            }
-            
-            result.push_str(&json[..actual_start]);
-            
-            // Skip comma and whitespace after the deleted value
-            let mut skip_pos = end;
-            // Skip whitespace first
-            while skip_pos < json.len() && json[skip_pos..].chars().next().map_or(false, |c| c.is_whitespace()) {
-                skip_pos += 1;
-            }
-            // Then skip comma if present
-            if skip_pos < json.len() && json[skip_pos..].starts_with(',') {
-                skip_pos += 1;
-                // Skip whitespace after comma
-                while skip_pos < json.len() && json[skip_pos..].chars().next().map_or(false, |c| c.is_whitespace()) {
-                    skip_pos += 1;
-                }
-            }
-            
-            // Include everything after the deleted value
-            result.push_str(&json[end..]);
-            return Ok(result);
-        }
+    let splice = Splice { start, end, value: text.to_string() };
+    splice.within(json)?.unless_unchanged(json, opts)
+}
+
+/// Check that the result of a scalar splice is well-formed json without
+/// repeated keys, which the parser path would have resolved
+fn splice_sound(result: &[u8]) -> bool {
+    matches!(duplicates::find_duplicate(result), Ok(None))
+}
+
+/// [`scalar_splice`] for a document held in a string
+fn scalar_splice_str(json: &str, path: &str, text: &str, opts: Option<&Options>) -> Option<String> {
+    let result = scalar_splice(json.as_bytes(), path, text, opts)?;
+    Some(String::from_utf8(result).expect("splicing utf-8 into utf-8 is utf-8"))
+}
+
+/// The json the default splice writes for `value`, the text given to a set
+/// function, or `None` when it isn't a scalar. A number the parser path
+/// can't hold keeps its digits, so it is still set as a number.
+fn scalar_text(value: &str, opts: Option<&Options>) -> Option<String> {
+    match parse_value::<JsonValue>(value, opts) {
+        Ok(value) => json_scalar_text(&value),
+        // Only a valid json number fails to parse
+        Err(_) => Some(value.to_string()),
+    }
+}
+
+/// The json of `value` when it is a string, number, bool or null
+fn json_scalar_text(value: &JsonValue) -> Option<String> {
+    if value.is_object() || value.is_array() {
+        return None;
     }
+    serde_json::to_string(value).ok()
+}
 
-    let parsed = serde_json::from_str::<JsonValue>(json)
-        .map_err(|e| SjsonError::Custom(format!("Invalid JSON: {}", e)))?;
+/// Check that `segments` doesn't exceed the configured `max_depth`
+fn check_depth(segments: &[Segment], opts: Option<&Options>) -> Result<(), SjsonError> {
+    match opts.and_then(|o| o.max_depth) {
+        Some(max_depth) if segments.len() > max_depth => Err(SjsonError::PathTooDeep),
+        _ => Ok(()),
+    }
+}
 
-    let parts: Vec<&str> = path.split('.').collect();
-    if parts.is_empty() {
-        return Err(SjsonError::EmptyPath);
+/// The layout to pretty print with, or `None` for compact output
+#[cfg(feature = "std")]
+fn pretty_layout(opts: Option<&Options>) -> Option<Cow<'_, PrettyOptions>> {
+    let opts = opts?;
+    match &opts.pretty {
+        Some(pretty) => Some(Cow::Borrowed(pretty)),
+        None if !opts.compact => Some(Cow::Owned(PrettyOptions::default())),
+        None => None,
     }
+}
 
-    let mut result = parsed.clone();
-    let mut current = &mut result;
+/// Serialize the result of the parser path into `writer`, pretty printed
+/// when configured by `pretty` or `compact`, with sorted keys when
+/// configured by `sort_keys` and with the escapes `escape_non_ascii` and
+/// `escape_slashes` ask for.
+/// serde_json only exposes its writers and formatters with `std`.
+#[cfg(feature = "std")]
+fn write_json<W: std::io::Write, V: Tree>(writer: W, value: &mut V, opts: Option<&Options>) -> Result<(), SjsonError> {
+    let pretty = pretty_layout(opts);
+    if opts.is_some_and(|o| o.sort_keys) || pretty.as_ref().is_some_and(|p| p.sort_keys) {
+        value.sort_all_objects();
+    }
 
-    // Navigate to the parent of the target
-    for i in 0..parts.len() - 1 {
-        let part = parts[i];
-        match current {
-            JsonValue::Object(map) => {
-                if !map.contains_key(part) {
-                    return Err(SjsonError::NoChange);
-                }
-                current = map.get_mut(part).unwrap();
-            }
-            JsonValue::Array(arr) => {
-                let index = parse_array_index(part, arr.len())?;
-                if index >= arr.len() {
-                    return Err(SjsonError::NoChange);
-                }
-                current = &mut arr[index];
-            }
-            _ => {
-                return Err(SjsonError::NoChange);
-            }
+    match pretty {
+        Some(pretty) => {
+            let formatter = serde_json::ser::PrettyFormatter::with_indent(pretty.indent.as_bytes());
+            serialize_with(writer, value, formatter, opts)
         }
+        None => serialize_with(writer, value, serde_json::ser::CompactFormatter, opts),
     }
+}
 
-    // Delete the final value
-    let final_part = parts.last().unwrap();
-    
-    match current {
-        JsonValue::Object(map) => {
-            if map.remove(&final_part.to_string()).is_none() {
-                return Err(SjsonError::NoChange);
-            }
-        }
-        JsonValue::Array(arr) => {
-            let index = parse_array_index(final_part, arr.len())?;
-            if index >= arr.len() {
-                return Err(SjsonError::NoChange);
-            }
-            arr.remove(index);
-        }
-        _ => {
-            return Err(SjsonError::NoChange);
-        }
+/// Serialize `value` with `formatter`, wrapped to escape more of each string
+/// when configured by `escape_non_ascii` or `escape_slashes`
+#[cfg(feature = "std")]
+fn serialize_with<W, V, F>(writer: W, value: &V, formatter: F, opts: Option<&Options>) -> Result<(), SjsonError>
+where
+    W: std::io::Write,
+    V: Tree,
+    F: serde_json::ser::Formatter,
+{
+    let non_ascii = opts.is_some_and(|o| o.escape_non_ascii);
+    let slashes = opts.is_some_and(|o| o.escape_slashes);
+    if non_ascii || slashes {
+        let formatter = escape::Escaping::new(formatter, non_ascii, slashes);
+        let mut serializer = serde_json::Serializer::with_formatter(writer, formatter);
+        return serde::Serialize::serialize(value, &mut serializer).map_err(write_error);
     }
 
-    serde_json::to_string(&result)
-        .map_err(|e| SjsonError::Custom(format!("Failed to serialize: {}", e)))
+    let mut serializer = serde_json::Serializer::with_formatter(writer, formatter);
+    serde::Serialize::serialize(value, &mut serializer).map_err(write_error)
 }
 
-/// Set a boolean value
-pub fn set_bool(json: &str, path: &str, value: bool, opts: Option<&Options>) -> Result<String, SjsonError> {
-    let raw = if value { "true" } else { "false" };
-    set_options(json, path, raw, opts)
+/// Tell a failing writer apart from a value that can't be serialized
+#[cfg(feature = "std")]
+fn write_error(e: serde_json::Error) -> SjsonError {
+    if e.is_io() {
+        SjsonError::Io(Arc::new(e.into()))
+    } else {
+        SjsonError::Serialize(Arc::new(e))
+    }
+}
+
+/// Serialize the result of the parser path into a string.
+/// `size_hint` estimates the output length, such as the input length plus
+/// the new value, so the buffer rarely has to grow while writing.
+fn to_json_string<V: Tree>(value: &mut V, opts: Option<&Options>, size_hint: usize) -> Result<String, SjsonError> {
+    let buf = to_json_vec(value, opts, size_hint)?;
+    Ok(String::from_utf8(buf).expect("serde_json writes valid utf-8"))
+}
+
+/// Serialize the result of the parser path into `buf`, replacing what it
+/// held but keeping its allocation, see [`to_json_string`]
+fn to_json_into<V: Tree>(buf: &mut String, value: &mut V, opts: Option<&Options>, size_hint: usize) -> Result<(), SjsonError> {
+    #[cfg(feature = "std")]
+    {
+        let mut bytes = core::mem::take(buf).into_bytes();
+        bytes.clear();
+        bytes.reserve(size_hint);
+        let written = write_json(&mut bytes, value, opts);
+        if written.is_err() {
+            bytes.clear();
+        }
+        *buf = String::from_utf8(bytes).expect("serde_json writes valid utf-8");
+        written
+    }
+
+    // Without `std` serde_json can only write into its own buffer
+    #[cfg(not(feature = "std"))]
+    {
+        let json = to_json_string(value, opts, size_hint)?;
+        buf.clear();
+        buf.push_str(&json);
+        Ok(())
+    }
+}
+
+/// Serialize the result of the parser path into bytes, see [`to_json_string`]
+#[cfg_attr(not(feature = "std"), allow(unused_variables))]
+fn to_json_vec<V: Tree>(value: &mut V, opts: Option<&Options>, size_hint: usize) -> Result<Vec<u8>, SjsonError> {
+    #[cfg(feature = "std")]
+    {
+        let mut buf = Vec::with_capacity(size_hint);
+        write_json(&mut buf, value, opts)?;
+        Ok(buf)
+    }
+
+    // Without `std` serde_json can only write into its own buffer
+    #[cfg(not(feature = "std"))]
+    serde_json::to_vec(value).map_err(|e| SjsonError::Serialize(Arc::new(e)))
+}
+
+/// Find the end of the string literal whose opening quote is at `start`,
+/// returning the position just past the closing quote
+fn find_string_end(json: &[u8], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    while i < json.len() {
+        match json[i] {
+            b'\\' => i += 2,
+            b'"' => return Some(i + 1),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Skip json whitespace starting at `pos`
+fn skip_whitespace(json: &[u8], pos: usize) -> usize {
+    pos + json.get(pos..).map_or(0, |rest| rest.iter().take_while(|b| b.is_ascii_whitespace()).count())
+}
+
+/// The result of looking up a key among the members of one object
+enum KeyLookup {
+    /// The member exists: the position of its key's opening quote and the
+    /// start and end of its value
+    Found { key_start: usize, value_start: usize, value_end: usize },
+    /// The object has no such member. A new one goes at `insert_at`, just
+    /// past the last member, or past the `{` when the object is empty.
+    Missing { insert_at: usize, is_empty: bool },
+}
+
+/// Look up the member named `key` of the object whose `{` is at `start`.
+/// Only the object's own members are compared and their values are skipped
+/// as a whole, so neither text inside strings nor keys of nested objects
+/// can match. Keys written with escapes such as `\u0061ge` are decoded before
+/// they are compared. Returns `None` when there is no object at `start` or
+/// when it is malformed.
+/// The whole object is scanned, and a key that appears more than once is
+/// also `None`, leaving `Options::on_duplicate_key` to the parser.
+fn find_key(json: &[u8], start: usize, key: &str) -> Option<KeyLookup> {
+    if json.get(start) != Some(&b'{') {
+        return None;
+    }
+
+    let mut pos = skip_whitespace(json, start + 1);
+    if json.get(pos) == Some(&b'}') {
+        return Some(KeyLookup::Missing { insert_at: start + 1, is_empty: true });
+    }
+
+    let mut found = None;
+    loop {
+        if json.get(pos) != Some(&b'"') {
+            return None;
+        }
+        // An unterminated string is ambiguous, leave it to the parser
+        let key_end = find_string_end(json, pos)?;
+        let name = &json[pos + 1..key_end - 1];
+        let is_key = if name.contains(&b'\\') {
+            serde_json::from_slice::<String>(&json[pos..key_end]).ok()? == key
+        } else {
+            name == key.as_bytes()
+        };
+
+        let colon = skip_whitespace(json, key_end);
+        if json.get(colon) != Some(&b':') {
+            return None;
+        }
+        let value_start = skip_whitespace(json, colon + 1);
+        let delimiter = value_start + find_value_end(json.get(value_start..)?)?;
+        // An empty value means the object is malformed
+        let value_end = value_start + json[value_start..delimiter].iter().rposition(|b| !b.is_ascii_whitespace())? + 1;
+
+        if is_key {
+            if found.is_some() {
+                return None;
+            }
+            found = Some(KeyLookup::Found { key_start: pos, value_start, value_end });
+        }
+
+        match json.get(delimiter)? {
+            b',' => pos = skip_whitespace(json, delimiter + 1),
+            b'}' if found.is_some() => return found,
+            b'}' => return Some(KeyLookup::Missing { insert_at: value_end, is_empty: false }),
+            _ => return None,
+        }
+    }
+}
+
+/// Look up the final segment of `path` in the object its parents lead to.
+/// The scan steps through object members and into existing array elements
+/// by index. A parent that doesn't exist is left to the parser.
+fn lookup_member(json: &[u8], path: &str, sep: char) -> Option<KeyLookup> {
+    let (parents, last) = match path.rsplit_once(sep) {
+        Some((parents, last)) => (Some(parents), last),
+        None => (None, path),
+    };
+
+    let mut value_start = skip_whitespace(json, 0);
+    for part in parents.into_iter().flat_map(|parents| parents.split(sep)) {
+        value_start = match json.get(value_start)? {
+            b'[' => find_nth_element(json, value_start, part)?,
+            _ => match find_key(json, value_start, part)? {
+                KeyLookup::Found { value_start, .. } => value_start,
+                KeyLookup::Missing { .. } => return None,
+            },
+        };
+    }
+
+    find_key(json, value_start, last)
+}
+
+/// Find the start of the element `part` addresses in the array whose `[` is
+/// at `start`. Elements before it are skipped without collecting them, only
+/// a negative index needs the length of the array.
+fn find_nth_element(json: &[u8], start: usize, part: &str) -> Option<usize> {
+    let index = part.parse::<i64>().ok()?;
+    if index < 0 {
+        let elements = array_elements(json, start)?;
+        let index = resolve_array_index(index, elements.len()).ok()?;
+        return elements.get(index).map(|&(start, _)| start);
+    }
+
+    let mut pos = skip_whitespace(json, start + 1);
+    if json.get(pos) == Some(&b']') {
+        return None;
+    }
+    for _ in 0..index {
+        let end = pos + find_value_end(json.get(pos..)?)?;
+        if json.get(end)? != &b',' {
+            return None;
+        }
+        pos = skip_whitespace(json, end + 1);
+    }
+    Some(pos)
+}
+
+/// Find the member addressed by `path`, returning the position of its key
+/// and the start and end of its value
+fn find_member(json: &[u8], path: &str, sep: char) -> Option<(usize, usize, usize)> {
+    match lookup_member(json, path, sep)? {
+        KeyLookup::Found { key_start, value_start, value_end } => Some((key_start, value_start, value_end)),
+        KeyLookup::Missing { .. } => None,
+    }
+}
+
+/// Find the position of a value in JSON string for optimistic replacement.
+/// Each segment only matches a member of the object the previous one led
+/// to, so `age` never finds the `age` of a nested object that comes first.
+fn find_value_position(json: &[u8], path: &str, sep: char) -> Option<(usize, usize)> {
+    find_member(json, path, sep).map(|(_, start, end)| (start, end))
+}
+
+/// Find the end of a JSON value, that is the byte offset of the `,`, `}` or `]`
+/// that terminates it. Returns `None` if the json ends before the value does.
+/// The scan is done over bytes: every delimiter is ASCII and multibyte UTF-8
+/// sequences never contain ASCII bytes, so the offset is always a char boundary.
+///
+/// Brackets have to close the container they opened, and nothing but
+/// whitespace may follow the value before its delimiter, so that a malformed
+/// document such as `{"a":{"b":1}"c":2}` is `None` rather than a span that
+/// swallows the members after it.
+fn find_value_end(json: &[u8]) -> Option<usize> {
+    // The closing brackets of the containers the scan is in
+    let mut closers = Vec::new();
+    let mut in_string = false;
+    let mut escape_next = false;
+    let mut started = false;
+    let mut ended = false;
+
+    for (i, &b) in json.iter().enumerate() {
+        if escape_next {
+            escape_next = false;
+            continue;
+        }
+        if in_string {
+            match b {
+                b'"' => {
+                    in_string = false;
+                    ended = closers.is_empty();
+                }
+                b'\\' => escape_next = true,
+                _ => {}
+            }
+            continue;
+        }
+
+        match b {
+            b',' | b'}' | b']' if closers.is_empty() => return Some(i),
+            b'}' | b']' => {
+                if closers.pop() != Some(b) {
+                    return None;
+                }
+                ended = closers.is_empty();
+            }
+            _ if b.is_ascii_whitespace() => {
+                ended |= started && closers.is_empty();
+                continue;
+            }
+            // Only a scalar continues at the top, and only up to whitespace
+            _ if ended || (started && closers.is_empty() && matches!(b, b'"' | b'{' | b'[')) => return None,
+            b'"' => in_string = true,
+            b'{' => closers.push(b'}'),
+            b'[' => closers.push(b']'),
+            _ => {}
+        }
+        started = true;
+    }
+
+    None
+}
+
+/// Splice is an edit produced by the optimistic path: the bytes in
+/// `start..end` of the input are replaced with `value`.
+struct Splice {
+    start: usize,
+    end: usize,
+    value: String,
+}
+
+impl Splice {
+    /// Keep the splice only if it lies within `json` and starts and ends on
+    /// char boundaries, so applying it can't panic or split a character.
+    /// Every offset the scan produces should pass, anything else is left to
+    /// the parser rather than trusted.
+    fn within(self, json: &[u8]) -> Option<Splice> {
+        // Continuation bytes of multibyte UTF-8 sequences are 0b10xxxxxx
+        let is_boundary = |i: usize| json.get(i).is_none_or(|&b| b & 0xC0 != 0x80);
+        let fits = self.start <= self.end && self.end <= json.len();
+        (fits && is_boundary(self.start) && is_boundary(self.end)).then_some(self)
+    }
+
+    fn apply_str(&self, json: &str) -> String {
+        let mut result = String::new();
+        self.apply_into(json, &mut result);
+        result
+    }
+
+    /// Write the spliced json into `buf`, replacing what it held but
+    /// keeping its allocation
+    fn apply_into(&self, json: &str, buf: &mut String) {
+        buf.clear();
+        buf.reserve(json.len() - (self.end - self.start) + self.value.len());
+        buf.push_str(&json[..self.start]);
+        buf.push_str(&self.value);
+        buf.push_str(&json[self.end..]);
+    }
+
+    /// Write the spliced json into `writer` without building it in memory
+    #[cfg(feature = "std")]
+    fn write_to<W: std::io::Write>(&self, writer: &mut W, json: &[u8]) -> Result<(), SjsonError> {
+        writer.write_all(&json[..self.start])
+            .and_then(|()| writer.write_all(self.value.as_bytes()))
+            .and_then(|()| writer.write_all(&json[self.end..]))
+            .map_err(|e| SjsonError::Io(Arc::new(e)))
+    }
+
+    fn apply_bytes(&self, json: &[u8]) -> Vec<u8> {
+        let mut result = Vec::with_capacity(json.len() - (self.end - self.start) + self.value.len());
+        result.extend_from_slice(&json[..self.start]);
+        result.extend_from_slice(self.value.as_bytes());
+        result.extend_from_slice(&json[self.end..]);
+        result
+    }
+
+    /// Drop the splice of an existing value when `only_if_absent` is set, or
+    /// when `error_on_nochange` is set and the replaced value equals the new
+    /// one, leaving it to the parser to report the no-op
+    fn unless_unchanged(self, json: &[u8], opts: Option<&Options>) -> Option<Splice> {
+        if opts.map(|o| o.only_if_absent).unwrap_or(false) {
+            return None;
+        }
+
+        let error_on_nochange = opts.map(|o| o.error_on_nochange).unwrap_or(false);
+        if error_on_nochange {
+            let old = serde_json::from_slice::<JsonValue>(&json[self.start..self.end]);
+            let new = serde_json::from_str::<JsonValue>(&self.value);
+            if matches!((old, new), (Ok(old), Ok(new)) if old == new) {
+                return None;
+            }
+        }
+        Some(self)
+    }
+}
+
+/// Keep the result of an optimistic splice unless `verify` is set and the
+/// result isn't well-formed json
+fn verified<T: AsRef<[u8]>>(result: T, opts: Option<&Options>) -> Option<T> {
+    let verify = opts.map(|o| o.verify).unwrap_or(false);
+    if verify && serde_json::from_slice::<serde::de::IgnoredAny>(result.as_ref()).is_err() {
+        return None;
+    }
+    Some(result)
+}
+
+/// Edit is a set or delete worked out by [`set_edit`], [`set_raw_edit`] or
+/// [`delete_edit`], before it is written out. The string, byte and writer
+/// functions share these cores and only differ in how they write the edit,
+/// so they always agree on the result.
+enum Edit {
+    /// A splice of the input that is still to be applied
+    Splice(Splice),
+    /// The input with a splice applied, built already to check it
+    Spliced { splice: Splice, result: Vec<u8> },
+    /// The edited tree of the parser path, with the expected output size
+    Tree { value: Parsed, size_hint: usize },
+}
+
+impl Edit {
+    /// An optimistic splice, applied and checked first when `verify` is set,
+    /// or `None` when the check fails
+    fn optimistic(splice: Splice, json: &[u8], opts: Option<&Options>) -> Option<Edit> {
+        if opts.is_some_and(|o| o.verify) {
+            let result = verified(splice.apply_bytes(json), opts)?;
+            return Some(Edit::Spliced { splice, result });
+        }
+        Some(Edit::Splice(splice))
+    }
+
+    /// A splice from [`scalar_splice_at`], kept only when its result is
+    /// sound, see [`splice_sound`]
+    fn scalar(splice: Splice, json: &[u8]) -> Option<Edit> {
+        let result = splice.apply_bytes(json);
+        splice_sound(&result).then_some(Edit::Spliced { splice, result })
+    }
+
+    /// The splice the edit makes, or `None` for the parser path
+    fn splice(&self) -> Option<&Splice> {
+        match self {
+            Edit::Splice(splice) | Edit::Spliced { splice, .. } => Some(splice),
+            Edit::Tree { .. } => None,
+        }
+    }
+
+    /// Write the edited json into `buf`, replacing what it held but keeping
+    /// its allocation
+    fn write_into(self, json: &str, buf: &mut String, opts: Option<&Options>) -> Result<(), SjsonError> {
+        match self {
+            Edit::Splice(splice) => splice.apply_into(json, buf),
+            Edit::Spliced { result, .. } => {
+                buf.clear();
+                buf.push_str(core::str::from_utf8(&result).expect("splicing utf-8 into utf-8 is utf-8"));
+            }
+            Edit::Tree { mut value, size_hint } => return to_json_into(buf, &mut value, opts, size_hint),
+        }
+        Ok(())
+    }
+
+    fn into_bytes(self, json: &[u8], opts: Option<&Options>) -> Result<Vec<u8>, SjsonError> {
+        match self {
+            Edit::Splice(splice) => Ok(splice.apply_bytes(json)),
+            Edit::Spliced { result, .. } => Ok(result),
+            Edit::Tree { mut value, size_hint } => to_json_vec(&mut value, opts, size_hint),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn write_to<W: std::io::Write>(self, writer: &mut W, json: &[u8], opts: Option<&Options>) -> Result<(), SjsonError> {
+        match self {
+            Edit::Splice(splice) => splice.write_to(writer, json),
+            Edit::Spliced { result, .. } => writer.write_all(&result).map_err(|e| SjsonError::Io(Arc::new(e))),
+            Edit::Tree { mut value, .. } => write_json(writer, &mut value, opts),
+        }
+    }
+}
+
+/// Quote `value` for splicing when it doesn't look like a json literal.
+/// Strings are escaped by serde_json, so quotes, backslashes and control
+/// characters come out exactly as the parser path would write them.
+/// With `go_sjson_compat` every value is a string, and like in `parse_value`
+/// text in brackets that isn't valid json is one as well.
+fn optimistic_value(value: &str, opts: Option<&Options>) -> String {
+    if opts.is_some_and(|o| o.go_sjson_compat) {
+        return quote(value);
+    }
+
+    let is_container = ((value.starts_with('[') && value.ends_with(']'))
+        || (value.starts_with('{') && value.ends_with('}')))
+        && serde_json::from_str::<serde::de::IgnoredAny>(value).is_ok();
+    if is_container || is_json_number(value) || matches!(value, "true" | "false" | "null") {
+        value.to_string()
+    } else {
+        quote(value)
+    }
+}
+
+/// Write `s` as a json string literal, escaped exactly like serde_json
+/// escapes strings and keys on the parser path. Any key or string that is
+/// written into the output directly must go through here.
+fn quote(s: &str) -> String {
+    serde_json::to_string(s).expect("serializing a str can't fail")
+}
+
+/// Check if a final `-1` segment appends to an array instead of indexing
+fn appends_on_minus_one(opts: Option<&Options>) -> bool {
+    opts.is_some_and(|o| o.go_compatible_append || o.go_sjson_compat)
+}
+
+/// Check if the final segment of `path` appends to an array
+fn is_append_path(path: &str, opts: Option<&Options>) -> bool {
+    appends_on_minus_one(opts) && path.rsplit(separator(opts)).next() == Some("-1")
+}
+
+/// Try to append `value` to the array addressed by the parent of `path`
+/// by inserting it before the closing bracket
+fn optimistic_append(json: &[u8], path: &str, sep: char, value: String) -> Option<Splice> {
+    let (parent, _) = path.rsplit_once(sep)?;
+    let (start, end) = find_value_position(json, parent, sep)?;
+
+    let array = json.get(start..end)?;
+    let close = start + array.iter().rposition(|b| !b.is_ascii_whitespace())?;
+    if array.first() != Some(&b'[') || json.get(close) != Some(&b']') || close == start {
+        return None;
+    }
+
+    let is_empty = json[start + 1..close].iter().all(|b| b.is_ascii_whitespace());
+    let value = if is_empty { value } else { format!(",{}", value) };
+
+    Some(Splice { start: close, end: close, value })
+}
+
+/// Try to replace an existing value, or add a missing final key to its
+/// object, by scanning the json. A new member is appended after the last one,
+/// so the order of the existing keys is kept. The value is written exactly
+/// as given, so a raw number such as `1e3` keeps its text.
+fn optimistic_put(json: &[u8], path: &str, value: String, opts: Option<&Options>) -> Option<Splice> {
+    if !splice_allowed(json, opts) {
+        return None;
+    }
+    let sep = separator(opts);
+    if is_append_path(path, opts) {
+        return optimistic_append(json, path, sep, value)?.within(json);
+    }
+
+    let Some(lookup) = lookup_member(json, path, sep) else {
+        // An existing array element is replaced the same way as a member
+        let (start, end) = find_element_span(json, path, sep)?;
+        return Splice { start, end, value }.within(json)?.unless_unchanged(json, opts);
+    };
+    match lookup {
+        KeyLookup::Found { value_start, value_end, .. } => {
+            Splice { start: value_start, end: value_end, value }.within(json)?.unless_unchanged(json, opts)
+        }
+        KeyLookup::Missing { insert_at, is_empty } => {
+            let key = quote(path.rsplit(sep).next()?);
+            let comma = if is_empty { "" } else { "," };
+            Splice { start: insert_at, end: insert_at, value: format!("{}{}:{}", comma, key, value) }.within(json)
+        }
+    }
+}
+
+/// Try to set `value` by scanning the json, quoting it when it doesn't look
+/// like a json literal
+fn optimistic_set(json: &[u8], path: &str, value: &str, opts: Option<&Options>) -> Option<Splice> {
+    optimistic_put(json, path, optimistic_value(value, opts), opts)
+}
+
+/// Try to set a raw json block by scanning the json
+fn optimistic_set_raw(json: &[u8], path: &str, value: &str, opts: Option<&Options>) -> Option<Splice> {
+    optimistic_put(json, path, value.to_string(), opts)
+}
+
+/// Find the span of the existing array element addressed by `path` without
+/// collecting the spans of the elements around it
+fn find_element_span(json: &[u8], path: &str, sep: char) -> Option<(usize, usize)> {
+    let (parent, last) = path.rsplit_once(sep)?;
+    let (start, _) = find_value_position(json, parent, sep)?;
+    if json.get(start) != Some(&b'[') {
+        return None;
+    }
+
+    let element_start = find_nth_element(json, start, last)?;
+    let end = element_start + find_value_end(json.get(element_start..)?)?;
+    // An empty element means the array is malformed
+    let last = json[element_start..end].iter().rposition(|b| !b.is_ascii_whitespace())?;
+    Some((element_start, element_start + last + 1))
+}
+
+/// Widen the span of an object member or array element so that removing it
+/// also removes exactly one adjacent comma: the one before it when there is
+/// one, otherwise the one after it. A sole element has no comma to remove.
+fn removal_span(json: &[u8], start: usize, end: usize) -> (usize, usize) {
+    let before = json[..start].iter().rposition(|b| !b.is_ascii_whitespace());
+    if let Some(comma) = before.filter(|&i| json[i] == b',') {
+        // Also remove whitespace before comma
+        let start = json[..comma]
+            .iter()
+            .rposition(|b| !b.is_ascii_whitespace())
+            .map_or(0, |i| i + 1);
+        return (start, end);
+    }
+
+    let after = skip_whitespace(json, end);
+    if json.get(after) == Some(&b',') {
+        // Skip whitespace after comma
+        return (start, skip_whitespace(json, after + 1));
+    }
+
+    (start, end)
+}
+
+/// Find the spans of the elements of the array whose `[` is at `start`,
+/// tracking nesting and strings so commas inside elements are skipped
+fn array_elements(json: &[u8], start: usize) -> Option<Vec<(usize, usize)>> {
+    if json.get(start) != Some(&b'[') {
+        return None;
+    }
+
+    let mut elements = Vec::new();
+    let mut pos = skip_whitespace(json, start + 1);
+    if json.get(pos) == Some(&b']') {
+        return Some(elements);
+    }
+
+    loop {
+        let end = pos + find_value_end(json.get(pos..)?)?;
+        // An empty element means the array is malformed
+        let last = json[pos..end].iter().rposition(|b| !b.is_ascii_whitespace())?;
+        elements.push((pos, pos + last + 1));
+
+        match json.get(end)? {
+            b',' => pos = skip_whitespace(json, end + 1),
+            b']' => return Some(elements),
+            _ => return None,
+        }
+    }
+}
+
+/// An array element found by scanning the json
+struct FoundElement {
+    /// Position of the array's `[`
+    array_start: usize,
+    /// Spans of all elements of the array
+    elements: Vec<(usize, usize)>,
+    /// Index of the element that was looked for
+    index: usize,
+}
+
+/// Find the array element addressed by `path`, whose final segment must be
+/// an index into an array
+fn find_element(json: &[u8], path: &str, sep: char) -> Option<FoundElement> {
+    let (parent, last) = path.rsplit_once(sep)?;
+    if last.parse::<i64>().is_err() {
+        return None;
+    }
+
+    let (start, _) = find_value_position(json, parent, sep)?;
+    let elements = array_elements(json, start)?;
+    let index = parse_array_index(last, elements.len()).ok()?;
+    if index >= elements.len() {
+        return None;
+    }
+    Some(FoundElement { array_start: start, elements, index })
+}
+
+/// Span removing the element at `index` together with the nulls that would
+/// be left at the end of the array. `None` when those aren't adjacent, as the
+/// parser has to remove them in two places.
+fn trimmed_removal_span(json: &[u8], found: &FoundElement) -> Option<(usize, usize)> {
+    let FoundElement { array_start, ref elements, index } = *found;
+    let last_kept = elements
+        .iter()
+        .enumerate()
+        .rposition(|(i, &(s, e))| i != index && &json[s..e] != b"null");
+
+    match last_kept {
+        // Keep everything up to the last remaining element that isn't null
+        Some(kept) if kept < index => Some((elements[kept].1, elements.last()?.1)),
+        None => Some((array_start + 1, elements.last()?.1)),
+        Some(kept) if kept == elements.len() - 1 => Some(removal_span(json, elements[index].0, elements[index].1)),
+        Some(_) => None,
+    }
+}
+
+/// Try to remove an existing member or array element by scanning the json
+fn optimistic_delete(json: &[u8], path: &str, opts: Option<&Options>) -> Option<Splice> {
+    if !splice_allowed(json, opts) {
+        return None;
+    }
+    let trim_trailing_nulls = opts.map(|o| o.trim_trailing_nulls).unwrap_or(false);
+    let sep = separator(opts);
+    let path = if opts.is_some_and(|o| o.prune_empty) {
+        // Trimming could empty an array the scan doesn't know about
+        if trim_trailing_nulls {
+            return None;
+        }
+        pruned_path(json, path, sep)
+    } else {
+        path
+    };
+
+    // Only trimming needs to see every element, otherwise the scan stops at
+    // the one being removed
+    let element = if trim_trailing_nulls {
+        find_element(json, path, sep).map(|found| trimmed_removal_span(json, &found))
+    } else {
+        find_element_span(json, path, sep).map(|(start, end)| Some(removal_span(json, start, end)))
+    };
+
+    let (start, end) = match element {
+        Some(span) => span?,
+        None => {
+            let (key_start, _, end) = find_member(json, path, sep)?;
+            removal_span(json, key_start, end)
+        }
+    };
+
+    Splice { start, end, value: String::new() }.within(json)
+}
+
+/// The path to delete so that `path` goes with every parent that would be
+/// left empty: the innermost parent that has other members as well, or the
+/// root, stops the walk
+fn pruned_path<'a>(json: &[u8], path: &'a str, sep: char) -> &'a str {
+    // A parent with one member only goes if that member is the target
+    if value_start(json, path, sep).is_none() {
+        return path;
+    }
+
+    let mut target = path;
+    while let Some((parent, _)) = target.rsplit_once(sep) {
+        match value_start(json, parent, sep) {
+            Some(start) if has_single_member(json, start) => target = parent,
+            _ => break,
+        }
+    }
+    target
+}
+
+/// Find where the value at `path` starts, be it an object member or an
+/// array element
+fn value_start(json: &[u8], path: &str, sep: char) -> Option<usize> {
+    let (container, last) = match path.rsplit_once(sep) {
+        Some((parent, last)) => (value_start(json, parent, sep)?, last),
+        None => (skip_whitespace(json, 0), path),
+    };
+    match json.get(container)? {
+        b'[' => find_nth_element(json, container, last),
+        _ => match find_key(json, container, last)? {
+            KeyLookup::Found { value_start, .. } => Some(value_start),
+            KeyLookup::Missing { .. } => None,
+        },
+    }
+}
+
+/// Check if the object or array whose opening bracket is at `start` has
+/// exactly one member or element
+fn has_single_member(json: &[u8], start: usize) -> bool {
+    let mut pos = skip_whitespace(json, start + 1);
+    if json.get(start) == Some(&b'{') {
+        // Step over the key to the value
+        let Some(key_end) = json.get(pos).filter(|&&b| b == b'"').and_then(|_| find_string_end(json, pos)) else {
+            return false;
+        };
+        pos = skip_whitespace(json, key_end);
+        if json.get(pos) != Some(&b':') {
+            return false;
+        }
+        pos += 1;
+    } else if json.get(start) != Some(&b'[') || json.get(pos) == Some(&b']') {
+        return false;
+    }
+
+    json.get(pos..)
+        .and_then(find_value_end)
+        .is_some_and(|end| matches!(json[pos + end], b'}' | b']'))
+}
+
+/// Set sets a json value for the specified path.
+/// A path is in dot syntax, such as "name.last" or "age".
+/// This function expects that the json is well-formed, and does not validate.
+/// Invalid json will not panic, but it may return back unexpected results.
+/// An error is returned if the path is not valid.
+///
+/// A path is a series of keys separated by a dot.
+///
+/// ```json
+/// {
+///   "name": {"first": "Tom", "last": "Anderson"},
+///   "age": 37,
+///   "children": ["Sara","Alex","Jack"],
+///   "friends": [
+///     {"first": "James", "last": "Murphy"},
+///     {"first": "Roger", "last": "Craig"}
+///   ]
+/// }
+/// ```
+/// "name.last"          >> "Anderson"
+/// "age"                >> 37
+/// "children.1"         >> "Alex"
+///
+/// A dot that is part of a key can be escaped with a backslash, so
+/// `user\.name` addresses the key "user.name".
+pub fn set(json: &str, path: &str, value: &str) -> Result<String, SjsonError> {
+    set_options(json, path, value, None)
+}
+
+/// SetOptions sets a json value for the specified path with options.
+pub fn set_options(
+    json: &str,
+    path: &str,
+    value: &str,
+    opts: Option<&Options>,
+) -> Result<String, SjsonError> {
+    let mut buf = String::new();
+    set_into(&mut buf, json, path, value, opts)?;
+    Ok(buf)
 }
 
-/// Set an integer value
-pub fn set_int<T: std::fmt::Display>(
-    json: &str,
-    path: &str,
-    value: T,
-    opts: Option<&Options>,
-) -> Result<String, SjsonError> {
-    let raw = value.to_string();
-    set_options(json, path, &raw, opts)
-}
+/// Write the result of [`set_options`] into `buf`, replacing what it held.
+/// The string functions and [`Editor`] share it, so the results of an
+/// editor are exactly those of the functions.
+fn set_into(buf: &mut String, json: &str, path: &str, value: &str, opts: Option<&Options>) -> Result<(), SjsonError> {
+    set_edit(json.as_bytes(), path, value, opts)?.write_into(json, buf, opts)
+}
+
+/// Work out the edit of [`set_options`], [`set_bytes_options`] and
+/// [`set_to_writer`]
+fn set_edit(json: &[u8], path: &str, value: &str, opts: Option<&Options>) -> Result<Edit, SjsonError> {
+    if path.is_empty() {
+        return Err(SjsonError::EmptyPath);
+    }
+
+    let treat_null_as_delete = opts.map(|o| o.treat_null_as_delete).unwrap_or(false);
+    if treat_null_as_delete && parse_value::<JsonValue>(value, opts).is_ok_and(|v| v.is_null()) {
+        return delete_edit(json, path, opts);
+    }
+
+    if let Some(edit) = set_splice(json, path, value, opts) {
+        return Ok(edit);
+    }
+    report_fallback(json, path, opts, false);
+
+    // Fall back to full JSON parsing approach
+    let mut parsed = parse_document::<Parsed>(json, opts)?;
+
+    set_value_at(&mut parsed, path, parse_value(value, opts)?, opts)?;
+
+    Ok(Edit::Tree { value: parsed, size_hint: json.len() + value.len() })
+}
+
+/// Make the edit of [`set_edit`] by splicing: with the optimistic fast path
+/// when it is enabled, otherwise by replacing an existing scalar. `None`
+/// leaves the edit to the parser.
+fn set_splice(json: &[u8], path: &str, value: &str, opts: Option<&Options>) -> Option<Edit> {
+    if use_optimistic(path, opts) {
+        optimistic_set(json, path, value, opts).and_then(|splice| Edit::optimistic(splice, json, opts))
+    } else {
+        let text = scalar_text(value, opts)?;
+        scalar_splice_at(json, path, &text, opts).and_then(|splice| Edit::scalar(splice, json))
+    }
+}
+
+/// Get returns the json value at the specified path, or `None` if the path
+/// doesn't exist or the json is invalid.
+/// The path uses the same syntax as [`set`], including negative array indices.
+pub fn get(json: &str, path: &str) -> Option<JsonValue> {
+    let parsed = parse_document::<JsonValue>(json.as_bytes(), None).ok()?;
+    get_value_at(&parsed, path, DEFAULT_SEPARATOR).cloned()
+}
+
+/// Look up the value at `path` without modifying anything
+fn get_value_at<'a, V: Tree>(root: &'a V, path: &str, sep: char) -> Option<&'a V> {
+    if path == ROOT_PATH {
+        return Some(root);
+    }
+    if path.is_empty() {
+        return None;
+    }
+
+    let mut current = root;
+    for segment in path_segments(path, sep).ok()? {
+        current = match current.node() {
+            Node::Object(map) => map.get(&segment.key().ok()?)?,
+            Node::Array(arr) => arr.get(segment.element_index(arr).ok()?)?,
+            Node::Scalar => return None,
+        };
+    }
+
+    Some(current)
+}
+
+/// Look up the value at `path` for modification, without creating anything
+fn get_value_at_mut<'a, V: Tree>(root: &'a mut V, path: &str, sep: char) -> Option<&'a mut V> {
+    if path == ROOT_PATH {
+        return Some(root);
+    }
+    if path.is_empty() {
+        return None;
+    }
+
+    let mut current = root;
+    for segment in path_segments(path, sep).ok()? {
+        current = match current.node_mut() {
+            Node::Object(map) => map.get_mut(&segment.key().ok()?)?,
+            Node::Array(arr) => {
+                let index = segment.element_index(arr).ok()?;
+                arr.get_mut(index)?
+            }
+            Node::Scalar => return None,
+        };
+    }
+
+    Some(current)
+}
+
+/// Set `json_value` at `path` by mutating `root` in place, returning the
+/// value it replaced or `None` if the path didn't exist.
+/// Missing parents are created along the way, so on error `root` may have
+/// gained empty intermediate containers.
+fn set_value_at<V: Tree>(
+    root: &mut V,
+    path: &str,
+    json_value: V,
+    opts: Option<&Options>,
+) -> Result<Option<V>, SjsonError> {
+    if path == ROOT_PATH {
+        return set_segments_at(root, &[], json_value, opts);
+    }
+    set_segments_at(root, &path_segments(path, separator(opts))?, json_value, opts)
+}
+
+/// Set `json_value` at the path given by `segments`, where no segments
+/// address the root itself. An edit that fails leaves `root` as it was.
+fn set_segments_at<V: Tree>(
+    root: &mut V,
+    segments: &[Segment],
+    json_value: V,
+    opts: Option<&Options>,
+) -> Result<Option<V>, SjsonError> {
+    check_depth(segments, opts)?;
+
+    if segments.is_empty() {
+        let only_if_absent = opts.map(|o| o.only_if_absent).unwrap_or(false);
+        let error_on_nochange = opts.map(|o| o.error_on_nochange).unwrap_or(false);
+        if only_if_absent || (error_on_nochange && *root == json_value) {
+            return Err(SjsonError::NoChange);
+        }
+        return Ok(Some(core::mem::replace(root, json_value)));
+    }
+
+    // A document that is just a scalar has no members to descend into
+    if matches!(root.node(), Node::Scalar) && !opts.is_some_and(|o| o.allow_scalar_root_replace) {
+        return Err(SjsonError::JsonMustBeObjectOrArray);
+    }
+
+    set_segments_from(root, segments, json_value, opts, false)
+}
+
+/// Walk `segments` down from `current` and set `json_value` at the end.
+/// Nothing is changed on the way until a parent has to be created or a
+/// scalar replaced. Everything below that point is new, so when the rest of
+/// the edit fails, only that first change is undone. `fresh` tells that
+/// `current` is already part of such a new value, which its creator undoes.
+fn set_segments_from<V: Tree>(
+    current: &mut V,
+    segments: &[Segment],
+    json_value: V,
+    opts: Option<&Options>,
+    fresh: bool,
+) -> Result<Option<V>, SjsonError> {
+    let Some((final_part, parents)) = segments.split_last() else {
+        return Err(SjsonError::InvalidPath);
+    };
+
+    let only_if_absent = opts.map(|o| o.only_if_absent).unwrap_or(false);
+    let create_missing = opts.is_none_or(|o| o.create_missing);
+    let strict_indices = opts.is_some_and(|o| o.strict_indices);
+    let go_compatible_append = appends_on_minus_one(opts);
+    // Whether `segment` is a final `-1` that appends rather than indexes
+    let appends = |segment: &Segment| go_compatible_append && core::ptr::eq(segment, final_part);
+    let mut current = current;
+
+    // Navigate to the parent of the target
+    for (i, (part, next)) in parents.iter().zip(&segments[1..]).enumerate() {
+        if *part == Segment::Wildcard {
+            return set_each(current, &segments[i + 1..], json_value, opts);
+        }
+
+        // Convert to a container if needed
+        if matches!(current.node(), Node::Scalar) {
+            let scalar = (!fresh).then(|| current.clone());
+            clobber(current, part, opts, false)?;
+            if let Some(scalar) = scalar {
+                return set_segments_from(current, &segments[i..], json_value, opts, true).inspect_err(|_| *current = scalar);
+            }
+        }
+
+        match current.node_mut() {
+            Node::Object(map) => {
+                let key = part.object_key(strict_indices)?;
+                if !map.contains_key(&key) {
+                    if !create_missing {
+                        return Err(SjsonError::PathNotFound(part.to_string()));
+                    }
+                    // A new container has no children to fan out to
+                    if *next == Segment::Wildcard {
+                        return Err(SjsonError::NoChange);
+                    }
+                    map.insert(key.to_string(), container_for(next, appends(next))?);
+                    if !fresh {
+                        let child = map.get_mut(&key).unwrap();
+                        return set_segments_from(child, &segments[i + 1..], json_value, opts, true).inspect_err(|_| {
+                            map.remove(&key);
+                        });
+                    }
+                }
+                current = map.get_mut(&key).unwrap();
+            }
+            Node::Array(arr) => {
+                let index = part.element_index(arr)?;
+                if index >= arr.len() && !create_missing {
+                    return Err(SjsonError::PathNotFound(part.to_string()));
+                }
+                if index >= arr.len() && *next == Segment::Wildcard {
+                    return Err(SjsonError::NoChange);
+                }
+                if index >= arr.len() {
+                    // Extend array with null values up to the new container
+                    let len = arr.len();
+                    while arr.len() < index {
+                        arr.push(V::null());
+                    }
+                    arr.push(container_for(next, appends(next))?);
+                    if !fresh {
+                        return set_segments_from(&mut arr[index], &segments[i + 1..], json_value, opts, true)
+                            .inspect_err(|_| arr.truncate(len));
+                    }
+                }
+                current = &mut arr[index];
+            }
+            Node::Scalar => unreachable!("scalars were converted to containers"),
+        }
+    }
+
+    if *final_part == Segment::Wildcard {
+        return set_each(current, &[], json_value, opts);
+    }
+
+    // Set the final value
+    let error_on_nochange = opts.map(|o| o.error_on_nochange).unwrap_or(false);
+
+    // Convert to a container if needed
+    if matches!(current.node(), Node::Scalar) {
+        let scalar = (!fresh).then(|| current.clone());
+        clobber(current, final_part, opts, appends(final_part))?;
+        if let Some(scalar) = scalar {
+            let target = core::slice::from_ref(final_part);
+            return set_segments_from(current, target, json_value, opts, true).inspect_err(|_| *current = scalar);
+        }
+    }
+
+    let old = match current.node_mut() {
+        Node::Object(map) => {
+            let key = final_part.object_key(strict_indices)?;
+            if (only_if_absent && map.contains_key(&key))
+                || (error_on_nochange && map.get(&key) == Some(&json_value)) {
+                return Err(SjsonError::NoChange);
+            }
+            map.insert(key.into_owned(), json_value)
+        }
+        Node::Array(arr) if go_compatible_append && *final_part == Segment::Index(-1) => {
+            arr.push(json_value);
+            None
+        }
+        Node::Array(arr) => {
+            if let Segment::Slice { start, end } = *final_part {
+                return set_slice(arr, start..end, json_value, only_if_absent, error_on_nochange).map(Some);
+            }
+            let index = final_part.element_index(arr)?;
+            if (only_if_absent && index < arr.len())
+                || (error_on_nochange && arr.get(index) == Some(&json_value)) {
+                return Err(SjsonError::NoChange);
+            }
+            if index >= arr.len() {
+                // Extend array with null values
+                while arr.len() < index {
+                    arr.push(V::null());
+                }
+                arr.push(json_value);
+                None
+            } else {
+                Some(core::mem::replace(&mut arr[index], json_value))
+            }
+        }
+        Node::Scalar => unreachable!("scalars were converted to containers"),
+    };
+
+    Ok(old)
+}
+
+/// Replace the elements of `arr` in the slice `bounds` with those of the
+/// array `json_value`, returning an array of the replaced elements
+fn set_slice<V: Tree>(
+    arr: &mut Vec<V>,
+    bounds: core::ops::Range<usize>,
+    mut json_value: V,
+    only_if_absent: bool,
+    error_on_nochange: bool,
+) -> Result<V, SjsonError> {
+    let range = Segment::slice_range(bounds.start, bounds.end, arr.len())?;
+    let Node::Array(items) = json_value.node_mut() else {
+        return Err(SjsonError::SliceValueMustBeArray);
+    };
+    if (only_if_absent && !range.is_empty()) || (error_on_nochange && arr[range.clone()] == items[..]) {
+        return Err(SjsonError::NoChange);
+    }
+    Ok(array_of(arr.splice(range, core::mem::take(items)).collect()))
+}
+
+/// Set `json_value` at `rest` under every child of `current`, which a `*`
+/// segment stepped into. Children the first segment of `rest` can't step
+/// into are skipped, or refused with `strict_wildcards`, and so is `current`
+/// when it isn't an object or array. Without a child that changed, the edit
+/// is `SjsonError::NoChange`. There is no single value that was replaced, so
+/// `None` is returned. A child that fails puts back the ones already edited.
+fn set_each<V: Tree>(current: &mut V, rest: &[Segment], json_value: V, opts: Option<&Options>) -> Result<Option<V>, SjsonError> {
+    let only_if_absent = opts.is_some_and(|o| o.only_if_absent);
+    let error_on_nochange = opts.is_some_and(|o| o.error_on_nochange);
+
+    // Replacing the children themselves can't fail half way
+    let snapshot = (!rest.is_empty()).then(|| current.clone());
+    let mut changed = false;
+    let mut failed = None;
+    for child in wildcard_children(current, rest.first(), opts)? {
+        let result = match rest {
+            [] if only_if_absent || (error_on_nochange && *child == json_value) => Err(SjsonError::NoChange),
+            [] => {
+                *child = json_value.clone();
+                Ok(None)
+            }
+            _ => set_segments_at(child, rest, json_value.clone(), opts),
+        };
+        match result {
+            Ok(_) => changed = true,
+            Err(SjsonError::NoChange) => {}
+            Err(e) => {
+                failed = Some(e);
+                break;
+            }
+        }
+    }
+
+    if let Some(e) = failed {
+        if let Some(snapshot) = snapshot {
+            *current = snapshot;
+        }
+        return Err(e);
+    }
+    if changed {
+        Ok(None)
+    } else {
+        Err(SjsonError::NoChange)
+    }
+}
+
+/// The children of `current` a `*` segment fans out to: every member of an
+/// object or element of an array that `next`, the segment after the `*`,
+/// can step into. The others are skipped, unless `strict_wildcards` makes
+/// them `SjsonError::PathTypeMismatch`.
+fn wildcard_children<'a, V: Tree>(current: &'a mut V, next: Option<&Segment>, opts: Option<&Options>) -> Result<Vec<&'a mut V>, SjsonError> {
+    let strict = opts.is_some_and(|o| o.strict_wildcards);
+    let mismatch = |found| SjsonError::PathTypeMismatch { segment: Segment::Wildcard.to_string(), found };
+
+    let found = current.kind();
+    let children = match current.node_mut() {
+        Node::Object(map) => map.values_mut(),
+        Node::Array(arr) => arr.iter_mut().collect(),
+        Node::Scalar if strict => return Err(mismatch(found)),
+        Node::Scalar => return Ok(Vec::new()),
+    };
+    let Some(next) = next else {
+        return Ok(children);
+    };
+
+    let mut fitting = Vec::with_capacity(children.len());
+    for child in children {
+        let fits = match child.node() {
+            Node::Object(_) => *next != Segment::Append,
+            Node::Array(_) => !matches!(next, Segment::Key(key) if key.parse::<i64>().is_err()),
+            Node::Scalar => false,
+        };
+        if fits {
+            fitting.push(child);
+        } else if strict {
+            return Err(mismatch(child.kind()));
+        }
+    }
+    Ok(fitting)
+}
+
+/// Replace the scalar `current` with the container `segment` steps into,
+/// unless `no_clobber` asks to keep it. An append only replaces null, and so
+/// does an index or slice unless `allow_scalar_index_replace` is set, as
+/// indexing into a string, number or bool is rarely meant.
+fn clobber<V: Tree>(current: &mut V, segment: &Segment, opts: Option<&Options>, appends: bool) -> Result<(), SjsonError> {
+    let no_clobber = opts.is_some_and(|o| o.no_clobber);
+    let only_null = match segment {
+        Segment::Append => true,
+        Segment::Index(_) | Segment::Slice { .. } => !opts.is_some_and(|o| o.allow_scalar_index_replace),
+        _ => false,
+    };
+    if no_clobber || (only_null && *current != V::null()) {
+        return Err(SjsonError::PathTypeMismatch { segment: segment.to_string(), found: current.kind() });
+    }
+    *current = container_for(segment, appends)?;
+    Ok(())
+}
+
+/// The empty container that `segment` can step into: an array for a
+/// non-negative index, an append or a slice, so `list.0` and `list.#` create `[...]`,
+/// and an object otherwise. A negative index counts from the end of an
+/// array that doesn't exist yet, so it is out of bounds, unless `appends`
+/// says it is a final `-1` that appends with `go_compatible_append`.
+fn container_for<V: Tree>(segment: &Segment, appends: bool) -> Result<V, SjsonError> {
+    match segment {
+        Segment::Index(index) if *index >= 0 || appends => Ok(V::array()),
+        Segment::Index(index) => Err(SjsonError::ArrayIndexOutOfBounds { index: *index, len: 0 }),
+        Segment::Append | Segment::Slice { .. } => Ok(V::array()),
+        _ => Ok(V::object()),
+    }
+}
+
+/// An array value holding `items`
+fn array_of<V: Tree>(items: Vec<V>) -> V {
+    let mut array = V::array();
+    if let Node::Array(arr) = array.node_mut() {
+        *arr = items;
+    }
+    array
+}
+
+/// A json value seen as a container the path functions can step into
+enum Node<M, A> {
+    Object(M),
+    Array(A),
+    Scalar,
+}
+
+/// A json tree the path functions can navigate and edit, implemented by
+/// `serde_json::Value` and, with the `ordered` feature, by `ordered::Value`
+trait Tree: Sized + Clone + PartialEq + serde::Serialize + serde::de::DeserializeOwned {
+    type Map: TreeMap<Self>;
+
+    fn from_json(value: JsonValue) -> Self;
+    fn into_json(self) -> JsonValue;
+    fn null() -> Self;
+    fn object() -> Self;
+    fn array() -> Self;
+    fn node(&self) -> Node<&Self::Map, &Vec<Self>>;
+    fn node_mut(&mut self) -> Node<&mut Self::Map, &mut Vec<Self>>;
+    fn into_map(self) -> Option<Self::Map>;
+    /// The json type of the value, such as "object" or "number"
+    fn kind(&self) -> &'static str;
+    #[cfg(feature = "std")]
+    fn sort_all_objects(&mut self);
+}
+
+/// The object operations the path functions need
+trait TreeMap<V>: IntoIterator<Item = (String, V)> {
+    fn get(&self, key: &str) -> Option<&V>;
+    fn get_mut(&mut self, key: &str) -> Option<&mut V>;
+    fn contains_key(&self, key: &str) -> bool;
+    /// Insert a value, returning the one it replaced
+    fn insert(&mut self, key: String, value: V) -> Option<V>;
+    /// Remove a key, keeping the remaining keys in order
+    fn remove(&mut self, key: &str) -> Option<V>;
+    fn values_mut(&mut self) -> Vec<&mut V>;
+}
+
+impl Tree for JsonValue {
+    type Map = serde_json::Map<String, JsonValue>;
+
+    fn from_json(value: JsonValue) -> Self {
+        value
+    }
+
+    fn into_json(self) -> JsonValue {
+        self
+    }
+
+    fn null() -> Self {
+        JsonValue::Null
+    }
+
+    fn object() -> Self {
+        JsonValue::Object(serde_json::Map::new())
+    }
+
+    fn array() -> Self {
+        JsonValue::Array(Vec::new())
+    }
+
+    fn node(&self) -> Node<&Self::Map, &Vec<Self>> {
+        match self {
+            JsonValue::Object(map) => Node::Object(map),
+            JsonValue::Array(arr) => Node::Array(arr),
+            _ => Node::Scalar,
+        }
+    }
+
+    fn node_mut(&mut self) -> Node<&mut Self::Map, &mut Vec<Self>> {
+        match self {
+            JsonValue::Object(map) => Node::Object(map),
+            JsonValue::Array(arr) => Node::Array(arr),
+            _ => Node::Scalar,
+        }
+    }
+
+    fn into_map(self) -> Option<Self::Map> {
+        match self {
+            JsonValue::Object(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            JsonValue::Null => "null",
+            JsonValue::Bool(_) => "bool",
+            JsonValue::Number(_) => "number",
+            JsonValue::String(_) => "string",
+            JsonValue::Array(_) => "array",
+            JsonValue::Object(_) => "object",
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn sort_all_objects(&mut self) {
+        JsonValue::sort_all_objects(self)
+    }
+}
+
+impl TreeMap<JsonValue> for serde_json::Map<String, JsonValue> {
+    fn get(&self, key: &str) -> Option<&JsonValue> {
+        serde_json::Map::get(self, key)
+    }
+
+    fn get_mut(&mut self, key: &str) -> Option<&mut JsonValue> {
+        serde_json::Map::get_mut(self, key)
+    }
+
+    fn contains_key(&self, key: &str) -> bool {
+        serde_json::Map::contains_key(self, key)
+    }
+
+    fn insert(&mut self, key: String, value: JsonValue) -> Option<JsonValue> {
+        serde_json::Map::insert(self, key, value)
+    }
+
+    fn remove(&mut self, key: &str) -> Option<JsonValue> {
+        #[cfg(feature = "preserve_order")]
+        return self.shift_remove(key);
+        #[cfg(not(feature = "preserve_order"))]
+        return serde_json::Map::remove(self, key);
+    }
+
+    fn values_mut(&mut self) -> Vec<&mut JsonValue> {
+        serde_json::Map::values_mut(self).collect()
+    }
+}
+
+#[cfg(feature = "ordered")]
+impl Tree for ordered::Value {
+    type Map = ordered::Map;
+
+    fn from_json(value: JsonValue) -> Self {
+        ordered::Value::from(value)
+    }
+
+    fn into_json(self) -> JsonValue {
+        JsonValue::from(self)
+    }
+
+    fn null() -> Self {
+        ordered::Value::Null
+    }
+
+    fn object() -> Self {
+        ordered::Value::Object(ordered::Map::new())
+    }
+
+    fn array() -> Self {
+        ordered::Value::Array(Vec::new())
+    }
+
+    fn node(&self) -> Node<&Self::Map, &Vec<Self>> {
+        match self {
+            ordered::Value::Object(map) => Node::Object(map),
+            ordered::Value::Array(arr) => Node::Array(arr),
+            _ => Node::Scalar,
+        }
+    }
+
+    fn node_mut(&mut self) -> Node<&mut Self::Map, &mut Vec<Self>> {
+        match self {
+            ordered::Value::Object(map) => Node::Object(map),
+            ordered::Value::Array(arr) => Node::Array(arr),
+            _ => Node::Scalar,
+        }
+    }
+
+    fn into_map(self) -> Option<Self::Map> {
+        match self {
+            ordered::Value::Object(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            ordered::Value::Null => "null",
+            ordered::Value::Bool(_) => "bool",
+            ordered::Value::Number(_) => "number",
+            ordered::Value::String(_) => "string",
+            ordered::Value::Array(_) => "array",
+            ordered::Value::Object(_) => "object",
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn sort_all_objects(&mut self) {
+        ordered::Value::sort_all_objects(self)
+    }
+}
+
+#[cfg(feature = "ordered")]
+impl TreeMap<ordered::Value> for ordered::Map {
+    fn get(&self, key: &str) -> Option<&ordered::Value> {
+        ordered::Map::get(self, key)
+    }
+
+    fn get_mut(&mut self, key: &str) -> Option<&mut ordered::Value> {
+        ordered::Map::get_mut(self, key)
+    }
+
+    fn contains_key(&self, key: &str) -> bool {
+        ordered::Map::contains_key(self, key)
+    }
+
+    fn insert(&mut self, key: String, value: ordered::Value) -> Option<ordered::Value> {
+        ordered::Map::insert(self, key, value)
+    }
+
+    fn remove(&mut self, key: &str) -> Option<ordered::Value> {
+        self.shift_remove(key)
+    }
+
+    fn values_mut(&mut self) -> Vec<&mut ordered::Value> {
+        ordered::Map::values_mut(self).collect()
+    }
+}
+
+/// Check if a value is an integer, an optional minus sign followed by digits
+fn is_integer(value: &str) -> bool {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Skip ascii digits starting at `pos`
+fn skip_digits(bytes: &[u8], pos: usize) -> usize {
+    pos + bytes[pos..].iter().take_while(|b| b.is_ascii_digit()).count()
+}
+
+/// Check if a value is a number in canonical json syntax, so strings like
+/// "007", "+1", ".5", "1.0.0" or "0x10" are not mistaken for numbers
+fn is_json_number(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    let mut i = usize::from(bytes.first() == Some(&b'-'));
+
+    // A single zero or digits without a leading zero
+    match bytes.get(i) {
+        Some(b'0') => i += 1,
+        Some(b'1'..=b'9') => i = skip_digits(bytes, i),
+        _ => return false,
+    }
+
+    if bytes.get(i) == Some(&b'.') {
+        let end = skip_digits(bytes, i + 1);
+        if end == i + 1 {
+            return false;
+        }
+        i = end;
+    }
+
+    if matches!(bytes.get(i), Some(b'e') | Some(b'E')) {
+        i += 1;
+        if matches!(bytes.get(i), Some(b'+') | Some(b'-')) {
+            i += 1;
+        }
+        let end = skip_digits(bytes, i);
+        if end == i {
+            return false;
+        }
+        i = end;
+    }
+
+    i == bytes.len()
+}
+
+/// Parse a value already known to be a json number. Without
+/// `arbitrary_precision`, a number that an `i64`, `u64` or `f64` can't hold
+/// is `SjsonError::NumberOutOfRange` rather than rounded or turned into a
+/// string.
+fn parse_number(value: &str) -> Result<JsonValue, SjsonError> {
+    if let Ok(num) = value.parse::<i64>() {
+        Ok(JsonValue::Number(serde_json::Number::from(num)))
+    } else if let Ok(num) = value.parse::<u64>() {
+        Ok(JsonValue::Number(serde_json::Number::from(num)))
+    } else if cfg!(feature = "arbitrary_precision") {
+        // Numbers keep their exact text, so nothing is lost
+        serde_json::from_str::<serde_json::Number>(value)
+            .map(JsonValue::Number)
+            .map_err(|_| SjsonError::NumberOutOfRange(value.to_string()))
+    } else if is_integer(value) {
+        // Too large for i64/u64, an f64 would lose digits
+        Err(SjsonError::NumberOutOfRange(value.to_string()))
+    } else {
+        value.parse::<f64>().ok()
+            .and_then(serde_json::Number::from_f64)
+            .map(JsonValue::Number)
+            .ok_or_else(|| SjsonError::NumberOutOfRange(value.to_string()))
+    }
+}
+
+/// Read the value given to a set function as the json it looks like, or as a
+/// string otherwise. With `go_sjson_compat` every value is a string.
+/// A number the tree can't hold is an error, see [`parse_number`].
+fn parse_value<V: Tree>(value: &str, opts: Option<&Options>) -> Result<V, SjsonError> {
+    if opts.is_some_and(|o| o.go_sjson_compat) {
+        return Ok(V::from_json(JsonValue::String(value.to_string())));
+    }
+
+    // Try to parse as different types
+    if value == "true" {
+        Ok(V::from_json(JsonValue::Bool(true)))
+    } else if value == "false" {
+        Ok(V::from_json(JsonValue::Bool(false)))
+    } else if value == "null" {
+        Ok(V::null())
+    } else if is_json_number(value) {
+        parse_number(value).map(V::from_json)
+    } else {
+        // Try to parse as JSON if it looks like JSON
+        if (value.starts_with('[') && value.ends_with(']')) || 
+           (value.starts_with('{') && value.ends_with('}')) {
+            if let Ok(json_value) = serde_json::from_str::<V>(value) {
+                return Ok(json_value);
+            }
+        }
+        Ok(V::from_json(JsonValue::String(value.to_string())))
+    }
+}
+
+/// SetIfAbsent sets a json value for the specified path only when nothing is
+/// there yet. An existing value, including `null`, is left alone and
+/// `SjsonError::NoChange` is returned.
+pub fn set_if_absent(
+    json: &str,
+    path: &str,
+    value: &str,
+    opts: Option<&Options>,
+) -> Result<String, SjsonError> {
+    let mut opts = opts.cloned().unwrap_or_default();
+    opts.only_if_absent = true;
+    set_options(json, path, value, Some(&opts))
+}
+
+/// GetOrSet returns the value at the specified path, with `json` unchanged,
+/// or sets `default` there like [`set`] and returns it when nothing is
+/// there yet. An existing `null` counts as present. The document is parsed
+/// once for both the lookup and the edit, so the optimistic fast path is not
+/// used.
+pub fn get_or_set(
+    json: &str,
+    path: &str,
+    default: &str,
+    opts: Option<&Options>,
+) -> Result<(String, JsonValue), SjsonError> {
+    if path.is_empty() {
+        return Err(SjsonError::EmptyPath);
+    }
+
+    let mut parsed = parse_document::<Parsed>(json.as_bytes(), opts)?;
+    if let Some(existing) = get_value_at(&parsed, path, separator(opts)) {
+        return Ok((json.to_string(), existing.clone().into_json()));
+    }
+
+    let value: Parsed = parse_value(default, opts)?;
+    set_value_at(&mut parsed, path, value.clone(), opts)?;
+
+    let result = to_json_string(&mut parsed, opts, json.len() + default.len())?;
+    Ok((result, value.into_json()))
+}
+
+/// Nullify sets the value at the specified path to `null` while keeping its
+/// key, for schemas where a member must be present but may be empty. Unlike
+/// `set(json, path, "null")` nothing is created: a missing path is
+/// `SjsonError::NoChange`. A string, number or bool is replaced by splicing,
+/// like [`set`] does, so the document keeps its layout and key order.
+pub fn nullify(json: &str, path: &str, opts: Option<&Options>) -> Result<String, SjsonError> {
+    if path.is_empty() {
+        return Err(SjsonError::EmptyPath);
+    }
+
+    if let Some(result) = scalar_splice_str(json, path, "null", opts) {
+        return Ok(result);
+    }
+
+    let mut parsed = parse_document::<Parsed>(json.as_bytes(), opts)?;
+    if get_value_at(&parsed, path, separator(opts)).is_none() {
+        return Err(SjsonError::NoChange);
+    }
+
+    set_value_at(&mut parsed, path, Parsed::null(), opts)?;
+
+    to_json_string(&mut parsed, opts, json.len())
+}
+
+/// SetRaw sets a raw json value for the specified path.
+/// This function works the same as Set except that the value is set as a
+/// raw json token: an object, an array, a quoted string, a number, a bool or
+/// null. It is never quoted again, so `"\"hi\""` sets the string "hi" and
+/// `"42"` the number 42, and anything that isn't valid json is
+/// `SjsonError::InvalidJson`.
+///
+/// The path `@this` ([`ROOT_PATH`]) replaces the whole document with the
+/// value, which must still be valid json.
+pub fn set_raw(json: &str, path: &str, value: &str) -> Result<String, SjsonError> {
+    set_raw_options(json, path, value, None)
+}
+
+/// SetRawOptions sets a raw json value for the specified path with options.
+pub fn set_raw_options(
+    json: &str,
+    path: &str,
+    value: &str,
+    opts: Option<&Options>,
+) -> Result<String, SjsonError> {
+    let mut buf = String::new();
+    set_raw_into(&mut buf, json, path, value, opts)?;
+    Ok(buf)
+}
+
+/// Write the result of [`set_raw_options`] into `buf`, like [`set_into`]
+fn set_raw_into(buf: &mut String, json: &str, path: &str, value: &str, opts: Option<&Options>) -> Result<(), SjsonError> {
+    set_raw_edit(json.as_bytes(), path, value, opts)?.write_into(json, buf, opts)
+}
+
+/// Work out the edit of [`set_raw_options`], [`set_raw_bytes_options`] and
+/// [`set_raw_to_writer`]
+fn set_raw_edit(json: &[u8], path: &str, value: &str, opts: Option<&Options>) -> Result<Edit, SjsonError> {
+    // The optimistic splice writes the value verbatim, so check it first
+    if use_optimistic(path, opts) {
+        validate_raw(value, opts)?;
+    }
+    set_raw_trusted_edit(json, path, value, opts)
+}
+
+/// Check that a raw value is a single valid json token, unless the caller
+/// vouches for it with `assume_valid_raw`
+fn validate_raw(value: &str, opts: Option<&Options>) -> Result<(), SjsonError> {
+    if opts.is_some_and(|o| o.assume_valid_raw) {
+        return Ok(());
+    }
+    serde_json::from_str::<serde::de::IgnoredAny>(value)?;
+    Ok(())
+}
+
+/// Parse an input document. A leading byte order mark is skipped, and with
+/// the `lenient` feature, comments and trailing commas are removed first.
+/// Edits pass their options, which may forbid reserializing the document
+/// with `preserve_untouched`.
+/// The optimistic path doesn't skip the mark, it finds no value at the start
+/// of such a document and leaves it to the parser.
+fn parse_document<V: Tree>(json: &[u8], opts: Option<&Options>) -> Result<V, SjsonError> {
+    let json = json.strip_prefix(BOM).unwrap_or(json);
+    let on_duplicate_key = opts.map_or(DuplicateKeys::KeepLast, |o| o.on_duplicate_key);
+    if on_duplicate_key == DuplicateKeys::Error {
+        check_duplicates(json)?;
+    }
+    if opts.is_some_and(|o| o.preserve_untouched) {
+        return Err(SjsonError::SpliceUnavailable);
+    }
+    #[cfg(feature = "lenient")]
+    let json = &*lenient::strip(json);
+    match on_duplicate_key {
+        DuplicateKeys::KeepFirst => Ok(duplicates::parse_first_wins::<V>(json)?),
+        _ => Ok(serde_json::from_slice::<V>(json)?),
+    }
+}
+
+/// Fail with `SjsonError::DuplicateKey` when an object in `json` repeats a
+/// key. Malformed json is left for the caller to report.
+fn check_duplicates(json: &[u8]) -> Result<(), SjsonError> {
+    #[cfg(feature = "lenient")]
+    let json = &*lenient::strip(json);
+    match duplicates::find_duplicate(json) {
+        Ok(Some(key)) => Err(SjsonError::DuplicateKey(key)),
+        _ => Ok(()),
+    }
+}
+
+/// Check that the optimistic path may splice `json`. Repeated keys are
+/// only looked for when they are an error; otherwise the scan leaves edits
+/// of a repeated key to the parser and splices around the others.
+fn splice_allowed(json: &[u8], opts: Option<&Options>) -> bool {
+    opts.is_none_or(|o| o.on_duplicate_key != DuplicateKeys::Error) || check_duplicates(json).is_ok()
+}
+
+/// Tell `on_fallback` why the optimistic fast path didn't make the edit of
+/// `path`, when it was asked for. With `preserve_untouched` there is no
+/// fallback, the parser refuses the edit instead.
+fn report_fallback(json: &[u8], path: &str, opts: Option<&Options>, deletes: bool) {
+    let Some(o) = opts.filter(|o| o.optimistic && !o.preserve_untouched) else {
+        return;
+    };
+    if let Some(on_fallback) = o.on_fallback {
+        on_fallback(&fallback_reason(json, path, o, deletes));
+    }
+}
+
+/// Work out why the optimistic fast path couldn't edit `path`, by checking
+/// the options and then walking the path through the document again
+fn fallback_reason(json: &[u8], path: &str, opts: &Options, deletes: bool) -> String {
+    if cfg!(feature = "lenient") {
+        return "the lenient feature edits documents through the parser".to_string();
+    }
+    #[cfg(feature = "std")]
+    {
+        if opts.pretty.is_some() || !opts.compact {
+            return "pretty printing needs the parser".to_string();
+        }
+        if opts.sort_keys || opts.escape_non_ascii || opts.escape_slashes {
+            return "reformatting the output needs the parser".to_string();
+        }
+    }
+
+    let sep = opts.separator;
+    if !is_optimistic_path(path, sep) {
+        return "path has escapes or characters the byte scan doesn't handle".to_string();
+    }
+    if !splice_possible(path, Some(opts)) {
+        return "max_depth or strict_indices need the parser to check the path".to_string();
+    }
+    if json.starts_with(BOM) {
+        return "document starts with a byte order mark".to_string();
+    }
+    if !splice_allowed(json, Some(opts)) {
+        return "document repeats a key".to_string();
+    }
+
+    let segments = path.split(sep).count();
+    let mut start = skip_whitespace(json, 0);
+    for (i, part) in path.split(sep).enumerate() {
+        let segment = i + 1;
+        start = match json.get(start) {
+            Some(b'[') => match find_nth_element(json, start, part) {
+                Some(element) => element,
+                None => return format!("element not found at segment {}", segment),
+            },
+            Some(b'{') => match find_key(json, start, part) {
+                Some(KeyLookup::Found { value_start, .. }) => value_start,
+                // A set adds a missing final key to its object
+                Some(KeyLookup::Missing { .. }) if segment == segments && !deletes => break,
+                Some(KeyLookup::Missing { .. }) => return format!("key not found at segment {}", segment),
+                None => return format!("key at segment {} is repeated or its object is malformed", segment),
+            },
+            _ => return format!("segment {} steps into a value that isn't an object or array", segment),
+        };
+    }
+
+    if opts.only_if_absent || opts.error_on_nochange {
+        return "only_if_absent or error_on_nochange need the parser to report the value".to_string();
+    }
+    if opts.verify {
+        return "the spliced result failed verification".to_string();
+    }
+    "the edit can't be made by splicing".to_string()
+}
+
+/// Set a raw json value that is already known to be valid when optimistic
+fn set_raw_trusted(
+    json: &str,
+    path: &str,
+    value: &str,
+    opts: Option<&Options>,
+) -> Result<String, SjsonError> {
+    let mut buf = String::new();
+    set_raw_trusted_edit(json.as_bytes(), path, value, opts)?.write_into(json, &mut buf, opts)?;
+    Ok(buf)
+}
+
+/// The edit of [`set_raw_trusted`], like [`set_raw_edit`] without checking
+/// the value
+fn set_raw_trusted_edit(json: &[u8], path: &str, value: &str, opts: Option<&Options>) -> Result<Edit, SjsonError> {
+    // Try optimistic path replacement if enabled
+    if use_optimistic(path, opts) {
+        if let Some(edit) = optimistic_set_raw(json, path, value, opts).and_then(|splice| Edit::optimistic(splice, json, opts)) {
+            return Ok(edit);
+        }
+    } else if let Some(edit) = serde_json::from_str(value).ok().as_ref().and_then(json_scalar_text)
+        .and_then(|text| scalar_splice_at(json, path, &text, opts))
+        .and_then(|splice| Edit::scalar(splice, json)) {
+        return Ok(edit);
+    }
+    report_fallback(json, path, opts, false);
+
+    // Parse the raw value as JSON
+    let json_value = serde_json::from_str::<Parsed>(value)?;
+
+    // Parse the original JSON
+    let mut parsed = parse_document::<Parsed>(json, opts)?;
+
+    // Set the value
+    set_value_at(&mut parsed, path, json_value, opts)?;
+
+    Ok(Edit::Tree { value: parsed, size_hint: json.len() + value.len() })
+}
+
+/// Delete deletes a value from json for the specified path.
+pub fn delete(json: &str, path: &str) -> Result<String, SjsonError> {
+    delete_options(json, path, None)
+}
+
+/// DeleteOptions deletes a value from json for the specified path with options.
+pub fn delete_options(json: &str, path: &str, opts: Option<&Options>) -> Result<String, SjsonError> {
+    let mut buf = String::new();
+    delete_into(&mut buf, json, path, opts)?;
+    Ok(buf)
+}
+
+/// Write the result of [`delete_options`] into `buf`, like [`set_into`]
+fn delete_into(buf: &mut String, json: &str, path: &str, opts: Option<&Options>) -> Result<(), SjsonError> {
+    delete_edit(json.as_bytes(), path, opts)?.write_into(json, buf, opts)
+}
+
+/// Work out the edit of [`delete_options`], [`delete_bytes_options`] and
+/// [`delete_to_writer`]
+fn delete_edit(json: &[u8], path: &str, opts: Option<&Options>) -> Result<Edit, SjsonError> {
+    if path.is_empty() {
+        return Err(SjsonError::EmptyPath);
+    }
+
+    // Try optimistic path deletion if enabled
+    if use_optimistic(path, opts) {
+        if let Some(edit) = optimistic_delete(json, path, opts).and_then(|splice| Edit::optimistic(splice, json, opts)) {
+            return Ok(edit);
+        }
+    }
+    report_fallback(json, path, opts, true);
+
+    let mut parsed = parse_document::<Parsed>(json, opts)?;
+
+    delete_value_at(&mut parsed, path, opts)?;
+
+    Ok(Edit::Tree { value: parsed, size_hint: json.len() })
+}
+
+/// Delete the value at `path` by mutating `root` in place, returning the
+/// removed value
+fn delete_value_at<V: Tree>(root: &mut V, path: &str, opts: Option<&Options>) -> Result<V, SjsonError> {
+    if path == ROOT_PATH {
+        return delete_segments_at(root, &[], opts);
+    }
+    delete_segments_at(root, &path_segments(path, separator(opts))?, opts)
+}
+
+/// Delete the value at the path given by `segments`. The root itself can't
+/// be deleted, so no segments is `SjsonError::InvalidPath`.
+/// With `prune_empty`, a parent left empty is deleted in turn.
+fn delete_segments_at<V: Tree>(root: &mut V, segments: &[Segment], opts: Option<&Options>) -> Result<V, SjsonError> {
+    check_depth(segments, opts)?;
+
+    let Some((final_part, parents)) = segments.split_last() else {
+        return Err(SjsonError::InvalidPath);
+    };
+
+    if matches!(root.node(), Node::Scalar) {
+        return Err(SjsonError::JsonMustBeObjectOrArray);
+    }
+
+    let strict_indices = opts.is_some_and(|o| o.strict_indices);
+    let mut current = &mut *root;
+
+    // Navigate to the parent of the target
+    for (i, part) in parents.iter().enumerate() {
+        if *part == Segment::Wildcard {
+            return delete_each(current, &segments[i + 1..], opts);
+        }
+
+        match current.node_mut() {
+            Node::Object(map) => {
+                let key = part.object_key(strict_indices)?;
+                if !map.contains_key(&key) {
+                    return Err(SjsonError::NoChange);
+                }
+                current = map.get_mut(&key).unwrap();
+            }
+            Node::Array(arr) => {
+                let index = part.element_index(arr)?;
+                if index >= arr.len() {
+                    return Err(SjsonError::NoChange);
+                }
+                current = &mut arr[index];
+            }
+            Node::Scalar => {
+                return Err(SjsonError::NoChange);
+            }
+        }
+    }
+
+    // Delete the final value
+    let removed = match current.node_mut() {
+        _ if *final_part == Segment::Wildcard => {
+            let emptied = match current.node() {
+                Node::Object(_) => V::object(),
+                Node::Array(_) => V::array(),
+                // A scalar has no children to remove, which `strict_wildcards` refuses
+                Node::Scalar => {
+                    wildcard_children(current, None, opts)?;
+                    return Err(SjsonError::NoChange);
+                }
+            };
+            if *current == emptied {
+                return Err(SjsonError::NoChange);
+            }
+            core::mem::replace(current, emptied)
+        }
+        Node::Object(map) => map.remove(&final_part.object_key(strict_indices)?).ok_or(SjsonError::NoChange)?,
+        Node::Array(arr) => {
+            let removed = match *final_part {
+                Segment::Slice { start, end } => {
+                    let range = Segment::slice_range(start, end, arr.len())?;
+                    if range.is_empty() {
+                        return Err(SjsonError::NoChange);
+                    }
+                    array_of(arr.drain(range).collect())
+                }
+                _ => {
+                    let index = final_part.element_index(arr)?;
+                    if index >= arr.len() {
+                        return Err(SjsonError::NoChange);
+                    }
+                    arr.remove(index)
+                }
+            };
+
+            let trim_trailing_nulls = opts.map(|o| o.trim_trailing_nulls).unwrap_or(false);
+            if trim_trailing_nulls {
+                while arr.last().is_some_and(|v| *v == V::null()) {
+                    arr.pop();
+                }
+            }
+            removed
+        }
+        Node::Scalar => return Err(SjsonError::NoChange),
+    };
+
+    let prune_empty = opts.is_some_and(|o| o.prune_empty);
+    if prune_empty && !parents.is_empty() && (*current == V::object() || *current == V::array()) {
+        delete_segments_at(root, parents, opts)?;
+    }
+    Ok(removed)
+}
+
+/// Delete `rest` under every child of `current`, which a `*` segment stepped
+/// into, and return the removed values as an array. Children are picked as
+/// [`set_each`] picks them. `prune_empty` stops at the children: a child left
+/// empty is pruned from its own parent only when `rest` goes deeper than it.
+fn delete_each<V: Tree>(current: &mut V, rest: &[Segment], opts: Option<&Options>) -> Result<V, SjsonError> {
+    let snapshot = current.clone();
+    let mut removed = Vec::new();
+    for child in wildcard_children(current, rest.first(), opts)? {
+        match delete_segments_at(child, rest, opts) {
+            Ok(value) => removed.push(value),
+            Err(SjsonError::NoChange) => {}
+            Err(e) => {
+                *current = snapshot;
+                return Err(e);
+            }
+        }
+    }
+
+    if removed.is_empty() {
+        return Err(SjsonError::NoChange);
+    }
+    Ok(array_of(removed))
+}
+
+/// Scalar is a string, number, bool or null for [`set_scalar`]. It is
+/// stored as the json value it is, rather than read from text the way
+/// [`set`] reads its value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scalar<'a> {
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    /// A NaN or infinite float has no json form and is stored as null, like
+    /// serde_json does
+    F64(f64),
+    Str(&'a str),
+    Null,
+}
+
+impl Scalar<'_> {
+    fn into_json(self) -> JsonValue {
+        match self {
+            Scalar::Bool(b) => JsonValue::Bool(b),
+            Scalar::I64(n) => JsonValue::from(n),
+            Scalar::U64(n) => JsonValue::from(n),
+            Scalar::F64(n) => JsonValue::from(n),
+            Scalar::Str(s) => JsonValue::String(s.to_string()),
+            Scalar::Null => JsonValue::Null,
+        }
+    }
+}
+
+/// SetScalar sets a string, number, bool or null for the specified path.
+/// The value is built directly instead of being guessed from text, so
+/// `Scalar::Str("42")` stays a string and `Scalar::U64(u64::MAX)` keeps
+/// every digit, also with `go_sjson_compat`. `Scalar::Null` deletes the
+/// path when `treat_null_as_delete` is set.
+pub fn set_scalar(json: &str, path: &str, value: Scalar<'_>, opts: Option<&Options>) -> Result<String, SjsonError> {
+    if path.is_empty() {
+        return Err(SjsonError::EmptyPath);
+    }
+
+    let treat_null_as_delete = opts.map(|o| o.treat_null_as_delete).unwrap_or(false);
+    if treat_null_as_delete && value == Scalar::Null {
+        return delete_options(json, path, opts);
+    }
+
+    let value = value.into_json();
+    let raw = serde_json::to_string(&value).expect("serializing a scalar can't fail");
+    if use_optimistic(path, opts) {
+        if let Some(result) = optimistic_set_raw(json.as_bytes(), path, &raw, opts).and_then(|splice| verified(splice.apply_str(json), opts)) {
+            return Ok(result);
+        }
+    } else if let Some(result) = scalar_splice_str(json, path, &raw, opts) {
+        return Ok(result);
+    }
+    report_fallback(json.as_bytes(), path, opts, false);
+
+    let mut parsed = parse_document::<Parsed>(json.as_bytes(), opts)?;
+
+    set_value_at(&mut parsed, path, Parsed::from_json(value), opts)?;
+
+    to_json_string(&mut parsed, opts, json.len())
+}
+
+/// Set a string value. Unlike [`set`], the value is always stored as a
+/// json string, even when it looks like a number, a bool or `null`.
+pub fn set_string(json: &str, path: &str, value: &str, opts: Option<&Options>) -> Result<String, SjsonError> {
+    set_scalar(json, path, Scalar::Str(value), opts)
+}
+
+/// Set a boolean value
+pub fn set_bool(json: &str, path: &str, value: bool, opts: Option<&Options>) -> Result<String, SjsonError> {
+    set_scalar(json, path, Scalar::Bool(value), opts)
+}
+
+/// Set `raw`, the text of a typed value, like `set_options`. A number is
+/// written as json even with `go_sjson_compat`, which would otherwise store
+/// it as a string.
+fn set_typed(json: &str, path: &str, raw: &str, opts: Option<&Options>) -> Result<String, SjsonError> {
+    if opts.is_some_and(|o| o.go_sjson_compat) && is_json_number(raw) {
+        return set_raw_trusted(json, path, raw, opts);
+    }
+    set_options(json, path, raw, opts)
+}
+
+/// Set an integer value
+pub fn set_int<T: fmt::Display>(
+    json: &str,
+    path: &str,
+    value: T,
+    opts: Option<&Options>,
+) -> Result<String, SjsonError> {
+    let raw = value.to_string();
+    set_typed(json, path, &raw, opts)
+}
+
+/// Set a `u64` value. The number is written as a json number token and set
+/// like [`set_raw`], so the full range up to `u64::MAX` is kept exactly.
+pub fn set_u64(json: &str, path: &str, value: u64, opts: Option<&Options>) -> Result<String, SjsonError> {
+    set_scalar(json, path, Scalar::U64(value), opts)
+}
+
+/// Set an `i128` value exactly. Numbers outside the `i64`/`u64` range need
+/// the `arbitrary_precision` feature to be stored without rounding.
+#[cfg(feature = "arbitrary_precision")]
+pub fn set_i128(json: &str, path: &str, value: i128, opts: Option<&Options>) -> Result<String, SjsonError> {
+    set_raw_trusted(json, path, &value.to_string(), opts)
+}
+
+/// Set a `u128` value exactly, see [`set_i128`].
+#[cfg(feature = "arbitrary_precision")]
+pub fn set_u128(json: &str, path: &str, value: u128, opts: Option<&Options>) -> Result<String, SjsonError> {
+    set_raw_trusted(json, path, &value.to_string(), opts)
+}
+
+/// Set a UTC timestamp as an RFC 3339 string, formatted like
+/// `DateTime::to_rfc3339`.
+#[cfg(feature = "chrono")]
+pub fn set_datetime(
+    json: &str,
+    path: &str,
+    dt: chrono::DateTime<chrono::Utc>,
+    opts: Option<&Options>,
+) -> Result<String, SjsonError> {
+    set_raw_trusted(json, path, &format!("\"{}\"", dt.to_rfc3339()), opts)
+}
+
+/// Set a calendar date as a `YYYY-MM-DD` string.
+#[cfg(feature = "chrono")]
+pub fn set_date(json: &str, path: &str, date: chrono::NaiveDate, opts: Option<&Options>) -> Result<String, SjsonError> {
+    set_raw_trusted(json, path, &format!("\"{}\"", date), opts)
+}
+
+/// Set a float value
+pub fn set_float<T: fmt::Display>(
+    json: &str,
+    path: &str,
+    value: T,
+    opts: Option<&Options>,
+) -> Result<String, SjsonError> {
+    let raw = value.to_string();
+    set_typed(json, path, &raw, opts)
+}
+
+/// Generic Set function that accepts any value that can be serialized to JSON
+pub fn set_value<T: serde::Serialize>(
+    json: &str,
+    path: &str,
+    value: &T,
+    opts: Option<&Options>,
+) -> Result<String, SjsonError> {
+    let json_value = serde_json::to_string(value)
+        .map_err(|e| SjsonError::Serialize(Arc::new(e)))?;
+    
+    set_raw_options(json, path, &json_value, opts)
+}
+
+/// SetJsonValue sets an already constructed `serde_json::Value` for the
+/// specified path. The value is moved into the parsed document directly, so
+/// unlike `set_value` it is never serialized and parsed again.
+pub fn set_json_value(
+    json: &str,
+    path: &str,
+    value: JsonValue,
+    opts: Option<&Options>,
+) -> Result<String, SjsonError> {
+    let mut parsed = parse_document::<Parsed>(json.as_bytes(), opts)?;
+
+    set_value_at(&mut parsed, path, Parsed::from_json(value), opts)?;
+
+    to_json_string(&mut parsed, opts, json.len())
+}
+
+/// SetInValue sets `value` at the specified path of an already parsed
+/// `serde_json::Value`, mutating it in place with the same semantics as
+/// [`set_options`], so a document that is kept as a `Value` doesn't have to
+/// be serialized and parsed again for every edit. An edit that fails
+/// leaves `root` as it was.
+pub fn set_in_value(
+    root: &mut JsonValue,
+    path: &str,
+    value: JsonValue,
+    opts: Option<&Options>,
+) -> Result<(), SjsonError> {
+    set_value_at(root, path, value, opts)?;
+    Ok(())
+}
+
+/// DeleteInValue deletes the value at the specified path of an already
+/// parsed `serde_json::Value` in place, like [`delete_options`].
+pub fn delete_in_value(root: &mut JsonValue, path: &str, opts: Option<&Options>) -> Result<(), SjsonError> {
+    delete_value_at(root, path, opts)?;
+    Ok(())
+}
+
+/// SetRawValue sets a premarshalled json value for the specified path.
+/// A `RawValue` is already known to be valid json, so in optimistic mode its
+/// text is spliced in as is without being parsed again.
+pub fn set_raw_value(
+    json: &str,
+    path: &str,
+    value: &serde_json::value::RawValue,
+    opts: Option<&Options>,
+) -> Result<String, SjsonError> {
+    set_raw_trusted(json, path, value.get(), opts)
+}
+
+/// SetInPlace sets a json value for the specified path, storing the result
+/// back into `buf`.
+/// With both `optimistic` and `replace_in_place` set, an existing value that
+/// is replaced by one of the same or shorter length is edited directly in
+/// `buf` without allocating. Anything else, such as a longer value or a path
+/// that needs the parser, builds a new string and moves it into `buf`.
+/// With `verify` set the result has to be checked before it replaces `buf`,
+/// so the direct edit is skipped.
+pub fn set_in_place(buf: &mut String, path: &str, value: &str, opts: Option<&Options>) -> Result<(), SjsonError> {
+    if path.is_empty() {
+        return Err(SjsonError::EmptyPath);
+    }
+
+    let treat_null_as_delete = opts.map(|o| o.treat_null_as_delete).unwrap_or(false);
+    if treat_null_as_delete && parse_value::<JsonValue>(value, opts).is_ok_and(|v| v.is_null()) {
+        *buf = delete_options(buf, path, opts)?;
+        return Ok(());
+    }
+
+    let replace_in_place = opts.map(|o| o.replace_in_place && !o.verify).unwrap_or(false);
+
+    if replace_in_place && use_optimistic(path, opts) {
+        if let Some(splice) = optimistic_set(buf.as_bytes(), path, value, opts) {
+            if splice.value.len() <= splice.end - splice.start {
+                buf.replace_range(splice.start..splice.end, &splice.value);
+            } else {
+                *buf = splice.apply_str(buf);
+            }
+            return Ok(());
+        }
+    }
+
+    *buf = set_options(buf, path, value, opts)?;
+    Ok(())
+}
+
+/// SetCow sets a json value for the specified path like `set_options`, but
+/// borrows `json` unchanged when the path already holds an equal value, so
+/// only real edits allocate a new string. With `error_on_nochange` or
+/// `only_if_absent` set, `SjsonError::NoChange` is still returned instead.
+pub fn set_cow<'a>(
+    json: &'a str,
+    path: &str,
+    value: &str,
+    opts: Option<&Options>,
+) -> Result<Cow<'a, str>, SjsonError> {
+    let mut cow_opts = opts.cloned().unwrap_or_default();
+    let report_nochange = cow_opts.error_on_nochange || cow_opts.only_if_absent;
+    cow_opts.error_on_nochange = true;
+
+    match set_options(json, path, value, Some(&cow_opts)) {
+        Ok(result) => Ok(Cow::Owned(result)),
+        Err(SjsonError::NoChange) if !report_nochange => Ok(Cow::Borrowed(json)),
+        Err(e) => Err(e),
+    }
+}
+
+/// SetRawIfChanged sets a raw json value for the specified path like
+/// `set_raw_options`, but returns `None` when the path already holds an
+/// equal value, so reconciliation loops can skip writing the document back.
+/// Values are compared as json, so objects are equal regardless of key
+/// order. With `error_on_nochange` or `only_if_absent` set,
+/// `SjsonError::NoChange` is still returned instead.
+pub fn set_raw_if_changed(
+    json: &str,
+    path: &str,
+    value: &str,
+    opts: Option<&Options>,
+) -> Result<Option<String>, SjsonError> {
+    let mut changed_opts = opts.cloned().unwrap_or_default();
+    let report_nochange = changed_opts.error_on_nochange || changed_opts.only_if_absent;
+    changed_opts.error_on_nochange = true;
+
+    match set_raw_options(json, path, value, Some(&changed_opts)) {
+        Ok(result) => Ok(Some(result)),
+        Err(SjsonError::NoChange) if !report_nochange => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// DeleteCow deletes a value from json for the specified path like
+/// `delete_options`, but borrows `json` unchanged when there is nothing to
+/// delete instead of returning `SjsonError::NoChange`.
+pub fn delete_cow<'a>(json: &'a str, path: &str, opts: Option<&Options>) -> Result<Cow<'a, str>, SjsonError> {
+    match delete_options(json, path, opts) {
+        Ok(result) => Ok(Cow::Owned(result)),
+        Err(SjsonError::NoChange) => Ok(Cow::Borrowed(json)),
+        Err(e) => Err(e),
+    }
+}
+
+/// SetWithOld sets a json value for the specified path like `set_options`
+/// and also returns the value that was there before, or `None` if the path
+/// was newly created. The old value is read from the parsed json, the
+/// result is spliced like `set_options` does whenever it can be. A path with
+/// a `*` segment has no single old value and is `SjsonError::InvalidPath`.
+pub fn set_with_old(
+    json: &str,
+    path: &str,
+    value: &str,
+    opts: Option<&Options>,
+) -> Result<(String, Option<JsonValue>), SjsonError> {
+    if path.is_empty() {
+        return Err(SjsonError::EmptyPath);
+    }
+    single_target(path, opts)?;
+
+    let treat_null_as_delete = opts.map(|o| o.treat_null_as_delete).unwrap_or(false);
+    if treat_null_as_delete && parse_value::<JsonValue>(value, opts).is_ok_and(|v| v.is_null()) {
+        return delete_with_old(json, path, opts);
+    }
+
+    let mut parsed = parse_document::<Parsed>(json.as_bytes(), opts)?;
+
+    if let Some(edit) = set_splice(json.as_bytes(), path, value, opts) {
+        let old = get_value_at(&parsed, path, separator(opts)).cloned().map(Tree::into_json);
+        let mut result = String::new();
+        edit.write_into(json, &mut result, opts)?;
+        return Ok((result, old));
+    }
+    report_fallback(json.as_bytes(), path, opts, false);
+
+    let old = set_value_at(&mut parsed, path, parse_value(value, opts)?, opts)?;
+
+    let result = to_json_string(&mut parsed, opts, json.len() + value.len())?;
+    Ok((result, old.map(Tree::into_json)))
+}
+
+/// Refuse a path with a `*` segment, for the functions that report on the
+/// one value an edit changed
+fn single_target(path: &str, opts: Option<&Options>) -> Result<(), SjsonError> {
+    if path != ROOT_PATH && path_segments(path, separator(opts))?.contains(&Segment::Wildcard) {
+        return Err(SjsonError::InvalidPath);
+    }
+    Ok(())
+}
+
+/// SetLocated sets a json value for the specified path like `set_options`
+/// and also returns the byte range of the result that holds the new value,
+/// for tools that highlight where an edit landed. `treat_null_as_delete`
+/// doesn't apply, as a deleted value has nowhere to point to, and a path
+/// with a `*` segment, which has many targets, is `SjsonError::InvalidPath`.
+pub fn set_located(
+    json: &str,
+    path: &str,
+    value: &str,
+    opts: Option<&Options>,
+) -> Result<(String, Range<usize>), SjsonError> {
+    if path.is_empty() {
+        return Err(SjsonError::EmptyPath);
+    }
+    single_target(path, opts)?;
+
+    if let Some(edit) = set_splice(json.as_bytes(), path, value, opts) {
+        let splice = edit.splice().expect("set_splice only splices");
+        // The value is always last in the splice, after any comma or key
+        let end = splice.start + splice.value.len();
+        let len = if use_optimistic(path, opts) { optimistic_value(value, opts).len() } else { splice.value.len() };
+        let mut result = String::new();
+        edit.write_into(json, &mut result, opts)?;
+        return Ok((result, end - len..end));
+    }
+    report_fallback(json.as_bytes(), path, opts, false);
+
+    let mut parsed = parse_document::<Parsed>(json.as_bytes(), opts)?;
+    let mut marked = parsed.clone();
+
+    let value: Parsed = parse_value(value, opts)?;
+    // A stand-in that differs from the value in its first and last byte, so
+    // the two serializations share exactly the text around the value
+    let marker = if value == Parsed::null() { Parsed::from_json(JsonValue::Bool(true)) } else { Parsed::null() };
+    set_value_at(&mut parsed, path, value, opts)?;
+    // The edit itself has passed, the marker may equal what it replaces
+    let marker_opts = opts.map(|o| Options { error_on_nochange: false, only_if_absent: false, ..o.clone() });
+    set_value_at(&mut marked, path, marker, marker_opts.as_ref())?;
+
+    let result = to_json_string(&mut parsed, opts, json.len())?;
+    let other = to_json_string(&mut marked, opts, json.len())?;
+    let (a, b) = (result.as_bytes(), other.as_bytes());
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a.iter().rev().zip(b.iter().rev()).take_while(|(x, y)| x == y).count()
+        .min(a.len().min(b.len()) - prefix);
+    let end = result.len() - suffix;
+    Ok((result, prefix..end))
+}
+
+/// DeleteWithOld deletes a value from json for the specified path like
+/// `delete_options` and also returns the removed value, which is always
+/// `Some` as a missing path is `SjsonError::NoChange`.
+pub fn delete_with_old(
+    json: &str,
+    path: &str,
+    opts: Option<&Options>,
+) -> Result<(String, Option<JsonValue>), SjsonError> {
+    if path.is_empty() {
+        return Err(SjsonError::EmptyPath);
+    }
+
+    let mut parsed = parse_document::<Parsed>(json.as_bytes(), opts)?;
+
+    let old = delete_value_at(&mut parsed, path, opts)?;
+
+    let result = to_json_string(&mut parsed, opts, json.len())?;
+    Ok((result, Some(old.into_json())))
+}
+
+/// ChangeKind tells what [`set_reporting`] did to the value at the path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// The path didn't exist and was created
+    Created,
+    /// A different value was overwritten
+    Updated,
+    /// The path already held an equal value, or with
+    /// `Options::only_if_absent` any value, so nothing was written
+    Unchanged,
+}
+
+/// DeleteKind tells what [`delete_reporting`] did to the value at the path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeleteKind {
+    /// The value was removed
+    Removed,
+    /// There was nothing at the path to remove
+    NotFound,
+}
+
+/// SetReporting sets a json value for the specified path like `set_options`
+/// and also reports whether it created, updated or left the value alone.
+/// An unchanged document is returned as given instead of failing with
+/// `SjsonError::NoChange`. The prior value is inspected in the parsed json,
+/// so the optimistic fast path is not used, and `treat_null_as_delete`
+/// doesn't apply. A path with a `*` segment may create some children and
+/// update others, so it is `SjsonError::InvalidPath`.
+pub fn set_reporting(
+    json: &str,
+    path: &str,
+    value: &str,
+    opts: Option<&Options>,
+) -> Result<(String, ChangeKind), SjsonError> {
+    if path.is_empty() {
+        return Err(SjsonError::EmptyPath);
+    }
+    single_target(path, opts)?;
+
+    let mut parsed = parse_document::<Parsed>(json.as_bytes(), opts)?;
+    let value: Parsed = parse_value(value, opts)?;
+
+    let kind = match set_value_at(&mut parsed, path, value.clone(), opts) {
+        Ok(None) => ChangeKind::Created,
+        Ok(Some(old)) if old == value => ChangeKind::Unchanged,
+        Ok(Some(_)) => ChangeKind::Updated,
+        Err(SjsonError::NoChange) => ChangeKind::Unchanged,
+        Err(e) => return Err(e),
+    };
+    if kind == ChangeKind::Unchanged {
+        return Ok((json.to_string(), kind));
+    }
+
+    let result = to_json_string(&mut parsed, opts, json.len())?;
+    Ok((result, kind))
+}
+
+/// DeleteReporting deletes a value from json for the specified path like
+/// `delete_options` and also reports whether there was a value to remove.
+/// A missing path returns the document as given instead of failing with
+/// `SjsonError::NoChange`.
+pub fn delete_reporting(json: &str, path: &str, opts: Option<&Options>) -> Result<(String, DeleteKind), SjsonError> {
+    match delete_options(json, path, opts) {
+        Ok(result) => Ok((result, DeleteKind::Removed)),
+        Err(SjsonError::NoChange) => Ok((json.to_string(), DeleteKind::NotFound)),
+        Err(e) => Err(e),
+    }
+}
+
+/// SetAll sets a json value at `child_path` within every element of the
+/// array at `array_path`, with the same semantics as [`set`].
+/// Elements that aren't objects are skipped, or rejected with
+/// `SjsonError::JsonMustBeObjectOrArray` when `error_on_non_object` is set.
+/// If no element changes, for example because the array is empty,
+/// `SjsonError::NoChange` is returned.
+pub fn set_all(
+    json: &str,
+    array_path: &str,
+    child_path: &str,
+    value: &str,
+    opts: Option<&Options>,
+) -> Result<String, SjsonError> {
+    if array_path.is_empty() || child_path.is_empty() {
+        return Err(SjsonError::EmptyPath);
+    }
+
+    let error_on_non_object = opts.map(|o| o.error_on_non_object).unwrap_or(false);
+
+    let mut parsed = parse_document::<Parsed>(json.as_bytes(), opts)?;
+    let json_value: Parsed = parse_value(value, opts)?;
+
+    let arr = match get_value_at_mut(&mut parsed, array_path, separator(opts)).map(Tree::node_mut) {
+        Some(Node::Array(arr)) => arr,
+        Some(_) => return Err(SjsonError::JsonMustBeObjectOrArray),
+        None => return Err(SjsonError::NoChange),
+    };
+
+    let mut changed = false;
+    for element in arr.iter_mut() {
+        if !matches!(element.node(), Node::Object(_)) {
+            if error_on_non_object {
+                return Err(SjsonError::JsonMustBeObjectOrArray);
+            }
+            continue;
+        }
+        match set_value_at(element, child_path, json_value.clone(), opts) {
+            Ok(_) => changed = true,
+            // An element that already has the value doesn't fail the batch
+            Err(SjsonError::NoChange) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    if !changed {
+        return Err(SjsonError::NoChange);
+    }
+
+    to_json_string(&mut parsed, opts, json.len() + value.len())
+}
+
+/// Merge sets the keys of the json object `fragment` into the object at
+/// `path`. Nested objects are merged recursively and any other value,
+/// including an array, replaces what was there, so arrays are never
+/// concatenated. A missing target is created as a copy of `fragment`.
+/// If `fragment` or the target isn't an object,
+/// `SjsonError::JsonMustBeObjectOrArray` is returned.
+pub fn merge(json: &str, path: &str, fragment: &str, opts: Option<&Options>) -> Result<String, SjsonError> {
+    if path.is_empty() {
+        return Err(SjsonError::EmptyPath);
+    }
+
+    let size_hint = json.len() + fragment.len();
+    let fragment = serde_json::from_str::<Parsed>(fragment)?;
+    if !matches!(fragment.node(), Node::Object(_)) {
+        return Err(SjsonError::JsonMustBeObjectOrArray);
+    }
+
+    let mut parsed = parse_document::<Parsed>(json.as_bytes(), opts)?;
+
+    match get_value_at_mut(&mut parsed, path, separator(opts)) {
+        Some(target) if matches!(target.node(), Node::Object(_)) => merge_value(target, fragment),
+        Some(_) => return Err(SjsonError::JsonMustBeObjectOrArray),
+        None => {
+            set_value_at(&mut parsed, path, fragment, opts)?;
+        }
+    }
+
+    to_json_string(&mut parsed, opts, size_hint)
+}
+
+/// SetRawUnder sets every member of the json object `fragment` under
+/// `prefix_path`, as a shorthand for one [`set_raw_options`] call per key,
+/// but parsing the document only once. The parent is created when it is
+/// missing. Unlike [`merge`], each value replaces what was there as a whole,
+/// nested objects included. A `fragment` that isn't an object is
+/// `SjsonError::JsonMustBeObjectOrArray`, and the first key that can't be
+/// set fails the whole call.
+pub fn set_raw_under(json: &str, prefix_path: &str, fragment: &str, opts: Option<&Options>) -> Result<String, SjsonError> {
+    if prefix_path.is_empty() {
+        return Err(SjsonError::EmptyPath);
+    }
+
+    let size_hint = json.len() + fragment.len();
+    let Some(members) = serde_json::from_str::<Parsed>(fragment)?.into_map() else {
+        return Err(SjsonError::JsonMustBeObjectOrArray);
+    };
+
+    let mut parsed = parse_document::<Parsed>(json.as_bytes(), opts)?;
+
+    let mut segments = match prefix_path {
+        ROOT_PATH => Vec::new(),
+        _ => path_segments(prefix_path, separator(opts))?,
+    };
+    for (key, value) in members {
+        // The keys of the fragment are taken as they are, without escapes
+        segments.push(Segment::Key(key));
+        set_segments_at(&mut parsed, &segments, value, opts)?;
+        segments.pop();
+    }
+
+    to_json_string(&mut parsed, opts, size_hint)
+}
+
+/// Merge `fragment` into `target`, recursing where both are objects
+fn merge_value<V: Tree>(target: &mut V, fragment: V) {
+    if !matches!(target.node(), Node::Object(_)) || !matches!(fragment.node(), Node::Object(_)) {
+        *target = fragment;
+        return;
+    }
+
+    if let (Node::Object(map), Some(fragment)) = (target.node_mut(), fragment.into_map()) {
+        for (key, value) in fragment {
+            match map.get_mut(&key) {
+                Some(existing) => merge_value(existing, value),
+                None => {
+                    map.insert(key, value);
+                }
+            }
+        }
+    }
+}
+
+/// ApplyDefaults fills in the keys of the json object `defaults` that
+/// `target` lacks. It is the reverse of [`merge`]: existing values win,
+/// including `null` and arrays, which are never combined element by element,
+/// and nested objects present in both are filled recursively. If either
+/// document isn't an object, `SjsonError::JsonMustBeObjectOrArray` is
+/// returned.
+pub fn apply_defaults(target: &str, defaults: &str) -> Result<String, SjsonError> {
+    let size_hint = target.len() + defaults.len();
+    let defaults = serde_json::from_str::<Parsed>(defaults)?;
+    let mut parsed = parse_document::<Parsed>(target.as_bytes(), None)?;
+    if !matches!(parsed.node(), Node::Object(_)) || !matches!(defaults.node(), Node::Object(_)) {
+        return Err(SjsonError::JsonMustBeObjectOrArray);
+    }
+
+    fill_defaults(&mut parsed, defaults);
+    to_json_string(&mut parsed, None, size_hint)
+}
+
+/// Insert the members of `defaults` missing from `target`, recursing where
+/// both are objects
+fn fill_defaults<V: Tree>(target: &mut V, defaults: V) {
+    if !matches!(defaults.node(), Node::Object(_)) {
+        return;
+    }
+
+    if let (Node::Object(map), Some(defaults)) = (target.node_mut(), defaults.into_map()) {
+        for (key, value) in defaults {
+            match map.get_mut(&key) {
+                Some(existing) => fill_defaults(existing, value),
+                None => {
+                    map.insert(key, value);
+                }
+            }
+        }
+    }
+}
+
+/// SetPath sets a json value for a [`Path`] parsed ahead of time, so hot
+/// code setting the same path repeatedly doesn't split it on every call.
+/// A path of plain keys and indices is spliced like [`set`] does, one with
+/// escapes or other syntax the byte scan doesn't read goes to the parser.
+pub fn set_path(json: &str, path: &Path, value: &str, opts: Option<&Options>) -> Result<String, SjsonError> {
+    set_segments_options(json, path.segments(), value, opts)
+}
+
+/// Set `value` at `segments` for [`set_path`] and [`set_segments`], through
+/// the splice of [`set`] when the segments can be written as a plain path
+fn set_segments_options(json: &str, segments: &[Segment], value: &str, opts: Option<&Options>) -> Result<String, SjsonError> {
+    if let Some(path) = plain_path(segments, separator(opts)) {
+        if let Some(edit) = set_splice(json.as_bytes(), &path, value, opts) {
+            let mut result = String::new();
+            edit.write_into(json, &mut result, opts)?;
+            return Ok(result);
+        }
+        report_fallback(json.as_bytes(), &path, opts, false);
+    }
+
+    let mut parsed = parse_document::<Parsed>(json.as_bytes(), opts)?;
+
+    set_segments_at(&mut parsed, segments, parse_value(value, opts)?, opts)?;
+
+    to_json_string(&mut parsed, opts, json.len() + value.len())
+}
+
+/// Write `segments` as a path the byte scan reads the same way, or `None`
+/// when a segment is anything but a plain key or index
+fn plain_path(segments: &[Segment], sep: char) -> Option<String> {
+    if segments.is_empty() {
+        return None;
+    }
+
+    let mut path = String::new();
+    for (i, segment) in segments.iter().enumerate() {
+        if i > 0 {
+            path.push(sep);
+        }
+        match segment {
+            // A key that reads as an index or holds the separator would be
+            // read differently
+            Segment::Key(key) if !key.contains(sep) && Segment::literal(key) == *segment => path.push_str(key),
+            Segment::Index(index) => path.push_str(&index.to_string()),
+            _ => return None,
+        }
+    }
+    Some(path)
+}
+
+/// DeletePath deletes a value from json for a [`Path`] parsed ahead of time.
+pub fn delete_path(json: &str, path: &Path, opts: Option<&Options>) -> Result<String, SjsonError> {
+    let mut parsed = parse_document::<Parsed>(json.as_bytes(), opts)?;
+
+    delete_segments_at(&mut parsed, path.segments(), opts)?;
+
+    to_json_string(&mut parsed, opts, json.len())
+}
+
+/// SetSegments sets a json value for a path that is already split into
+/// keys, such as one built by walking a tree. Every key is taken literally,
+/// so separators, backslashes and `#` need no escaping, and keys in the
+/// canonical form of an integer address array elements. Like
+/// [`set_path`], plain keys are spliced the way [`set`] does.
+pub fn set_segments(json: &str, segments: &[&str], value: &str, opts: Option<&Options>) -> Result<String, SjsonError> {
+    set_segments_options(json, &literal_segments(segments)?, value, opts)
+}
+
+/// SetFlattened sets a json value for a flattened key such as `DB__HOST`,
+/// split on `separator` into the keys of a nested path like
+/// [`set_segments`], so dots and other path syntax in the key stay literal.
+/// An empty separator is `SjsonError::InvalidPath`.
+pub fn set_flattened(
+    json: &str,
+    flat_key: &str,
+    value: &str,
+    separator: &str,
+    opts: Option<&Options>,
+) -> Result<String, SjsonError> {
+    if separator.is_empty() {
+        return Err(SjsonError::InvalidPath);
+    }
+    if flat_key.is_empty() {
+        return Err(SjsonError::EmptyPath);
+    }
+    let segments: Vec<&str> = flat_key.split(separator).collect();
+    set_segments(json, &segments, value, opts)
+}
+
+/// DeleteSegments deletes a value from json for a path that is already
+/// split into keys, see [`set_segments`].
+pub fn delete_segments(json: &str, segments: &[&str], opts: Option<&Options>) -> Result<String, SjsonError> {
+    let segments = literal_segments(segments)?;
+    let mut parsed = parse_document::<Parsed>(json.as_bytes(), opts)?;
+
+    delete_segments_at(&mut parsed, &segments, opts)?;
+
+    to_json_string(&mut parsed, opts, json.len())
+}
+
+/// SetMany sets several json values in one go.
+/// The json is parsed once, each `(path, value)` edit is applied in order, so
+/// later edits see the effects of earlier ones, and the result is serialized
+/// once. If an edit fails, `SjsonError::EditFailed` reports its index.
+/// As the whole tree is serialized, nothing is spliced: like the parser path
+/// of [`set`], keys come out sorted without `ordered` or `preserve_order`.
+pub fn set_many(json: &str, edits: &[(&str, &str)], opts: Option<&Options>) -> Result<String, SjsonError> {
+    let mut parsed = parse_document::<Parsed>(json.as_bytes(), opts)?;
+
+    for (index, (path, value)) in edits.iter().enumerate() {
+        let result = if path.is_empty() {
+            Err(SjsonError::EmptyPath)
+        } else {
+            set_value_at(&mut parsed, path, parse_value(value, opts)?, opts)
+        };
+        result.map_err(|e| SjsonError::EditFailed { index, source: Box::new(e) })?;
+    }
+
+    to_json_string(&mut parsed, opts, json.len())
+}
+
+/// DeleteMany deletes several paths in one go.
+/// The json is parsed once and the paths are deleted left to right, so a
+/// later path sees the effects of earlier deletes: after deleting `list.0`
+/// the old `list.1` is addressed as `list.0`. If a delete fails,
+/// `SjsonError::EditFailed` reports its index. A path that doesn't exist
+/// fails with `SjsonError::NoChange` unless `ignore_missing` is set.
+pub fn delete_many(json: &str, paths: &[&str], opts: Option<&Options>) -> Result<String, SjsonError> {
+    let ignore_missing = opts.map(|o| o.ignore_missing).unwrap_or(false);
+
+    let mut parsed = parse_document::<Parsed>(json.as_bytes(), opts)?;
+
+    for (index, path) in paths.iter().enumerate() {
+        let result = if path.is_empty() {
+            Err(SjsonError::EmptyPath)
+        } else {
+            delete_value_at(&mut parsed, path, opts).map(drop)
+        };
+        match result {
+            Err(SjsonError::NoChange) if ignore_missing => {}
+            result => result.map_err(|e| SjsonError::EditFailed { index, source: Box::new(e) })?,
+        }
+    }
+
+    to_json_string(&mut parsed, opts, json.len())
+}
+
+/// Apply several edits to a json document in order, threading the result
+/// of each edit into the next one.
+///
+/// `path => value` calls [`set`], `path => raw value` calls [`set_raw`] and
+/// `delete path` calls [`delete`]. Paths and values can be any expression
+/// that is `AsRef<str>`. The first failing edit stops the chain and its error
+/// is returned.
+///
+/// ```
+/// let json = sjson::sjson_set!(r#"{"a":{"d":0}}"#,
+///     "a.b" => "1",
+///     "a.c" => raw "[1,2]",
+///     delete "a.d",
+/// ).unwrap();
+/// assert_eq!(json, r#"{"a":{"b":1,"c":[1,2]}}"#);
+/// ```
+#[macro_export]
+macro_rules! sjson_set {
+    (@apply $json:ident;) => {};
+    (@apply $json:ident; delete $path:expr $(, $($rest:tt)*)?) => {
+        $json = $crate::delete(&$json, ::core::convert::AsRef::<str>::as_ref(&$path))?;
+        $crate::sjson_set!(@apply $json; $($($rest)*)?);
+    };
+    (@apply $json:ident; $path:expr => raw $value:expr $(, $($rest:tt)*)?) => {
+        $json = $crate::set_raw(
+            &$json,
+            ::core::convert::AsRef::<str>::as_ref(&$path),
+            ::core::convert::AsRef::<str>::as_ref(&$value),
+        )?;
+        $crate::sjson_set!(@apply $json; $($($rest)*)?);
+    };
+    (@apply $json:ident; $path:expr => $value:expr $(, $($rest:tt)*)?) => {
+        $json = $crate::set(
+            &$json,
+            ::core::convert::AsRef::<str>::as_ref(&$path),
+            ::core::convert::AsRef::<str>::as_ref(&$value),
+        )?;
+        $crate::sjson_set!(@apply $json; $($($rest)*)?);
+    };
+    ($json:expr, $($edits:tt)*) => {
+        (|| -> ::core::result::Result<$crate::__private::String, $crate::SjsonError> {
+            let mut json = $crate::__private::String::from(::core::convert::AsRef::<str>::as_ref(&$json));
+            $crate::sjson_set!(@apply json; $($edits)*);
+            Ok(json)
+        })()
+    };
+}
+
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::string::String;
+}
+
+/// SetBytes sets a json value for the specified path.
+/// This function works the same as Set except that the json is a byte slice
+/// and a new byte vector is returned, so callers holding raw bytes don't need
+/// to convert them to a string first.
+pub fn set_bytes(json: &[u8], path: &str, value: &str) -> Result<Vec<u8>, SjsonError> {
+    set_bytes_options(json, path, value, None)
+}
+
+/// SetBytesOptions sets a json value for the specified path with options.
+pub fn set_bytes_options(
+    json: &[u8],
+    path: &str,
+    value: &str,
+    opts: Option<&Options>,
+) -> Result<Vec<u8>, SjsonError> {
+    set_edit(json, path, value, opts)?.into_bytes(json, opts)
+}
+
+/// SetRawBytes sets a raw json value for the specified path.
+pub fn set_raw_bytes(json: &[u8], path: &str, value: &str) -> Result<Vec<u8>, SjsonError> {
+    set_raw_bytes_options(json, path, value, None)
+}
+
+/// SetRawBytesOptions sets a raw json value for the specified path with options.
+pub fn set_raw_bytes_options(
+    json: &[u8],
+    path: &str,
+    value: &str,
+    opts: Option<&Options>,
+) -> Result<Vec<u8>, SjsonError> {
+    set_raw_edit(json, path, value, opts)?.into_bytes(json, opts)
+}
+
+/// DeleteBytes deletes a value from json for the specified path.
+pub fn delete_bytes(json: &[u8], path: &str) -> Result<Vec<u8>, SjsonError> {
+    delete_bytes_options(json, path, None)
+}
+
+/// DeleteBytesOptions deletes a value from json for the specified path with options.
+pub fn delete_bytes_options(json: &[u8], path: &str, opts: Option<&Options>) -> Result<Vec<u8>, SjsonError> {
+    delete_edit(json, path, opts)?.into_bytes(json, opts)
+}
+
+/// SetToWriter sets a json value for the specified path like `set_options`
+/// and writes the result into `writer` instead of returning a string, so it
+/// can be streamed into a file or a response body.
+/// The optimistic path writes the untouched parts of `json` around the new
+/// value directly, the parser path serializes the edited tree into `writer`.
+#[cfg(feature = "std")]
+pub fn set_to_writer<W: std::io::Write>(
+    writer: &mut W,
+    json: &str,
+    path: &str,
+    value: &str,
+    opts: Option<&Options>,
+) -> Result<(), SjsonError> {
+    set_edit(json.as_bytes(), path, value, opts)?.write_to(writer, json.as_bytes(), opts)
+}
+
+/// SetRawToWriter sets a raw json value for the specified path like
+/// `set_raw_options` and writes the result into `writer`.
+#[cfg(feature = "std")]
+pub fn set_raw_to_writer<W: std::io::Write>(
+    writer: &mut W,
+    json: &str,
+    path: &str,
+    value: &str,
+    opts: Option<&Options>,
+) -> Result<(), SjsonError> {
+    set_raw_edit(json.as_bytes(), path, value, opts)?.write_to(writer, json.as_bytes(), opts)
+}
+
+/// DeleteToWriter deletes a value from json for the specified path like
+/// `delete_options` and writes the result into `writer`.
+#[cfg(feature = "std")]
+pub fn delete_to_writer<W: std::io::Write>(
+    writer: &mut W,
+    json: &str,
+    path: &str,
+    opts: Option<&Options>,
+) -> Result<(), SjsonError> {
+    delete_edit(json.as_bytes(), path, opts)?.write_to(writer, json.as_bytes(), opts)
+}
+
+/// Document is a parsed json document for applying many edits in a row.
+/// The json is parsed once and every `set`, `set_raw` and `delete` mutates the
+/// parsed value in place, so it is only serialized again when the result is
+/// needed. An edit that fails leaves the document as it was. The document
+/// is kept in the same tree as the string functions use, so with the
+/// `ordered` feature its keys keep their order too. It is always written
+/// from that tree and never spliced, so without `ordered` or
+/// `preserve_order` its keys come out sorted even where [`set`] would keep
+/// them in place.
+///
+/// ```
+/// use sjson::Document;
+///
+/// let mut doc = Document::parse(r#"{"name":"Tom"}"#).unwrap();
+/// doc.set("age", "37").unwrap();
+/// doc.set_raw("tags", r#"["a","b"]"#).unwrap();
+/// doc.delete("name").unwrap();
+/// assert_eq!(doc.to_string(), r#"{"age":37,"tags":["a","b"]}"#);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Document {
+    value: Parsed,
+}
+
+impl Document {
+    /// Parse a json document
+    pub fn parse(json: &str) -> Result<Document, SjsonError> {
+        let value = parse_document::<Parsed>(json.as_bytes(), None)?;
+        Ok(Document { value })
+    }
+
+    /// Set a json value for the specified path, see [`set`]
+    pub fn set(&mut self, path: &str, value: &str) -> Result<(), SjsonError> {
+        if path.is_empty() {
+            return Err(SjsonError::EmptyPath);
+        }
+        set_value_at(&mut self.value, path, parse_value(value, None)?, None)?;
+        Ok(())
+    }
+
+    /// Set a raw json value for the specified path, see [`set_raw`]
+    pub fn set_raw(&mut self, path: &str, value: &str) -> Result<(), SjsonError> {
+        let json_value = serde_json::from_str::<Parsed>(value)?;
+        set_value_at(&mut self.value, path, json_value, None)?;
+        Ok(())
+    }
+
+    /// Delete a value for the specified path, see [`delete`]
+    pub fn delete(&mut self, path: &str) -> Result<(), SjsonError> {
+        if path.is_empty() {
+            return Err(SjsonError::EmptyPath);
+        }
+        delete_value_at(&mut self.value, path, None)?;
+        Ok(())
+    }
+
+    /// Get a copy of the value for the specified path, see [`get`]
+    pub fn get(&self, path: &str) -> Option<JsonValue> {
+        get_value_at(&self.value, path, DEFAULT_SEPARATOR).cloned().map(Tree::into_json)
+    }
+
+    /// Serialize the document into a json string
+    pub fn into_string(self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&serde_json::to_string(&self.value).map_err(|_| fmt::Error)?)
+    }
+}
+
+/// Editor makes edits like [`set_options`], [`set_raw_options`] and
+/// [`delete_options`], but writes each result into a buffer it keeps, so a
+/// loop of edits reuses one allocation instead of building a new string
+/// every time. A result is borrowed from the editor until the next edit, so
+/// it can't be the json given to that edit; chain edits with [`Document`].
+///
+/// ```
+/// use sjson::Editor;
+///
+/// let mut editor = Editor::new();
+/// for i in 0..3 {
+///     let result = editor.set(r#"{"n":0}"#, "n", &i.to_string(), None).unwrap();
+///     assert_eq!(result, format!(r#"{{"n":{}}}"#, i));
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Editor {
+    buf: String,
+}
+
+impl Editor {
+    /// Create an editor with an empty buffer
+    pub fn new() -> Editor {
+        Editor::default()
+    }
+
+    /// Create an editor whose buffer holds results of `capacity` bytes
+    /// without growing
+    pub fn with_capacity(capacity: usize) -> Editor {
+        Editor { buf: String::with_capacity(capacity) }
+    }
+
+    /// Set a json value for the specified path, see [`set_options`]
+    pub fn set(&mut self, json: &str, path: &str, value: &str, opts: Option<&Options>) -> Result<&str, SjsonError> {
+        set_into(&mut self.buf, json, path, value, opts)?;
+        Ok(&self.buf)
+    }
+
+    /// Set a raw json value for the specified path, see [`set_raw_options`]
+    pub fn set_raw(&mut self, json: &str, path: &str, value: &str, opts: Option<&Options>) -> Result<&str, SjsonError> {
+        set_raw_into(&mut self.buf, json, path, value, opts)?;
+        Ok(&self.buf)
+    }
+
+    /// Delete a value for the specified path, see [`delete_options`]
+    pub fn delete(&mut self, json: &str, path: &str, opts: Option<&Options>) -> Result<&str, SjsonError> {
+        delete_into(&mut self.buf, json, path, opts)?;
+        Ok(&self.buf)
+    }
+
+    /// Take the buffer, which holds the result of the last edit when it
+    /// succeeded
+    pub fn into_string(self) -> String {
+        self.buf
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    #[cfg(not(feature = "lenient"))]
+    fn test_set_simple() {
+        let json = r#"{"name":"Tom","age":37}"#;
+        let result = set(json, "name", "Jerry").unwrap();
+        assert_eq!(result, r#"{"name":"Jerry","age":37}"#);
+    }
+
+    #[cfg(not(feature = "lenient"))]
+    #[test]
+    fn test_default_set_keeps_key_order() {
+        let json = r#"{"z":1,"name":"Tom","a":{"y":true,"b":[3,"x",null]}, "tail" : 0}"#;
+        let set = |path: &str, value: &str| set(json, path, value).unwrap();
+        assert_eq!(set("name", "Jerry"), r#"{"z":1,"name":"Jerry","a":{"y":true,"b":[3,"x",null]}, "tail" : 0}"#);
+        assert_eq!(set("a.y", "false"), r#"{"z":1,"name":"Tom","a":{"y":false,"b":[3,"x",null]}, "tail" : 0}"#);
+        assert_eq!(set("a.b.2", "1"), r#"{"z":1,"name":"Tom","a":{"y":true,"b":[3,"x",1]}, "tail" : 0}"#);
+        assert_eq!(set("a.b.-1", "w"), r#"{"z":1,"name":"Tom","a":{"y":true,"b":[3,"x","w"]}, "tail" : 0}"#);
+        assert_eq!(set_raw(json, "tail", r#""end""#).unwrap(), r#"{"z":1,"name":"Tom","a":{"y":true,"b":[3,"x",null]}, "tail" : "end"}"#);
+        assert_eq!(set_bytes(json.as_bytes(), "z", "2").unwrap(), set("z", "2").into_bytes());
+
+        // Every entry point that takes a single path splices the same way
+        assert_eq!(set_path(json, &Path::parse("a.b.-1").unwrap(), "w", None).unwrap(), set("a.b.-1", "w"));
+        assert_eq!(set_segments(json, &["a", "y"], "false", None).unwrap(), set("a.y", "false"));
+        assert_eq!(set_with_old(json, "name", "Jerry", None).unwrap().0, set("name", "Jerry"));
+        assert_eq!(set_located(json, "tail", "1", None).unwrap().0, set("tail", "1"));
+        // A key the byte scan can't read as written goes to the parser
+        let dotted = r#"{"b":1,"a.b":0}"#;
+        assert_eq!(
+            set_segments(dotted, &["a.b"], "1", None).unwrap(),
+            if cfg!(any(feature = "preserve_order", feature = "ordered")) { r#"{"b":1,"a.b":1}"# } else { r#"{"a.b":1,"b":1}"# }
+        );
+
+        // The value is written like the parser path writes it
+        #[cfg(not(feature = "arbitrary_precision"))]
+        assert_eq!(set("z", "1.50"), r#"{"z":1.5,"name":"Tom","a":{"y":true,"b":[3,"x",null]}, "tail" : 0}"#);
+        assert_eq!(set_raw(r#"{"b":1,"a":0}"#, "b", r#""\u0041""#).unwrap(), r#"{"b":"A","a":0}"#);
+
+        // Anything else is left to the parser
+        #[cfg(not(any(feature = "preserve_order", feature = "ordered")))]
+        {
+            assert_eq!(set_raw(r#"{"b":1,"a":0}"#, "b", "[1]").unwrap(), r#"{"a":0,"b":[1]}"#);
+            assert_eq!(super::set(r#"{"b":1,"a":0}"#, "c", "2").unwrap(), r#"{"a":0,"b":1,"c":2}"#);
+            assert_eq!(super::set(r#"{"b":{"c":1},"a":0}"#, "b", "2").unwrap(), r#"{"a":0,"b":2}"#);
+            assert_eq!(super::set(r#"{"b":1,"a":0,"a":2}"#, "b", "3").unwrap(), r#"{"a":2,"b":3}"#);
+        }
+        assert!(matches!(super::set(r#"{"b":1,"a":}"#, "b", "3"), Err(SjsonError::InvalidJson(_))));
+        assert!(matches!(set_raw(r#"{"b":1}"#, "b", r#"1,"c":2"#), Err(SjsonError::InvalidJson(_))));
+        let opts = Options { error_on_nochange: true, ..Default::default() };
+        assert_eq!(set_options(json, "z", "1", Some(&opts)), Err(SjsonError::NoChange));
+    }
+
+    #[test]
+    fn test_set_nested() {
+        let json = r#"{"name":{"first":"Tom","last":"Anderson"}}"#;
+        let result = set(json, "name.first", "Jerry").unwrap();
+        assert_eq!(result, r#"{"name":{"first":"Jerry","last":"Anderson"}}"#);
+    }
+
+    #[test]
+    fn test_set_array() {
+        let json = r#"{"children":["Sara","Alex","Jack"]}"#;
+        let result = set(json, "children.1", "Jerry").unwrap();
+        assert_eq!(result, r#"{"children":["Sara","Jerry","Jack"]}"#);
+        let result = set(json, "children", "[]").unwrap();
+        assert_eq!(result, r#"{"children":[]}"#);
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "preserve_order", feature = "ordered")))]
+    fn test_set_new_field() {
+        let json = r#"{"name":"Tom"}"#;
+        let result = set(json, "age", "37").unwrap();
+        assert_eq!(result, r#"{"age":37,"name":"Tom"}"#);
+    }
+
+    #[test]
+    fn test_array_index_operation() {
+        let json = r#"{"children":["Sara","Alex","Jack"]}"#;
+        let result = set(json, "children.-1", "Jerry").unwrap();
+        assert_eq!(result, r#"{"children":["Sara","Alex","Jerry"]}"#);
+        let result = delete(json, "children.-1").unwrap();
+        assert_eq!(result, r#"{"children":["Sara","Alex"]}"#);
+    }
+
+    #[test]
+    fn test_negative_array_indices() {
+        let json = r#"{"items":["a","b","c","d","e"]}"#;
+        
+        // Test -1 (last element)
+        let result = set(json, "items.-1", "z").unwrap();
+        assert_eq!(result, r#"{"items":["a","b","c","d","z"]}"#);
+        
+        // Test -2 (second to last)
+        let result = set(json, "items.-2", "y").unwrap();
+        assert_eq!(result, r#"{"items":["a","b","c","y","e"]}"#);
+        
+        // Test -3 (third to last)
+        let result = set(json, "items.-3", "x").unwrap();
+        assert_eq!(result, r#"{"items":["a","b","x","d","e"]}"#);
+    }
+
+    #[test]
+    fn test_negative_array_indices_delete() {
+        let json = r#"{"items":["a","b","c","d","e"]}"#;
+        
+        // Test deleting -1 (last element)
+        let result = delete(json, "items.-1").unwrap();
+        assert_eq!(result, r#"{"items":["a","b","c","d"]}"#);
+        
+        // Test deleting -2 (second to last)
+        let result = delete(json, "items.-2").unwrap();
+        assert_eq!(result, r#"{"items":["a","b","c","e"]}"#);
+        
+        // Test deleting -3 (third to last)
+        let result = delete(json, "items.-3").unwrap();
+        assert_eq!(result, r#"{"items":["a","b","d","e"]}"#);
+    }
+
+    #[test]
+    fn test_negative_array_indices_nested() {
+        let json = r#"{"data":{"items":[{"name":"item1"},{"name":"item2"},{"name":"item3"}]}}"#;
+        
+        // Test setting in nested array with negative index
+        let result = set(json, "data.items.-1.name", "updated").unwrap();
+        assert!(result.contains("\"name\":\"updated\""));
+        
+        // Test deleting in nested array with negative index
+        let result = delete(json, "data.items.-1.name").unwrap();
+        assert!(!result.contains("\"name\":\"item3\""));
+    }
+
+    #[test]
+    fn test_negative_array_indices_invalid() {
+        let json = r#"{"items":["a","b"]}"#;
+        
+        // Test invalid negative index (beyond array bounds)
+        let result = set(json, "items.-3", "x");
+        assert!(result.is_err());
+        
+        let result = delete(json, "items.-3");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_negative_array_indices_optimistic() {
+        let json = r#"{"items":["a","b","c","d"]}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        
+        // Test optimistic mode with negative indices
+        let result = set_options(json, "items.-1", "z", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"items":["a","b","c","z"]}"#);
+        
+        let result = delete_options(json, "items.-2", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"items":["a","b","d"]}"#);
+    }
+
+    #[test]
+    fn test_negative_array_indices_with_large_array() {
+        let json = r#"{"items":["a","b","c","d","e","f","g","h","i","j"]}"#;
+        
+        // Test various negative indices
+        let result = set(json, "items.-1", "last").unwrap();
+        assert_eq!(result, r#"{"items":["a","b","c","d","e","f","g","h","i","last"]}"#);
+        
+        let result = set(json, "items.-5", "middle").unwrap();
+        assert_eq!(result, r#"{"items":["a","b","c","d","e","middle","g","h","i","j"]}"#);
+        
+        let result = delete(json, "items.-1").unwrap();
+        assert_eq!(result, r#"{"items":["a","b","c","d","e","f","g","h","i"]}"#);
+    }
+
+    #[test]
+    fn test_split_path_escapes() {
+        assert_eq!(split_path("a.b", '.').unwrap(), vec!["a", "b"]);
+        assert_eq!(split_path(r"user\.name", '.').unwrap(), vec!["user.name"]);
+        assert_eq!(split_path(r"a\\.b", '.').unwrap(), vec![r"a\", "b"]);
+        assert_eq!(split_path(r"a\\\.b.c", '.').unwrap(), vec![r"a\.b", "c"]);
+    }
+
+    #[test]
+    fn test_escaped_dot_key() {
+        let json = r#"{"user.name":"Tom"}"#;
+        let result = set(json, r"user\.name", "Jerry").unwrap();
+        assert_eq!(result, r#"{"user.name":"Jerry"}"#);
+
+        let result = delete(&result, r"user\.name").unwrap();
+        assert_eq!(result, r#"{}"#);
+    }
+
+    #[test]
+    fn test_escaped_dot_key_optimistic() {
+        let json = r#"{"user":{"name":"Tom"},"user.name":"Tom"}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = set_options(json, r"user\.name", "Jerry", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"user":{"name":"Tom"},"user.name":"Jerry"}"#);
+
+        let result = set_raw_options(&result, r"user\.name", "1", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"user":{"name":"Tom"},"user.name":1}"#);
+
+        let result = delete_options(&result, r"user\.name", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"user":{"name":"Tom"}}"#);
+    }
+
+    #[test]
+    fn test_delete() {
+        let json = r#"{"name":"Tom","age":37}"#;
+        let result = delete(json, "age").unwrap();
+        assert_eq!(result, r#"{"name":"Tom"}"#);
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "preserve_order", feature = "ordered")))]
+    fn test_set_bool() {
+        let json = r#"{"name":"Tom"}"#;
+        let result = set_bool(json, "active", true, None).unwrap();
+        assert_eq!(result, r#"{"active":true,"name":"Tom"}"#);
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "preserve_order", feature = "ordered")))]
+    fn test_set_int() {
+        let json = r#"{"name":"Tom"}"#;
+        let result = set_int(json, "age", 37, None).unwrap();
+        assert_eq!(result, r#"{"age":37,"name":"Tom"}"#);
+    }
+
+    #[test]
+    fn test_empty_path() {
+        let json = r#"{"name":"Tom"}"#;
+        let result = set(json, "", "value");
+        assert!(result.is_err());
+    }
+
+    #[cfg(not(feature = "lenient"))]
+    #[test]
+    fn test_optimistic_set() {
+        let json = r#"{"name":"Tom","age":37}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = set_options(json, "name", "Jerry", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"name":"Jerry","age":37}"#);
+    }
+
+    #[test]
+    fn test_optimistic_delete() {
+        let json = r#"{"name":"Tom","age":37}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = delete_options(json, "age", Some(&opts)).unwrap();
+        // For now, just check that it doesn't panic and produces valid JSON
+        assert!(result.contains("\"name\":\"Tom\""));
+        assert!(!result.contains("\"age\":37"));
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "preserve_order", feature = "ordered")))]
+    fn test_options_without_optimistic() {
+        let json = r#"{"name":"Tom","age":37}"#;
+        let opts = Options::default(); // optimistic = false
+        // An existing scalar is spliced in place, except by `lenient`, which
+        // always reserializes
+        let result = set_options(json, "name", "Jerry", Some(&opts)).unwrap();
+        if cfg!(feature = "lenient") {
+            assert_eq!(result, r#"{"age":37,"name":"Jerry"}"#);
+        } else {
+            assert_eq!(result, r#"{"name":"Jerry","age":37}"#);
+        }
+        // Anything but replacing an existing scalar reserializes
+        let result = set_options(json, "name", "[1]", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"age":37,"name":[1]}"#);
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "preserve_order", feature = "ordered")))]
+    fn test_options_none() {
+        let json = r#"{"name":"Tom","age":37}"#;
+        let result = set_options(json, "name", "Jerry", None).unwrap();
+        if cfg!(feature = "lenient") {
+            assert_eq!(result, r#"{"age":37,"name":"Jerry"}"#);
+        } else {
+            assert_eq!(result, r#"{"name":"Jerry","age":37}"#);
+        }
+        let result = set_options(json, "city", "Paris", None).unwrap();
+        assert_eq!(result, r#"{"age":37,"city":"Paris","name":"Tom"}"#);
+    }
+
+    #[test]
+    fn test_optimistic_nested_set() {
+        let json = r#"{"user":{"name":"Tom","age":37}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = set_options(json, "user.name", "Jerry", Some(&opts)).unwrap();
+        // Check that the result contains the expected values, regardless of field order
+        assert!(result.contains("\"user\""));
+        assert!(result.contains("\"name\":\"Jerry\""));
+        assert!(result.contains("\"age\":37"));
+    }
+
+    #[test]
+    fn test_optimistic_array_set() {
+        let json = r#"{"items":["a","b","c"]}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = set_options(json, "items.1", "x", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"items":["a","x","c"]}"#);
+    }
+
+    #[cfg(not(feature = "lenient"))]
+    #[test]
+    fn test_optimistic_set_raw() {
+        let json = r#"{"data":{"name":"Tom"}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let complex_value = r#"{"city":"Beijing","country":"China"}"#;
+        let result = set_raw_options(json, "data.address", complex_value, Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"data":{"name":"Tom","address":{"city":"Beijing","country":"China"}}}"#);
+    }
+
+    #[cfg(not(feature = "lenient"))]
+    #[test]
+    fn test_optimistic_nested_key_shadowing() {
+        // An earlier nested member with the same name never matches the
+        // member of the object the path is in
+        let json = r#"{"meta":{"age":1},"list":[{"age":3}],"age":2}"#;
+        let opts = Options { preserve_untouched: true, ..Default::default() };
+
+        let result = set_options(json, "age", "9", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"meta":{"age":1},"list":[{"age":3}],"age":9}"#);
+        let result = delete_options(json, "age", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"meta":{"age":1},"list":[{"age":3}]}"#);
+        let result = set_options(json, "meta.age", "9", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"meta":{"age":9},"list":[{"age":3}],"age":2}"#);
+        // Nor does a key written inside a string value
+        let json = r#"{"note":"\"age\":0","meta":{"x":{"age":1}},"age":2}"#;
+        let result = set_options(json, "age", "9", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"note":"\"age\":0","meta":{"x":{"age":1}},"age":9}"#);
+
+        // A key that is only nested is missing at the top and gets added
+        let json = r#"{"meta":{"age":1}}"#;
+        let result = set_options(json, "age", "2", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"meta":{"age":1},"age":2}"#);
+        assert_eq!(delete_options(json, "age", Some(&opts)), Err(SjsonError::SpliceUnavailable));
+
+        // Replacing a scalar without optimistic takes the same scan
+        let json = r#"{"meta":{"age":1},"age":2}"#;
+        assert_eq!(set(json, "age", "9").unwrap(), r#"{"meta":{"age":1},"age":9}"#);
+    }
+
+    #[cfg(not(feature = "lenient"))]
+    #[test]
+    fn test_optimistic_replace_structured_value() {
+        let opts = Options { optimistic: true, ..Default::default() };
+        let set = |json: &str, path: &str, value: &str| set_options(json, path, value, Some(&opts)).unwrap();
+
+        // The whole old object or array goes, nested or not, first or last
+        assert_eq!(set(r#"{"a":{"b":{"c":[1,2]}},"z":1}"#, "a", "x"), r#"{"a":"x","z":1}"#);
+        assert_eq!(set(r#"{"z":1,"a":[[],{}]}"#, "a", "null"), r#"{"z":1,"a":null}"#);
+        assert_eq!(set(r#"{"o":{"a":[{"b":1}],"z":2}}"#, "o.a", "0"), r#"{"o":{"a":0,"z":2}}"#);
+        assert_eq!(set(r#"[{"a":1},[2,[3]],4]"#, "1", "true"), r#"[{"a":1},true,4]"#);
+
+        // Brackets, quotes and multibyte characters inside strings don't count
+        assert_eq!(set(r#"{"a":["]}\"",{"k":"{["}],"z":1}"#, "a", "5"), r#"{"a":5,"z":1}"#);
+        assert_eq!(set(r#"{"a":{"名":"é}"},"z":"ü"}"#, "a", "true"), r#"{"a":true,"z":"ü"}"#);
+
+        // Only the value is replaced, the whitespace around it stays
+        assert_eq!(set(r#"{"a": { "b": 1 } , "z": 1}"#, "a", "1"), r#"{"a": 1 , "z": 1}"#);
+        assert_eq!(set("{\"z\": 1, \"a\": [\n  1\n]\n}", "a", "2"), "{\"z\": 1, \"a\": 2\n}");
+        let json = r#"{"a": [1, 2] }"#;
+        let (result, range) = set_located(json, "a", "3", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"a": 3 }"#);
+        assert_eq!(&result[range], "3");
+
+        // A malformed value is left to the parser instead of being spliced
+        let invalid = |json: &str| matches!(set_options(json, "a", "1", Some(&opts)), Err(SjsonError::InvalidJson(_)));
+        assert!(invalid(r#"{"a":{"b":1}"z":2}"#));
+        assert!(invalid(r#"{"a":[1}],"z":2}"#));
+        assert!(invalid(r#"{"a":{"b":1]},"z":2}"#));
+        assert!(invalid(r#"{"a":1 2,"z":2}"#));
+        assert!(invalid(r#"{"a":"x"[1],"z":2}"#));
+    }
+
+    #[cfg(not(feature = "lenient"))]
+    #[test]
+    fn test_optimistic_set_raw_keeps_token() {
+        let opts = Options { optimistic: true, ..Default::default() };
+        let set_raw = |json: &str, path: &str, value: &str| set_raw_options(json, path, value, Some(&opts)).unwrap();
+
+        assert_eq!(set_raw(r#"{"x":1,"y":2}"#, "x", "1e3"), r#"{"x":1e3,"y":2}"#);
+        assert_eq!(set_raw(r#"{"y":2}"#, "x", "1e3"), r#"{"y":2,"x":1e3}"#);
+        assert_eq!(set_raw(r#"{"a":[1,2]}"#, "a.0", "1e3"), r#"{"a":[1e3,2]}"#);
+        assert_eq!(set_raw(r#"{"a":[1,2]}"#, "a.-1", "1E+2"), r#"{"a":[1,1E+2]}"#);
+        assert_eq!(set_raw(r#"{"x":1}"#, "x", "1.50"), r#"{"x":1.50}"#);
+        assert_eq!(set_raw(r#"{"x":1}"#, "x", "-0.0"), r#"{"x":-0.0}"#);
+        assert_eq!(set_raw(r#"{"x":1}"#, "x", r#"{"b": 1.0}"#), r#"{"x":{"b": 1.0}}"#);
+        assert_eq!(set_raw(r#"{"x":1}"#, "x", "true"), r#"{"x":true}"#);
+
+        // A new element or parent needs the parser, which normalizes the
+        // number unless `arbitrary_precision` keeps its text
+        let result = set_raw(r#"{"a":[1]}"#, "a.1", "1e3");
+        assert_eq!(get(&result, "a.1").unwrap().as_f64(), Some(1000.0));
+        assert_eq!(result.contains("1e3"), cfg!(feature = "arbitrary_precision"));
+    }
+
+    #[cfg(not(feature = "lenient"))]
+    #[test]
+    fn test_optimistic_through_array_elements() {
+        let json = r#"{"data":{"items":[{"z":1,"profile":null}, {"z":2,"profile":{}} ],"b":1.10}}"#;
+        let raw = r#"{"name":"Tom","age":37}"#;
+        // preserve_untouched fails instead of falling back to the parser
+        let opts = Options { preserve_untouched: true, ..Default::default() };
+
+        let result = set_raw_options(json, "data.items.0.profile", raw, Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"data":{"items":[{"z":1,"profile":{"name":"Tom","age":37}}, {"z":2,"profile":{}} ],"b":1.10}}"#);
+        let result = set_raw_options(json, "data.items.-1.profile", raw, Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"data":{"items":[{"z":1,"profile":null}, {"z":2,"profile":{"name":"Tom","age":37}} ],"b":1.10}}"#);
+        let result = set_options(json, "data.items.1.y", "x", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"data":{"items":[{"z":1,"profile":null}, {"z":2,"profile":{},"y":"x"} ],"b":1.10}}"#);
+        let result = delete_options(json, "data.items.1.z", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"data":{"items":[{"z":1,"profile":null}, {"profile":{}} ],"b":1.10}}"#);
+
+        // Nested arrays and elements that aren't there yet
+        let json = r#"{"m":[[0,{"a":1}],[]]}"#;
+        assert_eq!(set_options(json, "m.0.1.a", "2", Some(&opts)).unwrap(), r#"{"m":[[0,{"a":2}],[]]}"#);
+        assert_eq!(set_options(json, "m.2.a", "2", Some(&opts)), Err(SjsonError::SpliceUnavailable));
+        assert_eq!(set_options(json, "m.1.0.a", "2", Some(&opts)), Err(SjsonError::SpliceUnavailable));
+        assert_eq!(set_options(json, "m.0.0.a", "2", Some(&opts)), Err(SjsonError::SpliceUnavailable));
+        let opts = Options { optimistic: true, ..Default::default() };
+        assert_eq!(set_options(json, "m.2.a", "2", Some(&opts)).unwrap(), set(json, "m.2.a", "2").unwrap());
+    }
+
+    #[cfg(not(feature = "lenient"))]
+    #[test]
+    fn test_optimistic_set_bool() {
+        let json = r#"{"user":{"name":"Tom"}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = set_bool(json, "user.active", true, Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"user":{"name":"Tom","active":true}}"#);
+    }
+
+    #[cfg(not(feature = "lenient"))]
+    #[test]
+    fn test_optimistic_set_int() {
+        let json = r#"{"user":{"name":"Tom"}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = set_int(json, "user.age", 25, Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"user":{"name":"Tom","age":25}}"#);
+    }
+
+    #[test]
+    fn test_optimistic_set_float() {
+        let json = r#"{"user":{"name":"Tom"}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = set_float(json, "user.score", 95.5, Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"user":{"name":"Tom","score":95.5}}"#);
+    }
+
+    #[cfg(not(feature = "lenient"))]
+    #[test]
+    fn test_optimistic_set_value() {
+        let json = r#"{"user":{"name":"Tom"}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        
+        #[derive(serde::Serialize)]
+        struct Address {
+            city: String,
+            country: String,
+        }
+        
+        let address = Address {
+            city: "Beijing".to_string(),
+            country: "China".to_string(),
+        };
+        
+        let result = set_value(json, "user.address", &address, Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"user":{"name":"Tom","address":{"city":"Beijing","country":"China"}}}"#);
+    }
+
+    #[test]
+    fn test_options_clone() {
+        let mut opts1 = Options::default();
+        opts1.optimistic = true;
+        let opts2 = opts1.clone();
+        assert_eq!(opts1.optimistic, opts2.optimistic);
+    }
+
+    #[test]
+    fn test_options_default() {
+        let opts = Options::default();
+        assert!(!opts.optimistic);
+    }
+
+    #[test]
+    fn test_optimistic_delete_nested() {
+        let json = r#"{"user":{"name":"Tom","age":37,"city":"Beijing"}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = delete_options(json, "user.age", Some(&opts)).unwrap();
+        assert!(result.contains("\"user\""));
+        assert!(result.contains("\"name\":\"Tom\""));
+        assert!(result.contains("\"city\":\"Beijing\""));
+        assert!(!result.contains("\"age\":37"));
+    }
+
+    #[test]
+    fn test_optimistic_delete_array_element() {
+        let json = r#"{"items":["a","b","c","d"]}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = delete_options(json, "items.1", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"items":["a","c","d"]}"#);
+    }
+
+    #[test]
+    fn test_optimistic_set_with_special_characters() {
+        let json = r#"{"user":{"name":"Tom"}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        // Test with value containing special characters
+        let result = set_options(json, "user.description", "Hello, \"World\"!", Some(&opts)).unwrap();
+        assert!(result.contains("\"description\":\"Hello, \\\"World\\\"!\""));
+    }
+
+    #[test]
+    fn test_optimistic_set_null_value() {
+        let json = r#"{"user":{"name":"Tom","age":37}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = set_options(json, "user.age", "null", Some(&opts)).unwrap();
+        assert!(result.contains("\"age\":null"));
+    }
+
+    #[test]
+    fn test_optimistic_set_boolean_values() {
+        let json = r#"{"user":{"name":"Tom"}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        
+        // Test true
+        let result = set_options(json, "user.active", "true", Some(&opts)).unwrap();
+        assert!(result.contains("\"active\":true"));
+        
+        // Test false
+        let result = set_options(result.as_str(), "user.verified", "false", Some(&opts)).unwrap();
+        assert!(result.contains("\"verified\":false"));
+    }
+
+    #[test]
+    fn test_optimistic_set_numeric_values() {
+        let json = r#"{"user":{"name":"Tom"}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        
+        // Test integer
+        let result = set_options(json, "user.age", "25", Some(&opts)).unwrap();
+        assert!(result.contains("\"age\":25"));
+        
+        // Test float
+        let result = set_options(result.as_str(), "user.score", "95.5", Some(&opts)).unwrap();
+        assert!(result.contains("\"score\":95.5"));
+        
+        // Test negative number
+        let result = set_options(result.as_str(), "user.balance", "-100.50", Some(&opts)).unwrap();
+        assert!(result.contains("\"balance\":-100.5"));
+    }
+
+    #[test]
+    fn test_optimistic_set_array_value() {
+        let json = r#"{"user":{"name":"Tom"}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = set_options(json, "user.hobbies", "[\"reading\",\"swimming\"]", Some(&opts)).unwrap();
+        assert!(result.contains("\"hobbies\":[\"reading\",\"swimming\"]"));
+    }
+
+    #[test]
+    fn test_optimistic_set_object_value() {
+        let json = r#"{"user":{"name":"Tom"}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = set_options(json, "user.address", "{\"city\":\"Beijing\",\"country\":\"China\"}", Some(&opts)).unwrap();
+        assert!(result.contains("\"address\":{\"city\":\"Beijing\",\"country\":\"China\"}"));
+    }
+
+    #[test]
+    fn test_optimistic_set_empty_string() {
+        let json = r#"{"user":{"name":"Tom"}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = set_options(json, "user.description", "", Some(&opts)).unwrap();
+        assert!(result.contains("\"description\":\"\""));
+    }
+
+    #[test]
+    fn test_optimistic_set_with_unicode() {
+        let json = r#"{"user":{"name":"Tom"}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = set_options(json, "user.name", "张三", Some(&opts)).unwrap();
+        assert!(result.contains("\"name\":\"张三\""));
+    }
+
+    #[test]
+    fn test_optimistic_set_deep_nested() {
+        let json = r#"{"level1":{"level2":{"level3":{"name":"Tom"}}}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = set_options(json, "level1.level2.level3.age", "25", Some(&opts)).unwrap();
+        assert!(result.contains("\"age\":25"));
+        assert!(result.contains("\"name\":\"Tom\""));
+    }
+
+    #[test]
+    fn test_optimistic_set_array_deep_nested() {
+        let json = r#"{"data":{"items":[{"name":"item1"},{"name":"item2"}]}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = set_options(json, "data.items.0.price", "100", Some(&opts)).unwrap();
+        assert!(result.contains("\"price\":100"));
+    }
+
+    #[test]
+    fn test_optimistic_delete_array_deep_nested() {
+        let json = r#"{"data":{"items":[{"name":"item1","price":100},{"name":"item2","price":200}]}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = delete_options(json, "data.items.0.price", Some(&opts)).unwrap();
+        assert!(result.contains("\"name\":\"item1\""));
+        assert!(!result.contains("\"price\":100"));
+    }
+
+    #[test]
+    fn test_optimistic_set_with_existing_array() {
+        let json = r#"{"items":["a","b","c"]}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = set_options(json, "items.3", "d", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"items":["a","b","c","d"]}"#);
+    }
+
+    #[test]
+    fn test_optimistic_set_with_large_array_index() {
+        let json = r#"{"items":["a","b"]}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = set_options(json, "items.5", "f", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"items":["a","b",null,null,null,"f"]}"#);
+    }
+
+    #[test]
+    fn test_optimistic_set_raw_with_complex_json() {
+        let json = r#"{"data":{"user":{"name":"Tom"}}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let complex_value = r#"{"address":{"street":"123 Main St","city":"Beijing","country":"China"},"phone":"+86-123-4567","active":true,"scores":[95,87,92]}"#;
+        let result = set_raw_options(json, "data.user.profile", complex_value, Some(&opts)).unwrap();
+        // Check that all expected fields are present, regardless of order
+        assert!(result.contains("\"profile\""));
+        assert!(result.contains("\"active\":true"));
+        assert!(result.contains("\"phone\":\"+86-123-4567\""));
+        assert!(result.contains("\"scores\":[95,87,92]"));
+        assert!(result.contains("\"street\":\"123 Main St\""));
+        assert!(result.contains("\"city\":\"Beijing\""));
+        assert!(result.contains("\"country\":\"China\""));
+    }
+
+    #[test]
+    fn test_optimistic_fallback_to_parser() {
+        let json = r#"{"user":{"name":"Tom"}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        // Test with a path that contains special characters (should fall back to parser)
+        let result = set_options(json, "user.name", "Jerry", Some(&opts)).unwrap();
+        assert!(result.contains("\"name\":\"Jerry\""));
+    }
+
+    #[test]
+    fn test_optimistic_set_multiple_operations() {
+        let json = r#"{"user":{"name":"Tom"}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        
+        // Multiple set operations
+        let result1 = set_options(json, "user.age", "25", Some(&opts)).unwrap();
+        let result2 = set_options(result1.as_str(), "user.city", "Beijing", Some(&opts)).unwrap();
+        let result3 = set_options(result2.as_str(), "user.active", "true", Some(&opts)).unwrap();
+        
+        assert!(result3.contains("\"age\":25"));
+        assert!(result3.contains("\"city\":\"Beijing\""));
+        assert!(result3.contains("\"active\":true"));
+        assert!(result3.contains("\"name\":\"Tom\""));
+    }
+
+    #[test]
+    fn test_optimistic_delete_multiple_operations() {
+        let json = r#"{"user":{"name":"Tom","age":25,"city":"Beijing","active":true}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        
+        // Multiple delete operations
+        let result1 = delete_options(json, "user.age", Some(&opts)).unwrap();
+        let result2 = delete_options(result1.as_str(), "user.city", Some(&opts)).unwrap();
+        let result3 = delete_options(result2.as_str(), "user.active", Some(&opts)).unwrap();
+        
+        assert!(result3.contains("\"name\":\"Tom\""));
+        assert!(!result3.contains("\"age\":25"));
+        assert!(!result3.contains("\"city\":\"Beijing\""));
+        assert!(!result3.contains("\"active\":true"));
+    }
+
+    #[test]
+    fn test_optimistic_set_with_escaped_quotes() {
+        let json = r#"{"user":{"name":"Tom"}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = set_options(json, "user.quote", "He said \"Hello World\"", Some(&opts)).unwrap();
+        assert!(result.contains("\"quote\":\"He said \\\"Hello World\\\"\""));
+    }
+
+    #[cfg(not(feature = "lenient"))]
+    #[test]
+    fn test_optimistic_escaped_keys() {
+        // preserve_untouched fails instead of falling back to the parser
+        let opts = Options { preserve_untouched: true, ..Default::default() };
+        let json = r#"{"name":"Tom","\u0061ge":37,"ag\"e":1}"#;
+        assert_eq!(set_options(json, "age", "38", Some(&opts)).unwrap(), r#"{"name":"Tom","\u0061ge":38,"ag\"e":1}"#);
+        assert_eq!(delete_options(json, "age", Some(&opts)).unwrap(), r#"{"name":"Tom","ag\"e":1}"#);
+        assert_eq!(set_options(json, "ag", "1", Some(&opts)).unwrap(), r#"{"name":"Tom","\u0061ge":37,"ag\"e":1,"ag":1}"#);
+
+        let json = r#"{"\u0075ser":{"n\u00e4me":"Tom"},"user2":{}}"#;
+        assert_eq!(set_options(json, "user.x", "1", Some(&opts)).unwrap(), r#"{"\u0075ser":{"n\u00e4me":"Tom","x":1},"user2":{}}"#);
+
+        // An escaped spelling of the same key is a repeat
+        let json = r#"{"age":1,"\u0061ge":2}"#;
+        assert_eq!(set_options(json, "age", "3", Some(&opts)), Err(SjsonError::SpliceUnavailable));
+        let opts = Options { optimistic: true, ..Default::default() };
+        assert_eq!(set_options(json, "age", "3", Some(&opts)).unwrap(), r#"{"age":3}"#);
+    }
+
+    #[test]
+    fn test_optimistic_set_with_newlines() {
+        let json = r#"{"user":{"name":"Tom"}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = set_options(json, "user.description", "Line 1\nLine 2", Some(&opts)).unwrap();
+        assert!(result.contains("\"description\":\"Line 1\\nLine 2\""));
+    }
+
+    #[test]
+    fn test_optimistic_set_with_tabs() {
+        let json = r#"{"user":{"name":"Tom"}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = set_options(json, "user.description", "Tab\there", Some(&opts)).unwrap();
+        assert!(result.contains("\"description\":\"Tab\\there\""));
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "preserve_order", feature = "ordered")))]
+    fn test_set_bytes() {
+        let json = br#"{"name":"Tom","age":37}"#;
+        let result = set_bytes(json, "name", "Jerry").unwrap();
+        #[cfg(not(feature = "lenient"))]
+        assert_eq!(result, br#"{"name":"Jerry","age":37}"#);
+        assert_eq!(get(core::str::from_utf8(&result).unwrap(), "name"), Some(JsonValue::from("Jerry")));
+
+        let result = set_raw_bytes(json, "tags", r#"["a","b"]"#).unwrap();
+        assert_eq!(result, br#"{"age":37,"name":"Tom","tags":["a","b"]}"#);
+
+        let result = delete_bytes(json, "age").unwrap();
+        assert_eq!(result, br#"{"name":"Tom"}"#);
+    }
+
+    #[test]
+    fn test_bytes_match_str() {
+        let json = r#"{"user":{"name":"张三","age":37},"items":["a","b"]}"#;
+        let mut opts = Options::default();
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            assert_eq!(
+                set_bytes_options(json.as_bytes(), "user.name", "Jerry", Some(&opts)).unwrap(),
+                set_options(json, "user.name", "Jerry", Some(&opts)).unwrap().into_bytes()
+            );
+            assert_eq!(
+                set_raw_bytes_options(json.as_bytes(), "user.age", "40", Some(&opts)).unwrap(),
+                set_raw_options(json, "user.age", "40", Some(&opts)).unwrap().into_bytes()
+            );
+            assert_eq!(
+                delete_bytes_options(json.as_bytes(), "items.0", Some(&opts)).unwrap(),
+                delete_options(json, "items.0", Some(&opts)).unwrap().into_bytes()
+            );
+        }
+    }
+
+    #[test]
+    fn test_bytes_invalid_json() {
+        assert!(set_bytes(b"{\"name\":", "name", "Jerry").is_err());
+        assert!(delete_bytes(b"not json", "name").is_err());
+    }
+
+    #[cfg(not(feature = "lenient"))]
+    #[test]
+    fn test_optimistic_key_inside_string_value() {
+        let json = r#"{"note":"the age: is here","age":37}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = set_options(json, "age", "40", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"note":"the age: is here","age":40}"#);
+
+        let json = r#"{"note":"\"age\":1,","age":37}"#;
+        let result = set_options(json, "age", "40", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"note":"\"age\":1,","age":40}"#);
+        let result = delete_options(json, "age", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"note":"\"age\":1,"}"#);
+    }
+
+    #[cfg(not(feature = "lenient"))]
+    #[test]
+    fn test_optimistic_key_inside_nested_string_value() {
+        let json = r#"{"user":{"bio":"{\"age\":1,","age":37}}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = set_options(json, "user.age", "40", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"user":{"bio":"{\"age\":1,","age":40}}"#);
+    }
+
+    #[test]
+    fn test_optimistic_unterminated_string_falls_back() {
+        let json = r#"{"note":"unterminated"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        assert!(set_options(json, "note", "x", Some(&opts)).is_err());
+    }
+
+    #[cfg(not(feature = "lenient"))]
+    #[test]
+    fn test_optimistic_multibyte_values() {
+        let json = r#"{"name":"张三","age":37}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = set_options(json, "name", "Jerry", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"name":"Jerry","age":37}"#);
+        let result = set_raw_options(json, "name", r#""李四""#, Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"name":"李四","age":37}"#);
+        let result = delete_options(json, "age", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"name":"张三"}"#);
+
+        let json = r#"{"mood":"😀🎉","tags":["🚀","✨"],"age":37}"#;
+        let result = set_options(json, "mood", "calm", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"mood":"calm","tags":["🚀","✨"],"age":37}"#);
+        let result = set_options(json, "age", "38", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"mood":"😀🎉","tags":["🚀","✨"],"age":38}"#);
+        let result = set_raw_options(json, "tags", "[]", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"mood":"😀🎉","tags":[],"age":37}"#);
+    }
+
+    #[test]
+    #[cfg(feature = "preserve_order")]
+    fn test_preserve_order() {
+        let json = r#"{"name":"Tom","age":37}"#;
+        let result = set(json, "name", "Jerry").unwrap();
+        assert_eq!(result, r#"{"name":"Jerry","age":37}"#);
+
+        let result = set(json, "city", "Beijing").unwrap();
+        assert_eq!(result, r#"{"name":"Tom","age":37,"city":"Beijing"}"#);
+
+        let result = set_raw(json, "address.zip", "100000").unwrap();
+        assert_eq!(result, r#"{"name":"Tom","age":37,"address":{"zip":100000}}"#);
+    }
+
+    #[test]
+    #[cfg(feature = "preserve_order")]
+    fn test_preserve_order_delete() {
+        let json = r#"{"name":"Tom","age":37,"city":"Beijing"}"#;
+        let result = delete(json, "name").unwrap();
+        assert_eq!(result, r#"{"age":37,"city":"Beijing"}"#);
+
+        let result = delete_bytes(json.as_bytes(), "age").unwrap();
+        assert_eq!(result, br#"{"name":"Tom","city":"Beijing"}"#);
+    }
+
+    #[test]
+    fn test_go_compatible_append() {
+        let json = r#"{"children":["Sara","Alex"]}"#;
+        let mut opts = Options::default();
+        opts.go_compatible_append = true;
+
+        let result = set_options(json, "children.-1", "Jack", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"children":["Sara","Alex","Jack"]}"#);
+
+        let result = set_raw_options(json, "children.-1", r#"{"name":"Jack"}"#, Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"children":["Sara","Alex",{"name":"Jack"}]}"#);
+
+        let result = set_options(r#"{"children":[]}"#, "children.-1", "Jack", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"children":["Jack"]}"#);
+
+        // Without the flag -1 still overwrites the last element
+        let result = set_options(json, "children.-1", "Jack", None).unwrap();
+        assert_eq!(result, r#"{"children":["Sara","Jack"]}"#);
+    }
+
+    #[cfg(not(feature = "lenient"))]
+    #[test]
+    fn test_go_compatible_append_optimistic() {
+        let json = r#"{"children":["Sara","Alex"],"age":37}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        opts.go_compatible_append = true;
+
+        let result = set_options(json, "children.-1", "Jack", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"children":["Sara","Alex","Jack"],"age":37}"#);
+
+        let result = set_raw_options(json, "children.-1", "[1]", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"children":["Sara","Alex",[1]],"age":37}"#);
+
+        let json = r#"{"children": [ ],"age":37}"#;
+        let result = set_options(json, "children.-1", "Jack", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"children": [ "Jack"],"age":37}"#);
+
+        let result = set_bytes_options(json.as_bytes(), "children.-1", "Jack", Some(&opts)).unwrap();
+        assert_eq!(result, br#"{"children": [ "Jack"],"age":37}"#);
+    }
+
+    #[test]
+    fn test_bom_and_crlf() {
+        let json = "\u{feff}{\"a\":1,\"b\":[1,2]}";
+        let mut opts = Options::default();
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            assert_eq!(set_options(json, "a", "2", Some(&opts)).unwrap(), r#"{"a":2,"b":[1,2]}"#);
+            assert_eq!(set_raw_options(json, "b.-1", "3", Some(&opts)).unwrap(), r#"{"a":1,"b":[1,3]}"#);
+            assert_eq!(delete_options(json, "a", Some(&opts)).unwrap(), r#"{"b":[1,2]}"#);
+            assert_eq!(set_bytes_options(json.as_bytes(), "c", "x", Some(&opts)).unwrap(), br#"{"a":1,"b":[1,2],"c":"x"}"#);
+        }
+        assert_eq!(get(json, "b.1"), Some(serde_json::json!(2)));
+        // Only a leading mark is skipped
+        assert!(matches!(set("{\"a\":1}\u{feff}", "a", "2"), Err(SjsonError::InvalidJson(_))));
+        #[cfg(not(feature = "lenient"))]
+        {
+            let opts = Options { preserve_untouched: true, ..Default::default() };
+            assert_eq!(set_options(json, "a", "2", Some(&opts)), Err(SjsonError::SpliceUnavailable));
+        }
+
+        let json = "{\r\n  \"a\": 1,\r\n  \"b\": [\r\n    1\r\n  ]\r\n}\r\n";
+        // Splicing keeps the line endings, the lenient feature always goes
+        // through the parser
+        let expected = if cfg!(feature = "lenient") {
+            r#"{"a":2,"b":[1]}"#
+        } else {
+            "{\r\n  \"a\": 2,\r\n  \"b\": [\r\n    1\r\n  ]\r\n}\r\n"
+        };
+        assert_eq!(set(json, "a", "2").unwrap(), expected);
+        assert_eq!(set(json, "c", "2").unwrap(), r#"{"a":1,"b":[1],"c":2}"#);
+        assert_eq!(set(&format!("\u{feff}{}", json), "b.0", "2").unwrap(), r#"{"a":1,"b":[2]}"#);
+        #[cfg(not(feature = "lenient"))]
+        {
+            let opts = Options { preserve_untouched: true, ..Default::default() };
+            let result = delete_options(json, "a", Some(&opts)).unwrap();
+            assert_eq!(get(&result, "b"), Some(serde_json::json!([1])));
+            assert!(result.contains("\r\n  \"b\""));
+        }
+    }
+
+    #[test]
+    fn test_go_sjson_compat() {
+        // Examples from the tidwall/sjson documentation
+        let cases = [
+            (r#"{"name":{"last":"Anderson"}}"#, "name.last", "Smith", r#"{"name":{"last":"Smith"}}"#),
+            (r#"{"friends":["Andy","Carol"]}"#, "friends.2", "Sara", r#"{"friends":["Andy","Carol","Sara"]}"#),
+            (r#"{"friends":["Andy","Carol"]}"#, "friends.-1", "Sara", r#"{"friends":["Andy","Carol","Sara"]}"#),
+            (r#"{"friends":["Andy","Carol"]}"#, "friends.4", "Sara", r#"{"friends":["Andy","Carol",null,null,"Sara"]}"#),
+            (r#"{"app":{"name":"x"}}"#, "app.zip", "10001", r#"{"app":{"name":"x","zip":"10001"}}"#),
+            (r#"{"a":1}"#, "b", "true", r#"{"a":1,"b":"true"}"#),
+            (r#"{"a":1}"#, "b", "[1]", r#"{"a":1,"b":"[1]"}"#),
+        ];
+
+        let mut opts = Options { go_sjson_compat: true, ..Default::default() };
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            for (json, path, value, expected) in cases {
+                assert_eq!(set_options(json, path, value, Some(&opts)).unwrap(), expected, "{} {}", path, value);
+            }
+            assert_eq!(set_options("{}", "", "x", Some(&opts)), Err(SjsonError::EmptyPath));
+
+            // Json can still be written raw or typed
+            assert_eq!(set_raw_options(r#"{"a":1}"#, "b", "true", Some(&opts)).unwrap(), r#"{"a":1,"b":true}"#);
+            assert_eq!(set_int(r#"{"a":1}"#, "b", 10001, Some(&opts)).unwrap(), r#"{"a":1,"b":10001}"#);
+            assert_eq!(set_bool(r#"{"a":1}"#, "b", true, Some(&opts)).unwrap(), r#"{"a":1,"b":true}"#);
+            assert_eq!(set_float(r#"{"a":1}"#, "b", 1.5, Some(&opts)).unwrap(), r#"{"a":1,"b":1.5}"#);
+            assert_eq!(delete_options(r#"{"friends":["Andy","Carol"]}"#, "friends.-1", Some(&opts)).unwrap(), r#"{"friends":["Andy"]}"#);
+        }
+
+        // Without the flag -1 overwrites and values are read as json
+        let diverging = cases.iter().filter(|(json, path, value, expected)| set(json, path, value).unwrap() != *expected);
+        assert_eq!(diverging.map(|(_, path, _, _)| *path).collect::<Vec<_>>(), ["friends.-1", "app.zip", "b", "b"]);
+    }
+
+    #[test]
+    fn test_non_numeric_array_key() {
+        let json = r#"{"items":["a"],"data":[{"name":"x"}]}"#;
+        let is_non_numeric = |result: Result<String, SjsonError>, expected: &str| {
+            matches!(result, Err(SjsonError::CannotSetArrayElementForNonNumericKey(key)) if key == expected)
+        };
+
+        assert!(is_non_numeric(set(json, "items.name", "x"), "name"));
+        assert!(is_non_numeric(set(json, "data.first.name", "x"), "first"));
+        assert!(is_non_numeric(set_raw(json, "items.name", "1"), "name"));
+        assert!(is_non_numeric(delete(json, "items.name"), "name"));
+        assert!(is_non_numeric(delete(json, "data.first.name"), "first"));
+
+        let err = set(json, "items.name", "x").unwrap_err();
+        assert_eq!(err.to_string(), "cannot set array element for non-numeric key 'name'");
+    }
+
+    #[cfg(not(feature = "lenient"))]
+    #[test]
+    fn test_optimistic_delete_commas() {
+        let json = r#"{"name":"Tom","age":37,"city":"Beijing"}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+
+        let result = delete_options(json, "name", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"age":37,"city":"Beijing"}"#);
+        let result = delete_options(json, "age", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"name":"Tom","city":"Beijing"}"#);
+        let result = delete_options(json, "city", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"name":"Tom","age":37}"#);
+        let result = delete_options(r#"{"name":"Tom"}"#, "name", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{}"#);
+
+        let json = r#"{ "name": "Tom", "age": 37, "city": "Beijing" }"#;
+        let result = delete_options(json, "name", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{ "age": 37, "city": "Beijing" }"#);
+        let result = delete_options(json, "age", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{ "name": "Tom", "city": "Beijing" }"#);
+        let result = delete_options(json, "city", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{ "name": "Tom", "age": 37 }"#);
+
+        let json = r#"{"user":{"name":"Tom","age":37},"id":1}"#;
+        let result = delete_options(json, "user.name", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"user":{"age":37},"id":1}"#);
+    }
+
+    #[test]
+    fn test_optimistic_delete_array_commas() {
+        let json = r#"{"items":["a","b","c"]}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+
+        let result = delete_options(json, "items.0", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"items":["b","c"]}"#);
+        let result = delete_options(json, "items.1", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"items":["a","c"]}"#);
+        let result = delete_options(json, "items.2", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"items":["a","b"]}"#);
+        let result = delete_options(r#"{"items":["a"]}"#, "items.0", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"items":[]}"#);
+    }
+
+    #[test]
+    fn test_set_large_integers() {
+        let json = r#"{"id":1}"#;
+        let result = set(json, "id", "18446744073709551615").unwrap();
+        assert_eq!(result, r#"{"id":18446744073709551615}"#);
+
+        let result = set(json, "id", "9223372036854775808").unwrap();
+        assert_eq!(result, r#"{"id":9223372036854775808}"#);
+
+        let result = set(json, "id", "-9223372036854775808").unwrap();
+        assert_eq!(result, r#"{"id":-9223372036854775808}"#);
+
+        // Beyond u64 a splice keeps the digits of the number exactly. The
+        // lenient feature doesn't splice, so it needs arbitrary_precision.
+        let big = "123456789012345678901234567890";
+        #[cfg(any(not(feature = "lenient"), feature = "arbitrary_precision"))]
+        {
+            assert_eq!(set(json, "id", big).unwrap(), r#"{"id":123456789012345678901234567890}"#);
+            let optimistic = Options { optimistic: true, ..Default::default() };
+            assert_eq!(set_options(json, "id", big, Some(&optimistic)).unwrap(), r#"{"id":123456789012345678901234567890}"#);
+            assert_eq!(
+                set_int(json, "id", u128::MAX, None).unwrap(),
+                r#"{"id":340282366920938463463374607431768211455}"#
+            );
+        }
+
+        // The parser path can't hold it without arbitrary_precision, and
+        // the number never turns into a string
+        #[cfg(not(feature = "arbitrary_precision"))]
+        {
+            let err = SjsonError::NumberOutOfRange(big.to_string());
+            assert_eq!(set(json, "other", big), Err(err.clone()));
+            assert_eq!(set(json, "other", "1e400"), Err(SjsonError::NumberOutOfRange("1e400".to_string())));
+            assert_eq!(
+                set_int(json, "other", u128::MAX, None),
+                Err(SjsonError::NumberOutOfRange(u128::MAX.to_string()))
+            );
+            assert_eq!(err.to_string(), "number 123456789012345678901234567890 is out of range");
+        }
+        #[cfg(feature = "arbitrary_precision")]
+        assert_eq!(set(json, "other", big).unwrap(), r#"{"id":1,"other":123456789012345678901234567890}"#);
+    }
+
+    #[test]
+    fn test_is_json_number() {
+        for value in ["0", "-0", "7", "-12", "1.5", "0.25", "1e5", "1E+5", "-2.5e-3", "18446744073709551616"] {
+            assert!(is_json_number(value), "{}", value);
+        }
+        for value in ["", "-", "007", "-01", "+1", ".5", "5.", "1.0.0", "0x10", "1e", "1e+", "NaN", "inf", "1_000"] {
+            assert!(!is_json_number(value), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_set_number_like_strings() {
+        let json = r#"{"code":1}"#;
+        let mut opts = Options::default();
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            for value in ["007", "1.0.0", "+1", "0x10", ".5"] {
+                let result = set_options(json, "code", value, Some(&opts)).unwrap();
+                assert_eq!(result, format!(r#"{{"code":"{}"}}"#, value));
+            }
+
+            let result = set_options(json, "code", "1e5", Some(&opts)).unwrap();
+            let parsed: JsonValue = serde_json::from_str(&result).unwrap();
+            assert_eq!(parsed["code"].as_f64(), Some(100000.0));
+
+            let result = set_options(json, "code", "-0.5", Some(&opts)).unwrap();
+            assert_eq!(result, r#"{"code":-0.5}"#);
+        }
+    }
+
+    #[test]
+    fn test_document_matches_free_functions() {
+        let json = r#"{"name":"Tom","age":37,"items":["a","b","c"]}"#;
+        let mut doc = Document::parse(json).unwrap();
+        let mut expected = json.to_string();
+
+        let edits: [(&str, Option<&str>); 10] = [
+            ("name", Some("Jerry")),
+            ("age", Some("38")),
+            ("items.1", Some("x")),
+            ("items.-1", None),
+            ("address.city", Some("Beijing")),
+            ("address.zip", Some("100000")),
+            ("active", Some("true")),
+            ("age", None),
+            ("tags", Some(r#"["red","blue"]"#)),
+            ("items.0", None),
+        ];
+        for (path, value) in edits {
+            match value {
+                Some(value) => {
+                    doc.set(path, value).unwrap();
+                    expected = set(&expected, path, value).unwrap();
+                }
+                None => {
+                    doc.delete(path).unwrap();
+                    expected = delete(&expected, path).unwrap();
+                }
+            }
+        }
+
+        assert_eq!(doc.to_string(), expected);
+        assert_eq!(doc.into_string(), expected);
+    }
+
+    #[cfg(feature = "ordered")]
+    #[test]
+    fn test_document_keeps_key_order() {
+        let json = r#"{"z":1,"a":{"y":2,"b":3},"m":[{"q":4,"c":5}]}"#;
+        let mut doc = Document::parse(json).unwrap();
+        assert_eq!(doc.to_string(), json);
+
+        doc.set("a.x", "6").unwrap();
+        doc.set_raw("m.0.d", r#"{"k":7,"e":8}"#).unwrap();
+        doc.delete("z").unwrap();
+        let expected = r#"{"a":{"y":2,"b":3,"x":6},"m":[{"q":4,"c":5,"d":{"k":7,"e":8}}]}"#;
+        assert_eq!(doc.to_string(), expected);
+        assert_eq!(doc.into_string(), expected);
+    }
+
+    #[test]
+    fn test_document_errors() {
+        assert!(Document::parse("{").is_err());
+
+        let mut doc = Document::parse(r#"{"items":["a"]}"#).unwrap();
+        assert!(doc.set("", "x").is_err());
+        assert!(doc.set_raw("items.0", "{").is_err());
+        assert!(matches!(doc.delete("missing"), Err(SjsonError::NoChange)));
+
+        doc.set_raw("items.0", r#"{"a":1}"#).unwrap();
+        assert_eq!(doc.to_string(), r#"{"items":[{"a":1}]}"#);
+
+        // A failed edit leaves nothing behind, not even the parents it created
+        let mut doc = Document::parse("{}").unwrap();
+        assert!(doc.set("a.b.-1", "x").is_err());
+        assert!(doc.set("a.#(k=1).c", "x").is_err());
+        assert!(doc.set_raw("a.3.b.-1", "1").is_err());
+        assert_eq!(doc.to_string(), "{}");
+
+        // Nor does it keep a replaced scalar, or children a wildcard edited first
+        let json = r#"{"a":null,"s":[[1],[]]}"#;
+        let mut doc = Document::parse(json).unwrap();
+        assert!(doc.set("a.-1", "x").is_err());
+        assert!(doc.set("a.b.-1", "x").is_err());
+        assert!(doc.set("s.*.-1", "x").is_err());
+        assert_eq!(doc.to_string(), json);
+        doc.set("a.b", "x").unwrap();
+        assert_eq!(doc.to_string(), r#"{"a":{"b":"x"},"s":[[1],[]]}"#);
+    }
+
+    #[test]
+    fn test_editor_matches_free_functions() {
+        let json = r#"{"name":"Tom","items":[1,2,3],"meta":{"n":0}}"#;
+        let optimistic = Options { optimistic: true, ..Default::default() };
+        let mut editor = Editor::with_capacity(json.len() * 2);
+        let buffer = editor.buf.as_ptr();
+
+        for i in 0..10_000 {
+            let opts = if i % 2 == 0 { None } else { Some(&optimistic) };
+            let value = i.to_string();
+            match i % 5 {
+                0 => assert_eq!(editor.set(json, "name", &value, opts).unwrap(), set_options(json, "name", &value, opts).unwrap()),
+                1 => assert_eq!(editor.set(json, "meta.m", &value, opts).unwrap(), set_options(json, "meta.m", &value, opts).unwrap()),
+                2 => assert_eq!(
+                    editor.set_raw(json, "items.1", &value, opts).unwrap(),
+                    set_raw_options(json, "items.1", &value, opts).unwrap()
+                ),
+                3 => assert_eq!(editor.delete(json, "items.0", opts).unwrap(), delete_options(json, "items.0", opts).unwrap()),
+                _ => assert_eq!(editor.delete(json, "meta", opts).unwrap(), delete_options(json, "meta", opts).unwrap()),
+            }
+        }
+        // Every result fit, so the buffer was never reallocated
+        #[cfg(feature = "std")]
+        assert_eq!(editor.buf.as_ptr(), buffer);
+        #[cfg(not(feature = "std"))]
+        let _ = buffer;
+
+        // Errors are the same, and a later edit still works
+        assert_eq!(editor.set(json, "", "x", None), Err(SjsonError::EmptyPath));
+        assert_eq!(editor.delete(json, "missing", None), Err(SjsonError::NoChange));
+        assert!(matches!(editor.set_raw(json, "a", "{", None), Err(SjsonError::InvalidJson(_))));
+        let result = editor.set(json, "name", "Jerry", None).unwrap().to_string();
+        assert_eq!(editor.into_string(), result);
+    }
+
+    #[test]
+    fn test_large_document() {
+        let members: Vec<String> = (0..10_000).map(|i| format!(r#""key{:05}":{}"#, i, i)).collect();
+        let json = format!("{{{}}}", members.join(","));
+
+        let result = set(&json, "key05000", "changed").unwrap();
+        assert_eq!(result, json.replace(r#""key05000":5000"#, r#""key05000":"changed""#));
+
+        let result = set_raw(&json, "key09999", "[1,2]").unwrap();
+        assert_eq!(result, json.replace(r#""key09999":9999"#, r#""key09999":[1,2]"#));
+
+        let result = delete(&json, "key00000").unwrap();
+        assert_eq!(result, json.replace(r#""key00000":0,"#, ""));
+    }
+
+    #[cfg(not(feature = "lenient"))]
+    #[test]
+    fn test_set_raw_value() {
+        let json = r#"{"user":{"name":"Tom","address":null}}"#;
+        let raw = serde_json::value::RawValue::from_string(r#"{"city":"Beijing"}"#.to_string()).unwrap();
+
+        let result = set_raw_value(json, "user.address", &raw, None).unwrap();
+        assert_eq!(result, set_raw(json, "user.address", raw.get()).unwrap());
+
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        let result = set_raw_value(json, "user.address", &raw, Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"user":{"name":"Tom","address":{"city":"Beijing"}}}"#);
+        assert!(serde_json::from_str::<JsonValue>(&result).is_ok());
+    }
+
+    #[test]
+    fn test_set_many() {
+        let json = r#"{"name":"Tom"}"#;
+        let edits = [
+            ("address", "{}"),
+            ("address.city", "Beijing"),
+            ("address.tags", "[]"),
+            ("address.tags.0", "home"),
+            ("name", "Jerry"),
+        ];
+        let result = set_many(json, &edits, None).unwrap();
+        let expected: JsonValue = serde_json::from_str(r#"{"address":{"city":"Beijing","tags":["home"]},"name":"Jerry"}"#).unwrap();
+        assert_eq!(serde_json::from_str::<JsonValue>(&result).unwrap(), expected);
+
+        let mut expected = json.to_string();
+        for (path, value) in edits {
+            expected = set(&expected, path, value).unwrap();
+        }
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_set_many_error() {
+        let json = r#"{"items":["a"]}"#;
+        let edits = [("items.0", "b"), ("items.name", "c"), ("", "d")];
+        match set_many(json, &edits, None) {
+            Err(SjsonError::EditFailed { index, source }) => {
+                assert_eq!(index, 1);
+                assert!(matches!(*source, SjsonError::CannotSetArrayElementForNonNumericKey(_)));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let err = set_many(json, &[("", "d")], None).unwrap_err();
+        assert_eq!(err.to_string(), "edit 0 failed: path cannot be empty");
+        #[cfg(feature = "std")]
+        assert!(std::error::Error::source(&err).is_some());
+    }
+
+    #[cfg(not(feature = "lenient"))]
+    #[test]
+    fn test_set_in_place() {
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        opts.replace_in_place = true;
+
+        // Same length and shorter values are edited without reallocating
+        let mut buf = String::with_capacity(64);
+        buf.push_str(r#"{"name":"Tom","age":37}"#);
+        let ptr = buf.as_ptr();
+        set_in_place(&mut buf, "name", "Bob", Some(&opts)).unwrap();
+        assert_eq!(buf, r#"{"name":"Bob","age":37}"#);
+        set_in_place(&mut buf, "age", "5", Some(&opts)).unwrap();
+        assert_eq!(buf, r#"{"name":"Bob","age":5}"#);
+        assert_eq!(buf.as_ptr(), ptr);
+
+        // Longer values still produce the right result
+        set_in_place(&mut buf, "name", "Jerry Anderson", Some(&opts)).unwrap();
+        assert_eq!(buf, r#"{"name":"Jerry Anderson","age":5}"#);
+
+        // Paths the scanner can't reach fall back to the parser
+        set_in_place(&mut buf, "city", "Beijing", Some(&opts)).unwrap();
+        assert_eq!(
+            serde_json::from_str::<JsonValue>(&buf).unwrap(),
+            serde_json::json!({"name":"Jerry Anderson","age":5,"city":"Beijing"})
+        );
+
+        assert!(set_in_place(&mut buf, "", "x", Some(&opts)).is_err());
+    }
+
+    #[test]
+    fn test_error_on_nochange() {
+        let json = r#"{"name":"Tom","age":37,"items":[1,2]}"#;
+        let mut opts = Options::default();
+
+        // Without the flag an identical value is still written
+        let result = set_options(json, "name", "Tom", Some(&opts)).unwrap();
+        assert!(result.contains(r#""name":"Tom""#));
+
+        opts.error_on_nochange = true;
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            assert!(matches!(set_options(json, "name", "Tom", Some(&opts)), Err(SjsonError::NoChange)));
+            assert!(matches!(set_options(json, "age", "37", Some(&opts)), Err(SjsonError::NoChange)));
+            assert!(matches!(set_options(json, "items.1", "2", Some(&opts)), Err(SjsonError::NoChange)));
+            assert!(matches!(set_raw_options(json, "items", "[1, 2]", Some(&opts)), Err(SjsonError::NoChange)));
+            assert!(matches!(set_bytes_options(json.as_bytes(), "age", "37", Some(&opts)), Err(SjsonError::NoChange)));
+
+            let result = set_options(json, "age", "38", Some(&opts)).unwrap();
+            assert!(result.contains(r#""age":38"#));
+            let result = set_options(json, "city", "Beijing", Some(&opts)).unwrap();
+            assert!(result.contains(r#""city":"Beijing""#));
+        }
+    }
+
+    #[cfg(not(feature = "lenient"))]
+    #[test]
+    fn test_invalid_json_error() {
+        let json = "{\n  \"name\": \"Tom\",\n  \"age\": ,\n}";
+        match set(json, "name", "Jerry") {
+            Err(SjsonError::InvalidJson(e)) => {
+                assert_eq!(e.line(), 3);
+                assert_eq!(e.column(), 10);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        assert!(matches!(set_raw(r#"{"a":1}"#, "a", "{"), Err(SjsonError::InvalidJson(e)) if e.is_eof()));
+        assert!(matches!(delete("[1,", "0"), Err(SjsonError::InvalidJson(_))));
+
+        let err = SjsonError::from(serde_json::from_str::<JsonValue>("nope").unwrap_err());
+        assert!(err.to_string().starts_with("invalid json: expected ident"));
+    }
+
+    #[test]
+    fn test_is_optimistic_path() {
+        for path in ["name", "user.name", "items.0", "items.-1", "first_name", "last-name", "A.Z.a.z.0.9"] {
+            assert!(is_optimistic_path(path, '.'), "{}", path);
+        }
+        for ch in [' ', '!', '"', '#', '$', '*', '+', ',', '/', ':', ';', '<', '=', '>', '?', '@', '[', '\\', ']', '^', '`', '{', '|', '}', '~', 'é'] {
+            let path = format!("a{}b", ch);
+            assert!(!is_optimistic_path(&path, '.'), "{}", path);
+        }
+    }
+
+    #[cfg(not(feature = "lenient"))]
+    #[test]
+    fn test_optimistic_delete_array_element_keeps_formatting() {
+        // The spacing is kept, which shows the parser wasn't used
+        let json = r#"{"items": ["a", {"b": [1, 2]}, "c,d"], "z": 1}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+
+        let result = delete_options(json, "items.0", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"items": [{"b": [1, 2]}, "c,d"], "z": 1}"#);
+        let result = delete_options(json, "items.1", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"items": ["a", "c,d"], "z": 1}"#);
+        let result = delete_options(json, "items.2", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"items": ["a", {"b": [1, 2]}], "z": 1}"#);
+        let result = delete_options(json, "items.-1", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"items": ["a", {"b": [1, 2]}], "z": 1}"#);
 
-/// Set a float value
-pub fn set_float<T: std::fmt::Display>(
-    json: &str,
-    path: &str,
-    value: T,
-    opts: Option<&Options>,
-) -> Result<String, SjsonError> {
-    let raw = value.to_string();
-    set_options(json, path, &raw, opts)
-}
+        // Out of range indices fall back to the parser
+        assert!(matches!(delete_options(json, "items.3", Some(&opts)), Err(SjsonError::NoChange)));
+    }
 
-/// Generic Set function that accepts any value that can be serialized to JSON
-pub fn set_value<T: serde::Serialize>(
-    json: &str,
-    path: &str,
-    value: &T,
-    opts: Option<&Options>,
-) -> Result<String, SjsonError> {
-    let json_value = serde_json::to_string(value)
-        .map_err(|e| SjsonError::Custom(format!("Failed to serialize value: {}", e)))?;
-    
-    set_raw_options(json, path, &json_value, opts)
-}
+    #[test]
+    fn test_array_elements() {
+        let json = br#"[ 1 , "a,]" ,[2,3], {"k":[4]} ]"#;
+        let spans: Vec<&[u8]> = array_elements(json, 0)
+            .unwrap()
+            .into_iter()
+            .map(|(start, end)| &json[start..end])
+            .collect();
+        assert_eq!(spans, vec![&b"1"[..], br#""a,]""#, b"[2,3]", br#"{"k":[4]}"#]);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        assert_eq!(array_elements(b"[ ]", 0), Some(vec![]));
+        assert_eq!(array_elements(b"[1,]", 0), None);
+        assert_eq!(array_elements(b"[1", 0), None);
+        assert_eq!(array_elements(b"{}", 0), None);
+    }
 
     #[test]
-    fn test_set_simple() {
+    fn test_set_raw_root() {
+        let json = r#"{"name":"Tom"}"#;
+        let result = set_raw(json, ROOT_PATH, r#"{"age":37}"#).unwrap();
+        assert_eq!(result, r#"{"age":37}"#);
+        let result = set_raw(json, "@this", "[1, 2, 3]").unwrap();
+        assert_eq!(result, "[1,2,3]");
+        let result = set_raw(json, "@this", r#""scalar""#).unwrap();
+        assert_eq!(result, r#""scalar""#);
+        let result = set_raw_bytes(json.as_bytes(), "@this", "42").unwrap();
+        assert_eq!(result, b"42");
+
+        assert!(matches!(set_raw(json, "@this", "{"), Err(SjsonError::InvalidJson(_))));
+        assert!(matches!(set_raw(json, "@this", "nope"), Err(SjsonError::InvalidJson(_))));
+
+        // An escaped @this is an ordinary key
+        let result = set_raw(json, r"\@this", "1").unwrap();
+        assert!(result.contains(r#""@this":1"#));
+
+        // The root itself can't be deleted, the key only when escaped
+        let json = r#"{"@this":1,"a":2}"#;
+        let optimistic = Options { optimistic: true, ..Default::default() };
+        for opts in [None, Some(&optimistic)] {
+            assert_eq!(delete_options(json, ROOT_PATH, opts), Err(SjsonError::InvalidPath));
+            assert_eq!(delete_options(json, r"\@this", opts).unwrap(), r#"{"a":2}"#);
+        }
+        assert_eq!(delete_path(json, &Path::parse(ROOT_PATH).unwrap(), None), Err(SjsonError::InvalidPath));
+        assert_eq!(delete_bytes(json.as_bytes(), ROOT_PATH), Err(SjsonError::InvalidPath));
+        assert_eq!(delete_with_old(json, ROOT_PATH, None), Err(SjsonError::InvalidPath));
+        let mut value = serde_json::json!({"@this": 1});
+        assert_eq!(delete_in_value(&mut value, ROOT_PATH, None), Err(SjsonError::InvalidPath));
+    }
+
+    #[test]
+    fn test_get() {
+        let json = r#"{"name":{"first":"Tom","last":"Anderson"},"age":37,"children":["Sara","Alex","Jack"],"friends":[{"first":"James"},{"first":"Roger"}]}"#;
+        assert_eq!(get(json, "name.last"), Some(JsonValue::from("Anderson")));
+        assert_eq!(get(json, "age"), Some(JsonValue::from(37)));
+        assert_eq!(get(json, "children.1"), Some(JsonValue::from("Alex")));
+        assert_eq!(get(json, "children.-1"), Some(JsonValue::from("Jack")));
+        assert_eq!(get(json, "friends.-2.first"), Some(JsonValue::from("James")));
+        assert_eq!(get(json, "friends.0"), Some(serde_json::json!({"first":"James"})));
+
+        assert_eq!(get(json, "name.middle"), None);
+        assert_eq!(get(json, "children.3"), None);
+        assert_eq!(get(json, "children.-4"), None);
+        assert_eq!(get(json, "children.x"), None);
+        assert_eq!(get(json, "age.value"), None);
+        assert_eq!(get(json, ""), None);
+        assert_eq!(get("{", "age"), None);
+
+        let doc = Document::parse(json).unwrap();
+        assert_eq!(doc.get("friends.1.first"), Some(JsonValue::from("Roger")));
+    }
+
+    #[test]
+    fn test_get_or_set() {
+        let json = r#"{"name":"Tom","nickname":null,"ports":[80]}"#;
+        let lookup = |path: &str, default: &str| get_or_set(json, path, default, None).unwrap();
+
+        // Present values are returned and the json is left as it was
+        assert_eq!(lookup("name", "Jerry"), (json.to_string(), JsonValue::from("Tom")));
+        assert_eq!(lookup("nickname", "x"), (json.to_string(), JsonValue::Null));
+        assert_eq!(lookup("ports.-1", "443"), (json.to_string(), JsonValue::from(80)));
+        assert_eq!(lookup("ports", "[]"), (json.to_string(), serde_json::json!([80])));
+
+        // Absent values are inserted and returned
+        let (result, value) = lookup("timeout", "30");
+        assert_eq!(value, JsonValue::from(30));
+        assert_eq!(get(&result, "timeout"), Some(JsonValue::from(30)));
+        assert_eq!(get(&result, "name"), Some(JsonValue::from("Tom")));
+        let (result, value) = lookup("db.hosts", r#"["a","b"]"#);
+        assert_eq!(value, serde_json::json!(["a", "b"]));
+        assert_eq!(get(&result, "db"), Some(serde_json::json!({"hosts": ["a", "b"]})));
+        let (result, value) = lookup("ports.1", "443");
+        assert_eq!((get(&result, "ports"), value), (Some(serde_json::json!([80, 443])), JsonValue::from(443)));
+
+        assert_eq!(get_or_set(json, "", "1", None), Err(SjsonError::EmptyPath));
+        assert!(matches!(get_or_set("{", "a", "1", None), Err(SjsonError::InvalidJson(_))));
+    }
+
+    #[test]
+    fn test_set_if_absent() {
+        let json = r#"{"name":"Tom","nickname":null,"items":["a",null]}"#;
+        let mut opts = Options::default();
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            assert!(matches!(set_if_absent(json, "name", "Jerry", Some(&opts)), Err(SjsonError::NoChange)));
+            assert!(matches!(set_if_absent(json, "nickname", "Jerry", Some(&opts)), Err(SjsonError::NoChange)));
+            assert!(matches!(set_if_absent(json, "items.0", "b", Some(&opts)), Err(SjsonError::NoChange)));
+            assert!(matches!(set_if_absent(json, "items.1", "b", Some(&opts)), Err(SjsonError::NoChange)));
+
+            let result = set_if_absent(json, "age", "37", Some(&opts)).unwrap();
+            assert_eq!(get(&result, "age"), Some(JsonValue::from(37)));
+            assert_eq!(get(&result, "name"), Some(JsonValue::from("Tom")));
+
+            let result = set_if_absent(json, "items.2", "c", Some(&opts)).unwrap();
+            assert_eq!(get(&result, "items"), Some(serde_json::json!(["a", null, "c"])));
+        }
+
+        let mut opts = Options::default();
+        opts.only_if_absent = true;
+        assert!(matches!(set_raw_options(json, "name", "1", Some(&opts)), Err(SjsonError::NoChange)));
+        let result = set_raw_options(json, "address", r#"{"city":"Beijing"}"#, Some(&opts)).unwrap();
+        assert_eq!(get(&result, "address.city"), Some(JsonValue::from("Beijing")));
+    }
+
+    #[test]
+    fn test_array_index_errors() {
+        let json = r#"{"items":["a","b"]}"#;
+
+        let err = delete(json, "items.-5").unwrap_err();
+        assert!(matches!(err, SjsonError::ArrayIndexOutOfBounds { index: -5, len: 2 }));
+        assert_eq!(err.to_string(), "array index -5 out of bounds for length 2");
+        assert!(matches!(set(json, "items.-3", "x"), Err(SjsonError::ArrayIndexOutOfBounds { index: -3, len: 2 })));
+
+        assert!(matches!(delete(json, "items.x"), Err(SjsonError::CannotSetArrayElementForNonNumericKey(_))));
+
+        // A positive index past the end is simply absent
+        assert!(matches!(delete(json, "items.99"), Err(SjsonError::NoChange)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_pretty() {
+        let json = r#"{"name":"Tom","tags":["a"]}"#;
+        let mut opts = Options::default();
+        opts.optimistic = true;
+        opts.pretty = Some(PrettyOptions::default());
+
+        let result = set_options(json, "name", "Jerry", Some(&opts)).unwrap();
+        assert_eq!(result, "{\n  \"name\": \"Jerry\",\n  \"tags\": [\n    \"a\"\n  ]\n}");
+
+        opts.pretty = Some(PrettyOptions { indent: "\t".to_string(), sort_keys: true });
+        let result = delete_options(r#"{"b":{"y":1,"x":2},"a":1,"c":3}"#, "c", Some(&opts)).unwrap();
+        assert_eq!(result, "{\n\t\"a\": 1,\n\t\"b\": {\n\t\t\"x\": 2,\n\t\t\"y\": 1\n\t}\n}");
+
+        let result = set_raw_bytes_options(json.as_bytes(), "tags", "[]", Some(&opts)).unwrap();
+        assert_eq!(result, b"{\n\t\"name\": \"Tom\",\n\t\"tags\": []\n}");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_compact() {
+        let json = r#"{"name":"Tom","tags":["a"]}"#;
+        let mut opts = Options { optimistic: true, ..Default::default() };
+        assert_eq!(set_options(json, "name", "Jerry", Some(&opts)).unwrap(), r#"{"name":"Jerry","tags":["a"]}"#);
+
+        // Not compact pretty prints, also where the fast path would apply
+        opts.compact = false;
+        let pretty = "{\n  \"name\": \"Jerry\",\n  \"tags\": [\n    \"a\"\n  ]\n}";
+        assert_eq!(set_options(json, "name", "Jerry", Some(&opts)).unwrap(), pretty);
+        assert_eq!(delete_options(json, "tags", Some(&opts)).unwrap(), "{\n  \"name\": \"Tom\"\n}");
+        let mut buf = Vec::new();
+        set_to_writer(&mut buf, json, "name", "Jerry", Some(&opts)).unwrap();
+        assert_eq!(buf, pretty.as_bytes());
+
+        // An explicit layout wins
+        opts.pretty = Some(PrettyOptions { indent: "\t".to_string(), sort_keys: false });
+        assert_eq!(delete_options(json, "tags", Some(&opts)).unwrap(), "{\n\t\"name\": \"Tom\"\n}");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_sort_keys() {
+        let json = r#"{"z":{"b":[{"y":1,"x":2},3],"a":null},"m":1}"#;
+        let mut opts = Options { optimistic: true, sort_keys: true, ..Default::default() };
+        let sorted = r#"{"c":true,"m":1,"z":{"a":null,"b":[{"x":2,"y":1},3]}}"#;
+        assert_eq!(set_options(json, "c", "true", Some(&opts)).unwrap(), sorted);
+        let mut buf = Vec::new();
+        set_to_writer(&mut buf, json, "c", "true", Some(&opts)).unwrap();
+        assert_eq!(buf, sorted.as_bytes());
+        assert_eq!(delete_options(json, "m", Some(&opts)).unwrap(), r#"{"z":{"a":null,"b":[{"x":2,"y":1},3]}}"#);
+
+        // Also pretty printed
+        opts.compact = false;
+        let result = set_raw_options(r#"{"b":[2,1],"a":{"d":1,"c":2}}"#, "a.d", "0", Some(&opts)).unwrap();
+        assert_eq!(result, "{\n  \"a\": {\n    \"c\": 2,\n    \"d\": 0\n  },\n  \"b\": [\n    2,\n    1\n  ]\n}");
+
+        // The fast path can't sort, so it isn't taken
+        let opts = Options { preserve_untouched: true, sort_keys: true, ..Default::default() };
+        assert_eq!(set_options(json, "m", "2", Some(&opts)), Err(SjsonError::SpliceUnavailable));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_escape_output() {
+        let mut opts = Options { optimistic: true, escape_slashes: true, ..Default::default() };
+        assert_eq!(set_options(r#"{"a":1}"#, "a", "a/b", Some(&opts)).unwrap(), r#"{"a":"a\/b"}"#);
+        // The rest of the document is escaped too, keys included
+        assert_eq!(set_options(r#"{"url/":"http://x","z":1}"#, "z", "2", Some(&opts)).unwrap(), r#"{"url\/":"http:\/\/x","z":2}"#);
+
+        opts.escape_slashes = false;
+        opts.escape_non_ascii = true;
+        assert_eq!(set_options(r#"{"a":1}"#, "a", "é", Some(&opts)).unwrap(), r#"{"a":"\u00e9"}"#);
+        assert_eq!(set_options(r#"{"a":1}"#, "a", "a/é😀\n\"", Some(&opts)).unwrap(), r#"{"a":"a/\u00e9\ud83d\ude00\n\""}"#);
+        assert_eq!(set_options(r#"{"a":1}"#, "ключ", "1", Some(&opts)).unwrap(), r#"{"a":1,"\u043a\u043b\u044e\u0447":1}"#);
+
+        // The escapes read back as the same strings
+        let result = set_options(r#"{"a":"é/ü"}"#, "b", "😀", Some(&Options { escape_slashes: true, ..opts.clone() })).unwrap();
+        assert!(result.is_ascii() && !result.contains("é/"));
+        assert_eq!(get(&result, "a"), Some(serde_json::json!("é/ü")));
+        assert_eq!(get(&result, "b"), Some(serde_json::json!("😀")));
+
+        opts.pretty = Some(PrettyOptions::default());
+        assert_eq!(set_options("{}", "a", "é", Some(&opts)).unwrap(), "{\n  \"a\": \"\\u00e9\"\n}");
+    }
+
+    #[test]
+    fn test_set_json_value() {
+        let json = r#"{"name":"Tom","friends":[{"name":"Sara"}]}"#;
+        let value = serde_json::json!({"city": "Paris", "zip": [75001, 75002]});
+
+        let result = set_json_value(json, "friends.0.address", value, None).unwrap();
+        let expected = set_raw(
+            json,
+            "friends.0.address",
+            r#"{"city":"Paris","zip":[75001,75002]}"#,
+        )
+        .unwrap();
+        assert_eq!(result, expected);
+
+        let result = set_json_value(json, "name", JsonValue::Null, None).unwrap();
+        assert!(result.contains(r#""name":null"#));
+    }
+
+    #[cfg(not(feature = "lenient"))]
+    #[test]
+    fn test_treat_null_as_delete() {
         let json = r#"{"name":"Tom","age":37}"#;
+
+        let result = set_options(json, "age", "null", None).unwrap();
+        assert!(result.contains(r#""age":null"#));
+        assert_eq!(get(&result, "age"), Some(JsonValue::Null));
+
+        let mut opts = Options::default();
+        opts.treat_null_as_delete = true;
+        let result = set_options(json, "age", "null", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"name":"Tom"}"#);
+
+        opts.optimistic = true;
+        let result = set_options(json, "age", "null", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"name":"Tom"}"#);
+
+        // Other values are still set as usual
+        let result = set_options(json, "age", "38", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"name":"Tom","age":38}"#);
+
+        // Also when editing a buffer in place
+        opts.replace_in_place = true;
+        let mut buf = json.to_string();
+        set_in_place(&mut buf, "age", "null", Some(&opts)).unwrap();
+        assert_eq!(buf, r#"{"name":"Tom"}"#);
+        set_in_place(&mut buf, "name", "Jo", Some(&opts)).unwrap();
+        assert_eq!(buf, r#"{"name":"Jo"}"#);
+
+        // The byte and writer variants delete just the same
+        for optimistic in [false, true] {
+            let opts = Options { treat_null_as_delete: true, optimistic, ..Default::default() };
+            let result = set_bytes_options(json.as_bytes(), "age", "null", Some(&opts)).unwrap();
+            assert_eq!(result, br#"{"name":"Tom"}"#);
+            #[cfg(feature = "std")]
+            {
+                let mut out = Vec::new();
+                set_to_writer(&mut out, json, "age", "null", Some(&opts)).unwrap();
+                assert_eq!(out, br#"{"name":"Tom"}"#);
+            }
+        }
+    }
+
+    #[cfg(feature = "arbitrary_precision")]
+    #[test]
+    fn test_arbitrary_precision() {
+        let json = r#"{"max":1.7976931348623159e308,"big":1234567890.123456789012345678901234567890,"name":"Tom"}"#;
+
         let result = set(json, "name", "Jerry").unwrap();
-        assert_eq!(result, r#"{"age":37,"name":"Jerry"}"#);
+        assert!(result.contains(r#""max":1.7976931348623159e308"#));
+        assert!(result.contains(r#""big":1234567890.123456789012345678901234567890"#));
+
+        let result = set(json, "name", "0.1234567890123456789012345678901234567890").unwrap();
+        assert!(result.contains(r#""name":0.1234567890123456789012345678901234567890"#));
+
+        let result = set(json, "name", "123456789012345678901234567890").unwrap();
+        assert!(result.contains(r#""name":123456789012345678901234567890"#));
     }
 
     #[test]
-    fn test_set_nested() {
-        let json = r#"{"name":{"first":"Tom","last":"Anderson"}}"#;
-        let result = set(json, "name.first", "Jerry").unwrap();
-        assert_eq!(result, r#"{"name":{"first":"Jerry","last":"Anderson"}}"#);
+    fn test_set_cow() {
+        let json = r#"{"name":"Tom","age":37,"items":[1,2]}"#;
+        let mut opts = Options::default();
+
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+
+            let result = set_cow(json, "age", "37", Some(&opts)).unwrap();
+            assert!(matches!(result, Cow::Borrowed(s) if core::ptr::eq(s, json)));
+            let result = set_cow(json, "items.1", "2", Some(&opts)).unwrap();
+            assert!(matches!(result, Cow::Borrowed(_)));
+
+            let result = set_cow(json, "age", "38", Some(&opts)).unwrap();
+            assert!(matches!(result, Cow::Owned(_)));
+            assert!(result.contains(r#""age":38"#));
+        }
+
+        opts.error_on_nochange = true;
+        assert!(matches!(set_cow(json, "age", "37", Some(&opts)), Err(SjsonError::NoChange)));
+        assert!(matches!(set_cow(json, "", "37", None), Err(SjsonError::EmptyPath)));
     }
 
     #[test]
-    fn test_set_array() {
-        let json = r#"{"children":["Sara","Alex","Jack"]}"#;
-        let result = set(json, "children.1", "Jerry").unwrap();
-        assert_eq!(result, r#"{"children":["Sara","Jerry","Jack"]}"#);
-        let result = set(json, "children", "[]").unwrap();
-        assert_eq!(result, r#"{"children":[]}"#);
+    fn test_delete_cow() {
+        let json = r#"{"name":"Tom","items":[1,2]}"#;
+
+        assert!(matches!(delete_cow(json, "age", None).unwrap(), Cow::Borrowed(_)));
+        assert!(matches!(delete_cow(json, "items.5", None).unwrap(), Cow::Borrowed(_)));
+
+        let result = delete_cow(json, "name", None).unwrap();
+        assert!(matches!(result, Cow::Owned(_)));
+        assert_eq!(result, r#"{"items":[1,2]}"#);
     }
 
+    #[cfg(feature = "ordered")]
     #[test]
-    fn test_set_new_field() {
-        let json = r#"{"name":"Tom"}"#;
-        let result = set(json, "age", "37").unwrap();
-        assert_eq!(result, r#"{"age":37,"name":"Tom"}"#);
+    fn test_ordered() {
+        let json = r#"{"name":"Tom","age":37,"address":{"zip":"100000","city":"Beijing"}}"#;
+
+        let result = set(json, "age", "38").unwrap();
+        assert_eq!(result, r#"{"name":"Tom","age":38,"address":{"zip":"100000","city":"Beijing"}}"#);
+
+        let result = set(&result, "address.country", "China").unwrap();
+        assert_eq!(
+            result,
+            r#"{"name":"Tom","age":38,"address":{"zip":"100000","city":"Beijing","country":"China"}}"#
+        );
+
+        let result = set(&result, "active", "true").unwrap();
+        assert!(result.ends_with(r#""country":"China"},"active":true}"#));
+
+        let result = delete(&result, "name").unwrap();
+        assert!(result.starts_with(r#"{"age":38,"address":{"zip""#));
+
+        let result = set_raw(json, "tags", r#"{"z":1,"a":2}"#).unwrap();
+        assert!(result.ends_with(r#""tags":{"z":1,"a":2}}"#));
     }
 
     #[test]
-    fn test_array_index_operation() {
-        let json = r#"{"children":["Sara","Alex","Jack"]}"#;
-        let result = set(json, "children.-1", "Jerry").unwrap();
-        assert_eq!(result, r#"{"children":["Sara","Alex","Jerry"]}"#);
-        let result = delete(json, "children.-1").unwrap();
-        assert_eq!(result, r#"{"children":["Sara","Alex"]}"#);
+    fn test_root_array() {
+        let json = "[1,2,3]";
+        let mut opts = Options::default();
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            assert_eq!(set_options(json, "0", "9", Some(&opts)).unwrap(), "[9,2,3]");
+            assert_eq!(set_options(json, "-1", "9", Some(&opts)).unwrap(), "[1,2,9]");
+            assert_eq!(set_options(json, "5", "9", Some(&opts)).unwrap(), "[1,2,3,null,null,9]");
+            assert_eq!(set_options(json, "#", "9", Some(&opts)).unwrap(), "[1,2,3,9]");
+            assert_eq!(set_raw_options(json, "1", "[0]", Some(&opts)).unwrap(), "[1,[0],3]");
+            assert_eq!(set_raw_options(json, "-3", r#"{"a":1}"#, Some(&opts)).unwrap(), r#"[{"a":1},2,3]"#);
+            assert_eq!(delete_options(json, "1", Some(&opts)).unwrap(), "[1,3]");
+            assert_eq!(delete_options(json, "-1", Some(&opts)).unwrap(), "[1,2]");
+            assert!(matches!(delete_options(json, "3", Some(&opts)), Err(SjsonError::NoChange)));
+        }
+
+        let opts = Options { go_compatible_append: true, ..Default::default() };
+        assert_eq!(set_options(json, "-1", "9", Some(&opts)).unwrap(), "[1,2,3,9]");
     }
 
     #[test]
-    fn test_negative_array_indices() {
-        let json = r#"{"items":["a","b","c","d","e"]}"#;
-        
-        // Test -1 (last element)
-        let result = set(json, "items.-1", "z").unwrap();
-        assert_eq!(result, r#"{"items":["a","b","c","d","z"]}"#);
-        
-        // Test -2 (second to last)
-        let result = set(json, "items.-2", "y").unwrap();
-        assert_eq!(result, r#"{"items":["a","b","c","y","e"]}"#);
-        
-        // Test -3 (third to last)
-        let result = set(json, "items.-3", "x").unwrap();
-        assert_eq!(result, r#"{"items":["a","b","x","d","e"]}"#);
+    fn test_path() {
+        let path = Path::parse(r"friends.-1.user\.name").unwrap();
+        assert_eq!(
+            path.segments(),
+            [Segment::Key("friends".to_string()), Segment::Index(-1), Segment::Key("user.name".to_string())]
+        );
+
+        let json = r#"{"friends":[{"user.name":"Sara"},{"user.name":"Alex"}]}"#;
+        let mut result = json.to_string();
+        for name in ["Jack", "Jerry", "Tom"] {
+            result = set_path(&result, &path, name, None).unwrap();
+            assert_eq!(result, set(json, r"friends.-1.user\.name", name).unwrap());
+        }
+
+        let path = Path::parse("friends.0").unwrap();
+        let result = delete_path(json, &path, None).unwrap();
+        assert_eq!(result, r#"{"friends":[{"user.name":"Alex"}]}"#);
+
+        // An index segment is used as a key on objects
+        let path = Path::parse("ids.7").unwrap();
+        assert_eq!(set_path(r#"{"ids":{}}"#, &path, "x", None).unwrap(), r#"{"ids":{"7":"x"}}"#);
+        assert_eq!(Path::parse("a.007").unwrap().segments()[1], Segment::Key("007".to_string()));
+
+        let path = Path::parse(ROOT_PATH).unwrap();
+        assert!(path.segments().is_empty());
+        assert_eq!(set_path(json, &path, "[1]", None).unwrap(), "[1]");
+        assert!(matches!(delete_path(json, &path, None), Err(SjsonError::InvalidPath)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_path_shared_across_threads() {
+        fn assert_send_sync<T: Send + Sync + Clone>() {}
+        assert_send_sync::<Path>();
+        assert_send_sync::<Segment>();
+
+        let path = Arc::new(Path::parse("stats.count").unwrap());
+        let handles: Vec<_> = (0..2)
+            .map(|i| {
+                let path = Arc::clone(&path);
+                std::thread::spawn(move || {
+                    let mut json = String::from("{}");
+                    for n in 0..100 {
+                        json = set_path(&json, &path, &(n * 2 + i).to_string(), None).unwrap();
+                    }
+                    json
+                })
+            })
+            .collect();
+
+        let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(results, [r#"{"stats":{"count":198}}"#, r#"{"stats":{"count":199}}"#]);
+    }
+
+    #[test]
+    fn test_set_and_delete_segments() {
+        let json = r#"{"hosts":{"example.com":{"ports":[80,443]}}}"#;
+        let result = set_segments(json, &["hosts", "example.com", "ports", "0"], "8080", None).unwrap();
+        assert_eq!(result, r#"{"hosts":{"example.com":{"ports":[8080,443]}}}"#);
+        let result = set_segments(json, &["hosts", "example.com", "ports", "-1"], "8443", None).unwrap();
+        assert_eq!(result, r#"{"hosts":{"example.com":{"ports":[80,8443]}}}"#);
+        let result = delete_segments(json, &["hosts", "example.com", "ports", "0"], None).unwrap();
+        assert_eq!(result, r#"{"hosts":{"example.com":{"ports":[443]}}}"#);
+
+        // Nothing is unescaped or treated as path syntax
+        for key in ["a.b", r"a\.b", "#", "#(a=1)", "@this", "$", "007"] {
+            let result = set_segments("{}", &["k", key], "1", None).unwrap();
+            assert_eq!(get(&result, "k"), Some(serde_json::json!({ key: 1 })), "{:?}", key);
+            assert_eq!(delete_segments(&result, &["k", key], None).unwrap(), r#"{"k":{}}"#);
+        }
+
+        assert_eq!(set_segments(json, &[], "1", None), Err(SjsonError::EmptyPath));
+        assert_eq!(set_segments(json, &["hosts", ""], "1", None), Err(SjsonError::InvalidPath));
+        assert_eq!(delete_segments(json, &["missing"], None), Err(SjsonError::NoChange));
+    }
+
+    #[test]
+    fn test_set_flattened() {
+        let json = set_flattened("{}", "DB__HOST", "localhost", "__", None).unwrap();
+        let json = set_flattened(&json, "DB__PORT", "5432", "__", None).unwrap();
+        assert_eq!(json, r#"{"DB":{"HOST":"localhost","PORT":5432}}"#);
+
+        let json = set_flattened(&json, "DB__REPLICAS__0", "db.internal", "__", None).unwrap();
+        assert_eq!(get(&json, "DB.REPLICAS"), Some(serde_json::json!(["db.internal"])));
+        assert_eq!(set_flattened("{}", "log.level", "info", "__", None).unwrap(), r#"{"log.level":"info"}"#);
+
+        assert_eq!(set_flattened("{}", "", "x", "__", None), Err(SjsonError::EmptyPath));
+        assert_eq!(set_flattened("{}", "DB____HOST", "x", "__", None), Err(SjsonError::InvalidPath));
+        assert_eq!(set_flattened("{}", "DB__", "x", "__", None), Err(SjsonError::InvalidPath));
+        assert_eq!(set_flattened("{}", "DB", "x", "", None), Err(SjsonError::InvalidPath));
+    }
+
+    #[test]
+    fn test_path_parse_errors() {
+        assert!(matches!(Path::parse(""), Err(SjsonError::EmptyPath)));
+        assert!(matches!(Path::parse("a..b"), Err(SjsonError::InvalidPath)));
+        assert!(matches!(Path::parse("a."), Err(SjsonError::InvalidPath)));
+        assert!(matches!(Path::parse(".a"), Err(SjsonError::InvalidPath)));
+    }
+
+    #[test]
+    fn test_sjson_set_macro() {
+        let base = r#"{"a":{"d":true}}"#.to_string();
+        let key = "e";
+        let value = 7.to_string();
+
+        let result = sjson_set!(base,
+            "a.b" => "1",
+            "a.c" => raw r#"[1,2]"#,
+            delete "a.d",
+            format!("a.{}", key) => value,
+        )
+        .unwrap();
+        assert_eq!(result, r#"{"a":{"b":1,"c":[1,2],"e":7}}"#);
+
+        let result = sjson_set!(&base, "a.d" => "false").unwrap();
+        assert_eq!(result, r#"{"a":{"d":false}}"#);
+    }
+
+    #[test]
+    fn test_sjson_set_macro_error() {
+        let json = r#"{"a":[1]}"#;
+        let result = sjson_set!(json, delete "a.5", "a.0" => "2");
+        assert!(matches!(result, Err(SjsonError::NoChange)));
+
+        let result = sjson_set!(json, "a.0" => raw "{");
+        assert!(matches!(result, Err(SjsonError::InvalidJson(_))));
+    }
+
+    #[test]
+    fn test_set_all() {
+        let json = r#"{"users":[{"n":"a"},{"n":"b"}]}"#;
+        let result = set_all(json, "users", "active", "true", None).unwrap();
+        assert_eq!(get(&result, "users.0.active"), Some(JsonValue::Bool(true)));
+        assert_eq!(get(&result, "users.1.active"), Some(JsonValue::Bool(true)));
+
+        let result = set_all(json, "users", "meta.role", "admin", None).unwrap();
+        assert_eq!(get(&result, "users.1.meta"), Some(serde_json::json!({"role": "admin"})));
+    }
+
+    #[test]
+    fn test_set_all_mixed_array() {
+        let json = r#"{"users":[{"n":"a"},1,"b",null,[2],{"n":"c"}]}"#;
+        let result = set_all(json, "users", "active", "true", None).unwrap();
+        assert_eq!(
+            get(&result, "users"),
+            Some(serde_json::json!([{"n":"a","active":true},1,"b",null,[2],{"n":"c","active":true}]))
+        );
+
+        let mut opts = Options::default();
+        opts.error_on_non_object = true;
+        assert!(matches!(
+            set_all(json, "users", "active", "true", Some(&opts)),
+            Err(SjsonError::JsonMustBeObjectOrArray)
+        ));
+    }
+
+    #[test]
+    fn test_set_all_nothing_to_change() {
+        let json = r#"{"users":[],"names":["a"],"name":"Tom"}"#;
+        assert!(matches!(set_all(json, "users", "active", "true", None), Err(SjsonError::NoChange)));
+        assert!(matches!(set_all(json, "names", "active", "true", None), Err(SjsonError::NoChange)));
+        assert!(matches!(set_all(json, "missing", "active", "true", None), Err(SjsonError::NoChange)));
+        assert!(matches!(set_all(json, "name", "active", "true", None), Err(SjsonError::JsonMustBeObjectOrArray)));
+
+        let mut opts = Options::default();
+        opts.error_on_nochange = true;
+        let json = r#"{"users":[{"active":true},{"active":false}]}"#;
+        let result = set_all(json, "users", "active", "true", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"users":[{"active":true},{"active":true}]}"#);
+        assert!(matches!(set_all(&result, "users", "active", "true", Some(&opts)), Err(SjsonError::NoChange)));
+    }
+
+    #[test]
+    fn test_merge() {
+        let json = r#"{"a":1,"c":{"e":5},"list":[1,2]}"#;
+        let result = merge(json, ROOT_PATH, r#"{"b":2,"c":{"d":4},"list":[3]}"#, None).unwrap();
+        assert_eq!(get(&result, "a"), Some(serde_json::json!(1)));
+        assert_eq!(get(&result, "b"), Some(serde_json::json!(2)));
+        assert_eq!(get(&result, "c"), Some(serde_json::json!({"e": 5, "d": 4})));
+        // Arrays are replaced, not concatenated
+        assert_eq!(get(&result, "list"), Some(serde_json::json!([3])));
+        #[cfg(any(feature = "preserve_order", feature = "ordered"))]
+        assert_eq!(result, r#"{"a":1,"c":{"e":5,"d":4},"list":[3],"b":2}"#);
+
+        let result = merge(json, "c", r#"{"e":{"f":6}}"#, None).unwrap();
+        assert_eq!(get(&result, "c"), Some(serde_json::json!({"e": {"f": 6}})));
+
+        let result = merge(json, "x.y", r#"{"z":true}"#, None).unwrap();
+        assert_eq!(get(&result, "x"), Some(serde_json::json!({"y": {"z": true}})));
+    }
+
+    #[test]
+    fn test_merge_errors() {
+        let json = r#"{"a":1,"list":[1]}"#;
+        assert!(matches!(merge(json, "a", r#"{"b":2}"#, None), Err(SjsonError::JsonMustBeObjectOrArray)));
+        assert!(matches!(merge(json, "list", r#"{"b":2}"#, None), Err(SjsonError::JsonMustBeObjectOrArray)));
+        assert!(matches!(merge(json, ROOT_PATH, "[1]", None), Err(SjsonError::JsonMustBeObjectOrArray)));
+        assert!(matches!(merge(json, ROOT_PATH, "{", None), Err(SjsonError::InvalidJson(_))));
+        assert!(matches!(merge(json, "", "{}", None), Err(SjsonError::EmptyPath)));
+    }
+
+    #[test]
+    fn test_set_reporting() {
+        let json = r#"{"a":1,"b":{"c":[1,2]},"n":null}"#;
+        let report = |path: &str, value: &str, opts: Option<&Options>| set_reporting(json, path, value, opts).unwrap();
+
+        assert_eq!(report("z", "x", None), (r#"{"a":1,"b":{"c":[1,2]},"n":null,"z":"x"}"#.to_string(), ChangeKind::Created));
+        assert_eq!(report("b.c.2", "3", None).1, ChangeKind::Created);
+        assert_eq!(report("a", "2", None), (r#"{"a":2,"b":{"c":[1,2]},"n":null}"#.to_string(), ChangeKind::Updated));
+        assert_eq!(report("n", "0", None).1, ChangeKind::Updated);
+        assert_eq!(report("b.c.1", "3", None).1, ChangeKind::Updated);
+
+        // Unchanged hands back the input as is
+        assert_eq!(report("a", "1", None), (json.to_string(), ChangeKind::Unchanged));
+        assert_eq!(report("n", "null", None).1, ChangeKind::Unchanged);
+        let opts = Options { only_if_absent: true, error_on_nochange: true, ..Default::default() };
+        assert_eq!(report("a", "2", Some(&opts)), (json.to_string(), ChangeKind::Unchanged));
+        assert_eq!(report("e", "2", Some(&opts)).1, ChangeKind::Created);
+
+        assert_eq!(set_reporting(json, "", "1", None), Err(SjsonError::EmptyPath));
+        assert!(matches!(set_reporting("{", "a", "1", None), Err(SjsonError::InvalidJson(_))));
+    }
+
+    #[test]
+    fn test_delete_reporting() {
+        let json = r#"{"a":1,"b":{"c":[1,2]}}"#;
+        assert_eq!(delete_reporting(json, "a", None).unwrap(), (r#"{"b":{"c":[1,2]}}"#.to_string(), DeleteKind::Removed));
+        assert_eq!(delete_reporting(json, "b.c.0", None).unwrap(), (r#"{"a":1,"b":{"c":[2]}}"#.to_string(), DeleteKind::Removed));
+        assert_eq!(delete_reporting(json, "x", None).unwrap(), (json.to_string(), DeleteKind::NotFound));
+        assert_eq!(delete_reporting(json, "b.c.5", None).unwrap(), (json.to_string(), DeleteKind::NotFound));
+        assert_eq!(delete_reporting(json, "", None), Err(SjsonError::EmptyPath));
+    }
+
+    #[test]
+    fn test_set_raw_under() {
+        let fragment = r#"{"a":1,"b":{"c":2}}"#;
+        assert_eq!(set_raw_under("{}", "user", fragment, None).unwrap(), r#"{"user":{"a":1,"b":{"c":2}}}"#);
+        assert_eq!(set_raw_under("{}", "x.user", fragment, None).unwrap(), r#"{"x":{"user":{"a":1,"b":{"c":2}}}}"#);
+
+        // Existing members are replaced as a whole, others are kept
+        let json = r#"{"user":{"a":0,"b":{"d":3},"e":4}}"#;
+        assert_eq!(set_raw_under(json, "user", fragment, None).unwrap(), r#"{"user":{"a":1,"b":{"c":2},"e":4}}"#);
+        assert_eq!(set_raw_under(r#"{"0":0}"#, ROOT_PATH, fragment, None).unwrap(), r#"{"0":0,"a":1,"b":{"c":2}}"#);
+        assert_eq!(set_raw_under(r#"{"l":[0]}"#, "l.1", fragment, None).unwrap(), r#"{"l":[0,{"a":1,"b":{"c":2}}]}"#);
+
+        // Keys are literal, a separator in one doesn't nest
+        assert_eq!(set_raw_under("{}", "u", r#"{"a.b":1}"#, None).unwrap(), r#"{"u":{"a.b":1}}"#);
+
+        // Options apply to every key, the first failure fails the call
+        let opts = Options { only_if_absent: true, ..Default::default() };
+        assert_eq!(set_raw_under(json, "user", r#"{"f":5}"#, Some(&opts)).unwrap(), r#"{"user":{"a":0,"b":{"d":3},"e":4,"f":5}}"#);
+        assert_eq!(set_raw_under(json, "user", fragment, Some(&opts)), Err(SjsonError::NoChange));
+
+        assert_eq!(set_raw_under("{}", "user", "[1]", None), Err(SjsonError::JsonMustBeObjectOrArray));
+        assert_eq!(set_raw_under("{}", "", fragment, None), Err(SjsonError::EmptyPath));
+        assert!(matches!(set_raw_under("{}", "user", "{", None), Err(SjsonError::InvalidJson(_))));
+    }
+
+    #[test]
+    fn test_apply_defaults() {
+        let target = r#"{"name":"app","server":{"port":9000,"tls":{"cert":"a.pem"}},"tags":["a"],"retries":null}"#;
+        let defaults = r#"{"name":"default","server":{"host":"localhost","port":80,"tls":{"cert":"x.pem","key":"x.key"}},"tags":["x","y"],"retries":3,"debug":false}"#;
+        let result = apply_defaults(target, defaults).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&result).unwrap(),
+            serde_json::json!({
+                "name": "app",
+                "server": {"host": "localhost", "port": 9000, "tls": {"cert": "a.pem", "key": "x.key"}},
+                "tags": ["a"],
+                "retries": null,
+                "debug": false,
+            })
+        );
+
+        // A scalar in the target is kept even where the defaults have an object
+        let result = apply_defaults(r#"{"server":"off"}"#, r#"{"server":{"port":80}}"#).unwrap();
+        assert_eq!(result, r#"{"server":"off"}"#);
+        assert_eq!(apply_defaults("{}", "{}").unwrap(), "{}");
+
+        assert!(matches!(apply_defaults("[1]", "{}"), Err(SjsonError::JsonMustBeObjectOrArray)));
+        assert!(matches!(apply_defaults("{}", "42"), Err(SjsonError::JsonMustBeObjectOrArray)));
+        assert!(matches!(apply_defaults("{", "{}"), Err(SjsonError::InvalidJson(_))));
+    }
+
+    #[test]
+    fn test_presized_output_matches_serde_json() {
+        let items: Vec<String> = (0..2000).map(|i| format!(r#"{{"id":{},"label":"item {}"}}"#, i, i)).collect();
+        let json = format!(r#"{{"name":"Tom","items":[{}]}}"#, items.join(","));
+
+        let mut expected = serde_json::from_str::<Parsed>(&json).unwrap();
+        set_value_at(&mut expected, "name", parse_value("a much longer name than before", None).unwrap(), None).unwrap();
+        let result = set(&json, "name", "a much longer name than before").unwrap();
+        // Replacing a string splices it in place, unless the lenient feature
+        // sends every edit through the parser
+        if cfg!(feature = "lenient") {
+            assert_eq!(result, serde_json::to_string(&expected).unwrap());
+        } else {
+            assert_eq!(result, json.replacen(r#""Tom""#, r#""a much longer name than before""#, 1));
+        }
+
+        // Adding a key always reserializes
+        set_value_at(&mut expected, "title", parse_value("a title", None).unwrap(), None).unwrap();
+        let result = set(&result, "title", "a title").unwrap();
+        assert_eq!(result, serde_json::to_string(&expected).unwrap());
+
+        delete_value_at(&mut expected, "items", None).unwrap();
+        assert_eq!(delete(&result, "items").unwrap(), serde_json::to_string(&expected).unwrap());
+
+        let result = set_raw(&json, "name", r#"{"first":"Tom"}"#).unwrap();
+        assert_eq!(get(&result, "name.first"), Some(JsonValue::String("Tom".to_string())));
+        assert_eq!(result.len(), json.len() + r#"{"first":"Tom"}"#.len() - r#""Tom""#.len());
     }
 
     #[test]
-    fn test_negative_array_indices_delete() {
-        let json = r#"{"items":["a","b","c","d","e"]}"#;
-        
-        // Test deleting -1 (last element)
-        let result = delete(json, "items.-1").unwrap();
-        assert_eq!(result, r#"{"items":["a","b","c","d"]}"#);
-        
-        // Test deleting -2 (second to last)
-        let result = delete(json, "items.-2").unwrap();
-        assert_eq!(result, r#"{"items":["a","b","c","e"]}"#);
-        
-        // Test deleting -3 (third to last)
-        let result = delete(json, "items.-3").unwrap();
-        assert_eq!(result, r#"{"items":["a","b","d","e"]}"#);
+    fn test_empty_path_segments() {
+        let json = r#"{"a":{"b":1}}"#;
+        let mut opts = Options::default();
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            for path in ["a.", ".a", "a..b", "."] {
+                assert!(matches!(set_options(json, path, "2", Some(&opts)), Err(SjsonError::InvalidPath)), "{}", path);
+                assert!(matches!(set_raw_options(json, path, "2", Some(&opts)), Err(SjsonError::InvalidPath)), "{}", path);
+                assert!(matches!(delete_options(json, path, Some(&opts)), Err(SjsonError::InvalidPath)), "{}", path);
+            }
+        }
+
+        // The optimistic scan must not find a "" key either
+        let json = r#"{"a":{"":1}}"#;
+        assert!(matches!(set_options(json, "a.", "2", Some(&opts)), Err(SjsonError::InvalidPath)));
+        assert_eq!(get(json, "a."), None);
+
+        // Escaped dots are part of a key, not separators
+        assert_eq!(set("{}", r"a\.", "1").unwrap(), r#"{"a.":1}"#);
+        assert_eq!(set("{}", r"\..a", "1").unwrap(), r#"{".":{"a":1}}"#);
     }
 
     #[test]
-    fn test_negative_array_indices_nested() {
-        let json = r#"{"data":{"items":[{"name":"item1"},{"name":"item2"},{"name":"item3"}]}}"#;
-        
-        // Test setting in nested array with negative index
-        let result = set(json, "data.items.-1.name", "updated").unwrap();
-        assert!(result.contains("\"name\":\"updated\""));
-        
-        // Test deleting in nested array with negative index
-        let result = delete(json, "data.items.-1.name").unwrap();
-        assert!(!result.contains("\"name\":\"item3\""));
+    fn test_create_arrays_for_index_segments() {
+        assert_eq!(set("{}", "list.0", "x").unwrap(), r#"{"list":["x"]}"#);
+        assert_eq!(set("{}", "list.2", "x").unwrap(), r#"{"list":[null,null,"x"]}"#);
+        assert_eq!(set("{}", "a.0.name", "x").unwrap(), r#"{"a":[{"name":"x"}]}"#);
+        assert_eq!(set("{}", "a.1.0", "x").unwrap(), r#"{"a":[null,["x"]]}"#);
+        assert_eq!(set(r#"{"a":[]}"#, "a.1.b", "x").unwrap(), r#"{"a":[null,{"b":"x"}]}"#);
+
+        // Other segments still create objects
+        assert_eq!(set("{}", "a.007", "x").unwrap(), r#"{"a":{"007":"x"}}"#);
+        // Existing objects keep numeric keys
+        assert_eq!(set(r#"{"a":{}}"#, "a.0", "x").unwrap(), r#"{"a":{"0":"x"}}"#);
     }
 
     #[test]
-    fn test_negative_array_indices_invalid() {
-        let json = r#"{"items":["a","b"]}"#;
-        
-        // Test invalid negative index (beyond array bounds)
-        let result = set(json, "items.-3", "x");
-        assert!(result.is_err());
-        
-        let result = delete(json, "items.-3");
-        assert!(result.is_err());
+    fn test_negative_index_without_elements() {
+        let out_of_bounds = |index| Err(SjsonError::ArrayIndexOutOfBounds { index, len: 0 });
+        assert_eq!(set("{}", "a.-1", "x"), out_of_bounds(-1));
+        assert_eq!(set("{}", "a.-2.b", "x"), out_of_bounds(-2));
+        assert_eq!(set(r#"{"a":null}"#, "a.-1", "x"), out_of_bounds(-1));
+        assert_eq!(set(r#"{"a":[]}"#, "a.-1", "x"), out_of_bounds(-1));
+        assert_eq!(set(r#"{"a":["x"]}"#, "a.-1", "y").unwrap(), r#"{"a":["y"]}"#);
+        assert_eq!(set(r#"{"a":["x"]}"#, "a.-2", "y"), Err(SjsonError::ArrayIndexOutOfBounds { index: -2, len: 1 }));
+        // An existing object still takes it as a key
+        assert_eq!(set(r#"{"a":{}}"#, "a.-1", "x").unwrap(), r#"{"a":{"-1":"x"}}"#);
+
+        // A final -1 that appends creates the array
+        let opts = Options { go_compatible_append: true, ..Default::default() };
+        assert_eq!(set_options("{}", "a.-1", "x", Some(&opts)).unwrap(), r#"{"a":["x"]}"#);
+        assert_eq!(set_options(r#"{"a":null}"#, "a.-1", "x", Some(&opts)).unwrap(), r#"{"a":["x"]}"#);
+        assert_eq!(set_options(r#"{"a":[]}"#, "a.-1", "x", Some(&opts)).unwrap(), r#"{"a":["x"]}"#);
+        assert_eq!(set_options(r#"{"a":["x"]}"#, "a.-1", "y", Some(&opts)).unwrap(), r#"{"a":["x","y"]}"#);
+        assert_eq!(set_options("{}", "a.-1.b", "x", Some(&opts)), out_of_bounds(-1));
     }
 
     #[test]
-    fn test_negative_array_indices_optimistic() {
-        let json = r#"{"items":["a","b","c","d"]}"#;
+    fn test_extreme_negative_indices() {
+        let json = r#"{"items":[1,2,3]}"#;
         let mut opts = Options::default();
-        opts.optimistic = true;
-        
-        // Test optimistic mode with negative indices
-        let result = set_options(json, "items.-1", "z", Some(&opts)).unwrap();
-        assert_eq!(result, r#"{"items":["a","b","c","z"]}"#);
-        
-        let result = delete_options(json, "items.-2", Some(&opts)).unwrap();
-        assert_eq!(result, r#"{"items":["a","b","d"]}"#);
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            for index in [i64::MIN, i64::MIN + 1, -(u32::MAX as i64) - 1, -4] {
+                let out_of_bounds = Err(SjsonError::ArrayIndexOutOfBounds { index, len: 3 });
+                let path = format!("items.{}", index);
+                assert_eq!(set_options(json, &path, "x", Some(&opts)), out_of_bounds);
+                assert_eq!(set_options(json, &format!("{}.a", path), "x", Some(&opts)), out_of_bounds);
+                assert_eq!(delete_options(json, &path, Some(&opts)), out_of_bounds);
+                assert_eq!(get(json, &path), None);
+            }
+        }
+        assert_eq!(set(json, "items.-9223372036854775809", "x"), Err(SjsonError::CannotSetArrayElementForNonNumericKey("-9223372036854775809".to_string())));
+        assert_eq!(set("[]", "-9223372036854775808", "x"), Err(SjsonError::ArrayIndexOutOfBounds { index: i64::MIN, len: 0 }));
     }
 
     #[test]
-    fn test_negative_array_indices_with_large_array() {
-        let json = r#"{"items":["a","b","c","d","e","f","g","h","i","j"]}"#;
-        
-        // Test various negative indices
-        let result = set(json, "items.-1", "last").unwrap();
-        assert_eq!(result, r#"{"items":["a","b","c","d","e","f","g","h","i","last"]}"#);
-        
-        let result = set(json, "items.-5", "middle").unwrap();
-        assert_eq!(result, r#"{"items":["a","b","c","d","e","middle","g","h","i","j"]}"#);
-        
-        let result = delete(json, "items.-1").unwrap();
-        assert_eq!(result, r#"{"items":["a","b","c","d","e","f","g","h","i"]}"#);
+    fn test_create_missing() {
+        assert_eq!(set("{}", "a.b", "1").unwrap(), r#"{"a":{"b":1}}"#);
+
+        let mut opts = Options { create_missing: false, ..Default::default() };
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            let not_found = |segment: &str| Err(SjsonError::PathNotFound(segment.to_string()));
+            assert_eq!(set_options("{}", "a.b", "1", Some(&opts)), not_found("a"));
+            assert_eq!(set_raw_options(r#"{"a":{}}"#, "a.b.c", "[1]", Some(&opts)), not_found("b"));
+            assert_eq!(set_options(r#"{"a":[]}"#, "a.0.b", "1", Some(&opts)), not_found("0"));
+            assert_eq!(set_options("{}", "log.#", "1", Some(&opts)), not_found("log"));
+
+            // Only parents have to exist
+            assert_eq!(set_options(r#"{"a":{}}"#, "a.b", "1", Some(&opts)).unwrap(), r#"{"a":{"b":1}}"#);
+            assert_eq!(set_raw_options(r#"{"a":[{}]}"#, "a.0.b", "[1]", Some(&opts)).unwrap(), r#"{"a":[{"b":[1]}]}"#);
+            assert_eq!(set_options(r#"{"a":[]}"#, "a.1", "1", Some(&opts)).unwrap(), r#"{"a":[null,1]}"#);
+        }
     }
 
     #[test]
-    fn test_delete() {
-        let json = r#"{"name":"Tom","age":37}"#;
-        let result = delete(json, "age").unwrap();
-        assert_eq!(result, r#"{"name":"Tom"}"#);
+    fn test_strict_indices() {
+        let object = r#"{"a":{"0":1}}"#;
+        let array = r#"{"a":[1]}"#;
+        assert_eq!(set(object, "a.0", "2").unwrap(), r#"{"a":{"0":2}}"#);
+        assert_eq!(set(array, "a.0", "2").unwrap(), r#"{"a":[2]}"#);
+        assert_eq!(delete(object, "a.0").unwrap(), r#"{"a":{}}"#);
+        assert_eq!(delete(array, "a.0").unwrap(), r#"{"a":[]}"#);
+
+        let mut opts = Options { strict_indices: true, ..Default::default() };
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            let mismatch = Err(SjsonError::PathTypeMismatch { segment: "0".to_string(), found: "object" });
+            assert_eq!(set_raw_options(object, "a.0", "2", Some(&opts)), mismatch);
+            assert_eq!(set_raw_options(r#"{"a":{"0":{}}}"#, "a.0.b", "2", Some(&opts)), mismatch);
+            assert_eq!(delete_options(object, "a.0", Some(&opts)), mismatch);
+            assert_eq!(delete_options(r#"{"a":{"0":{"b":1}}}"#, "a.0.b", Some(&opts)), mismatch);
+
+            // Arrays and missing parents are unaffected
+            assert_eq!(set_raw_options(array, "a.0", "2", Some(&opts)).unwrap(), r#"{"a":[2]}"#);
+            assert_eq!(set_raw_options("{}", "a.1", "2", Some(&opts)).unwrap(), r#"{"a":[null,2]}"#);
+            assert_eq!(delete_options(array, "a.0", Some(&opts)).unwrap(), r#"{"a":[]}"#);
+            assert_eq!(set_raw_options(object, "a.b", "2", Some(&opts)).unwrap(), r#"{"a":{"0":1,"b":2}}"#);
+        }
     }
 
     #[test]
-    fn test_set_bool() {
-        let json = r#"{"name":"Tom"}"#;
-        let result = set_bool(json, "active", true, None).unwrap();
-        assert_eq!(result, r#"{"active":true,"name":"Tom"}"#);
+    fn test_prune_empty() {
+        let mut opts = Options { prune_empty: true, ..Default::default() };
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            let delete = |json: &str, path: &str| delete_options(json, path, Some(&opts));
+
+            assert_eq!(delete(r#"{"a":{"b":{"c":1}}}"#, "a.b.c").unwrap(), "{}");
+            assert_eq!(delete(r#"{"a":{"b":1},"z":0}"#, "a.b").unwrap(), r#"{"z":0}"#);
+            assert_eq!(delete(r#"{"a":{"b":{"c":1},"d":2}}"#, "a.b.c").unwrap(), r#"{"a":{"d":2}}"#);
+            assert_eq!(delete(r#"{"a":{"b":{"c":1,"e":3}}}"#, "a.b.c").unwrap(), r#"{"a":{"b":{"e":3}}}"#);
+
+            // Arrays are pruned the same way
+            assert_eq!(delete(r#"{"a":[{"b":[1]}],"z":0}"#, "a.0.b.0").unwrap(), r#"{"z":0}"#);
+            assert_eq!(delete(r#"{"a":[{"b":[1]},2]}"#, "a.0.b.-1").unwrap(), r#"{"a":[2]}"#);
+            assert_eq!(delete(r#"[[{"a":1}],3]"#, "0.0.a").unwrap(), "[3]");
+
+            // The root stays, and a missing target prunes nothing
+            assert_eq!(delete(r#"{"a":1}"#, "a").unwrap(), "{}");
+            assert_eq!(delete("[[1]]", "0.0").unwrap(), "[]");
+            assert_eq!(delete(r#"{"a":{"b":1}}"#, "a.c"), Err(SjsonError::NoChange));
+            assert_eq!(delete(r#"{"a":[1]}"#, "a.1"), Err(SjsonError::NoChange));
+        }
+
+        // The fast path removes the outermost emptied parent in one splice
+        #[cfg(not(feature = "lenient"))]
+        {
+            let opts = Options { prune_empty: true, preserve_untouched: true, ..Default::default() };
+            let result = delete_options(r#"{"z": 1.0, "a": {"b": [ {"c": 1} ]}}"#, "a.b.0.c", Some(&opts)).unwrap();
+            assert_eq!(result, r#"{"z": 1.0}"#);
+            let result = delete_options(r#"{"a": {"b": [ {"c": 1}, 2 ]}}"#, "a.b.0.c", Some(&opts)).unwrap();
+            assert_eq!(result, r#"{"a": {"b": [ 2 ]}}"#);
+        }
+
+        // Containers that were already empty are kept
+        let result = delete_options(r#"{"a":{"b":{}},"c":{"d":1}}"#, "c.d", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"a":{"b":{}}}"#);
+        // Without the flag the empty parents stay
+        assert_eq!(delete(r#"{"a":{"b":{"c":1}}}"#, "a.b.c").unwrap(), r#"{"a":{"b":{}}}"#);
+
+        // Trimming nulls can leave an array empty, which is pruned as well
+        opts.trim_trailing_nulls = true;
+        assert_eq!(delete_options(r#"{"a":{"b":[null,1]}}"#, "a.b.1", Some(&opts)).unwrap(), "{}");
     }
 
     #[test]
-    fn test_set_int() {
-        let json = r#"{"name":"Tom"}"#;
-        let result = set_int(json, "age", 37, None).unwrap();
-        assert_eq!(result, r#"{"age":37,"name":"Tom"}"#);
+    fn test_slice() {
+        let json = r#"{"items":[0,1,2,3]}"#;
+        assert_eq!(set_raw(json, "items.1:3", "[9,9]").unwrap(), r#"{"items":[0,9,9,3]}"#);
+        assert_eq!(set_raw(json, "items.1:3", "[9]").unwrap(), r#"{"items":[0,9,3]}"#);
+        assert_eq!(set_raw(json, "items.1:1", "[9,9]").unwrap(), r#"{"items":[0,9,9,1,2,3]}"#);
+        assert_eq!(set_raw(json, "items.0:4", "[]").unwrap(), r#"{"items":[]}"#);
+        assert_eq!(delete(json, "items.1:3").unwrap(), r#"{"items":[0,3]}"#);
+
+        // An end past the last element is clamped to the length
+        assert_eq!(set_raw(json, "items.2:99", "[9]").unwrap(), r#"{"items":[0,1,9]}"#);
+        assert_eq!(set_raw(json, "items.7:9", "[9]").unwrap(), r#"{"items":[0,1,2,3,9]}"#);
+        assert_eq!(delete(json, "items.2:99").unwrap(), r#"{"items":[0,1]}"#);
+        assert_eq!(delete(json, "items.4:9"), Err(SjsonError::NoChange));
+        assert_eq!(set_raw("{}", "items.0:2", "[1,2]").unwrap(), r#"{"items":[1,2]}"#);
+
+        // The value must be an array, and the start can't be after the end
+        assert_eq!(set_raw(json, "items.1:3", "9"), Err(SjsonError::SliceValueMustBeArray));
+        assert_eq!(set(json, "items.1:3", "nine"), Err(SjsonError::SliceValueMustBeArray));
+        assert_eq!(set(json, "items.1:3", "[9,9]").unwrap(), r#"{"items":[0,9,9,3]}"#);
+        assert_eq!(set_raw(json, "items.3:1", "[9]"), Err(SjsonError::InvalidPath));
+        assert_eq!(delete(json, "items.3:1"), Err(SjsonError::InvalidPath));
+
+        // The replaced elements are returned as an array
+        let (result, old) = set_with_old(json, "items.0:2", "[5]", None).unwrap();
+        assert_eq!(result, r#"{"items":[5,2,3]}"#);
+        assert_eq!(old, Some(serde_json::json!([0, 1])));
+
+        // Only the last segment is a slice, elsewhere and on objects it is a key
+        let json = r#"{"1:2":{"a":1},"x":{"1:2":3}}"#;
+        assert_eq!(set(json, "1:2.a", "2").unwrap(), r#"{"1:2":{"a":2},"x":{"1:2":3}}"#);
+        assert_eq!(delete(json, "x.1:2").unwrap(), r#"{"1:2":{"a":1},"x":{}}"#);
+        assert_eq!(Path::parse("a.1:2").unwrap().segments()[1], Segment::Slice { start: 1, end: 2 });
+        assert_eq!(Path::parse("1:2.a").unwrap().segments()[0], Segment::Key("1:2".to_string()));
+        assert_eq!(Path::parse("a.01:2").unwrap().segments()[1], Segment::Key("01:2".to_string()));
+        assert_eq!(Segment::Slice { start: 1, end: 2 }.to_string(), "1:2");
     }
 
     #[test]
-    fn test_empty_path() {
-        let json = r#"{"name":"Tom"}"#;
-        let result = set(json, "", "value");
-        assert!(result.is_err());
+    #[cfg(all(feature = "std", not(feature = "lenient")))]
+    fn test_on_fallback() {
+        std::thread_local! {
+            static REASONS: core::cell::RefCell<Vec<String>> = const { core::cell::RefCell::new(Vec::new()) };
+        }
+        fn record(reason: &str) {
+            REASONS.with(|reasons| reasons.borrow_mut().push(reason.to_string()));
+        }
+        let reasons = || REASONS.with(|reasons| reasons.take());
+
+        let json = r#"{"a":{"b":1},"list":[1,2],"s":"x"}"#;
+        let opts = Options { optimistic: true, on_fallback: Some(record), ..Default::default() };
+
+        // Edits the fast path makes report nothing
+        set_options(json, "a.b", "2", Some(&opts)).unwrap();
+        set_options(json, "a.c", "2", Some(&opts)).unwrap();
+        delete_options(json, "list.0", Some(&opts)).unwrap();
+        assert!(reasons().is_empty());
+
+        let result = set_options(json, "a.x.y", "2", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"a":{"b":1,"x":{"y":2}},"list":[1,2],"s":"x"}"#);
+        set_options(json, "list.5", "2", Some(&opts)).unwrap();
+        set_options(json, "s.t", "2", Some(&opts)).unwrap();
+        assert_eq!(delete_options(json, "a.c", Some(&opts)), Err(SjsonError::NoChange));
+        set_options(json, "a\\.b", "2", Some(&opts)).unwrap();
+        set_options("\u{feff}{}", "a", "2", Some(&opts)).unwrap();
+        assert_eq!(reasons(), [
+            "key not found at segment 2",
+            "element not found at segment 2",
+            "segment 2 steps into a value that isn't an object or array",
+            "key not found at segment 2",
+            "path has escapes or characters the byte scan doesn't handle",
+            "document starts with a byte order mark",
+        ]);
+
+        let pretty = Options { compact: false, ..opts.clone() };
+        set_options(json, "a.b", "2", Some(&pretty)).unwrap();
+        set_bytes_options(json.as_bytes(), "a.x.y", "2", Some(&opts)).unwrap();
+        assert_eq!(reasons(), ["pretty printing needs the parser", "key not found at segment 2"]);
+
+        // Without optimistic, or with preserve_untouched, nothing falls back
+        let quiet = Options { optimistic: false, ..opts.clone() };
+        set_options(json, "a.x.y", "2", Some(&quiet)).unwrap();
+        let preserving = Options { preserve_untouched: true, ..opts.clone() };
+        assert_eq!(set_options(json, "a.x.y", "2", Some(&preserving)), Err(SjsonError::SpliceUnavailable));
+        assert!(reasons().is_empty());
     }
 
     #[test]
-    fn test_optimistic_set() {
-        let json = r#"{"name":"Tom","age":37}"#;
+    fn test_trim_trailing_nulls() {
         let mut opts = Options::default();
-        opts.optimistic = true;
-        let result = set_options(json, "name", "Jerry", Some(&opts)).unwrap();
-        assert_eq!(result, r#"{"name":"Jerry","age":37}"#);
+        opts.trim_trailing_nulls = true;
+
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            let delete = |json: &str, path: &str| delete_options(json, path, Some(&opts)).unwrap();
+
+            assert_eq!(delete(r#"{"items":[null,null,"f"]}"#, "items.2"), r#"{"items":[]}"#);
+            assert_eq!(delete(r#"{"items":[1,null,null,"f"]}"#, "items.-1"), r#"{"items":[1]}"#);
+            assert_eq!(delete(r#"{"items":[1,null,2,null,"f"]}"#, "items.4"), r#"{"items":[1,null,2]}"#);
+            // Nulls before the last remaining value are real contents
+            assert_eq!(delete(r#"{"items":[null,"a",null,"b"]}"#, "items.1"), r#"{"items":[null,null,"b"]}"#);
+            assert_eq!(delete(r#"{"items":["a",null,"b",null]}"#, "items.0"), r#"{"items":[null,"b"]}"#);
+            assert_eq!(delete(r#"{"items":["a","b"],"n":null}"#, "n"), r#"{"items":["a","b"]}"#);
+        }
+
+        // Without the flag the padding stays
+        let json = set("{}", "items.2", "f").unwrap();
+        assert_eq!(delete(&json, "items.2").unwrap(), r#"{"items":[null,null]}"#);
     }
 
     #[test]
-    fn test_optimistic_delete() {
-        let json = r#"{"name":"Tom","age":37}"#;
-        let mut opts = Options::default();
-        opts.optimistic = true;
-        let result = delete_options(json, "age", Some(&opts)).unwrap();
-        // For now, just check that it doesn't panic and produces valid JSON
-        assert!(result.contains("\"name\":\"Tom\""));
-        assert!(!result.contains("\"age\":37"));
+    fn test_custom_separator() {
+        assert_eq!(Options::default().separator, '.');
+
+        let json = r#"{"a.b":{"c":1},"list":[1,2,3]}"#;
+        let mut opts = Options { separator: '/', ..Default::default() };
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            assert_eq!(set_options(json, "a.b/c", "2", Some(&opts)).unwrap(), r#"{"a.b":{"c":2},"list":[1,2,3]}"#);
+            assert_eq!(set_raw_options(json, "a.b/c", "[0]", Some(&opts)).unwrap(), r#"{"a.b":{"c":[0]},"list":[1,2,3]}"#);
+            assert_eq!(delete_options(json, "a.b/c", Some(&opts)).unwrap(), r#"{"a.b":{},"list":[1,2,3]}"#);
+            assert_eq!(delete_options(json, "list/1", Some(&opts)).unwrap(), r#"{"a.b":{"c":1},"list":[1,3]}"#);
+            assert!(matches!(set_options(json, "a.b/", "2", Some(&opts)), Err(SjsonError::InvalidPath)));
+        }
+
+        // The separator can be escaped, a dot needs no escape
+        assert_eq!(set_options("{}", r"x\/y/z", "1", Some(&opts)).unwrap(), r#"{"x/y":{"z":1}}"#);
+        assert_eq!(split_path(r"a.b/c\/d", '/').unwrap(), vec!["a.b", "c/d"]);
+
+        // Appending honours the separator too
+        opts.go_compatible_append = true;
+        assert_eq!(set_options(json, "list/-1", "4", Some(&opts)).unwrap(), r#"{"a.b":{"c":1},"list":[1,2,3,4]}"#);
+
+        // The default still splits on dots
+        assert_eq!(set(json, "list.0", "0").unwrap(), r#"{"a.b":{"c":1},"list":[0,2,3]}"#);
+
+        // A separator that means something else in a path is refused
+        for separator in ['\\', '#', '*', ':', '@', '$', '(', ')', '='] {
+            for optimistic in [false, true] {
+                let opts = Options { separator, optimistic, ..Default::default() };
+                let path = format!("a{}b", separator);
+                assert_eq!(set_options(json, &path, "1", Some(&opts)), Err(SjsonError::InvalidPath), "{}", separator);
+                assert_eq!(set_raw_options(json, &path, "1", Some(&opts)), Err(SjsonError::InvalidPath));
+                assert_eq!(delete_options(json, &path, Some(&opts)), Err(SjsonError::InvalidPath));
+                assert_eq!(set_options(json, "list", "1", Some(&opts)), Err(SjsonError::InvalidPath));
+            }
+        }
     }
 
     #[test]
-    fn test_options_without_optimistic() {
-        let json = r#"{"name":"Tom","age":37}"#;
-        let opts = Options::default(); // optimistic = false
-        let result = set_options(json, "name", "Jerry", Some(&opts)).unwrap();
-        assert_eq!(result, r#"{"age":37,"name":"Jerry"}"#);
+    fn test_set_raw_if_changed() {
+        let json = r#"{"spec":{"replicas":3,"ports":[80,443]},"name":"api"}"#;
+        let mut opts = Options::default();
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            let set = |path, raw| set_raw_if_changed(json, path, raw, Some(&opts));
+
+            assert_eq!(set("spec", r#"{"replicas":3,"ports":[80,443]}"#), Ok(None));
+            assert_eq!(set("spec", r#"{ "ports": [80, 443], "replicas": 3 }"#), Ok(None));
+            assert_eq!(set("name", r#""api""#), Ok(None));
+
+            let result = set("spec", r#"{"replicas":4,"ports":[80,443]}"#).unwrap().unwrap();
+            assert_eq!(get(&result, "spec.replicas"), Some(serde_json::json!(4)));
+            // Array order matters
+            assert!(set("spec.ports", "[443,80]").unwrap().is_some());
+            assert!(set("spec.image", r#""nginx""#).unwrap().is_some());
+            assert!(matches!(set("spec", "{"), Err(SjsonError::InvalidJson(_))));
+        }
+
+        let opts = Options { error_on_nochange: true, ..Default::default() };
+        assert_eq!(set_raw_if_changed(json, "name", r#""api""#, Some(&opts)), Err(SjsonError::NoChange));
     }
 
     #[test]
-    fn test_options_none() {
-        let json = r#"{"name":"Tom","age":37}"#;
-        let result = set_options(json, "name", "Jerry", None).unwrap();
-        assert_eq!(result, r#"{"age":37,"name":"Jerry"}"#);
+    fn test_set_and_delete_with_old() {
+        let json = r#"{"name":{"first":"Tom"},"list":[1,2]}"#;
+
+        // Overwriting returns the previous value
+        let (result, old) = set_with_old(json, "name.first", "Sara", None).unwrap();
+        assert_eq!(result, set(json, "name.first", "Sara").unwrap());
+        assert_eq!(old, Some(serde_json::json!("Tom")));
+        let (_, old) = set_with_old(json, "list.1", "3", None).unwrap();
+        assert_eq!(old, Some(serde_json::json!(2)));
+        let (_, old) = set_with_old(json, ROOT_PATH, "{}", None).unwrap();
+        assert_eq!(old, Some(serde_json::from_str::<JsonValue>(json).unwrap()));
+
+        // Creating returns None
+        let (result, old) = set_with_old(json, "name.last", "Anderson", None).unwrap();
+        assert_eq!(result, set(json, "name.last", "Anderson").unwrap());
+        assert_eq!(old, None);
+        let (_, old) = set_with_old(json, "list.5", "6", None).unwrap();
+        assert_eq!(old, None);
+        let optimistic = Options { optimistic: true, ..Default::default() };
+        let (result, old) = set_with_old(json, "name.last", "Anderson", Some(&optimistic)).unwrap();
+        assert_eq!(result, set_options(json, "name.last", "Anderson", Some(&optimistic)).unwrap());
+        assert_eq!(old, None);
+
+        // Deleting returns the removed value
+        let (result, old) = delete_with_old(json, "name", None).unwrap();
+        assert_eq!(result, delete(json, "name").unwrap());
+        assert_eq!(old, Some(serde_json::json!({"first": "Tom"})));
+        let (result, old) = delete_with_old(json, "list.-1", None).unwrap();
+        assert_eq!(result, delete(json, "list.-1").unwrap());
+        assert_eq!(old, Some(serde_json::json!(2)));
+        assert!(matches!(delete_with_old(json, "missing", None), Err(SjsonError::NoChange)));
+
+        // A null set with treat_null_as_delete reports the removed value
+        let opts = Options { treat_null_as_delete: true, ..Default::default() };
+        let (_, old) = set_with_old(json, "list.0", "null", Some(&opts)).unwrap();
+        assert_eq!(old, Some(serde_json::json!(1)));
     }
 
     #[test]
-    fn test_optimistic_nested_set() {
-        let json = r#"{"user":{"name":"Tom","age":37}}"#;
+    fn test_set_located() {
+        let json = r#"{"a":{"b":1},"list":[1,2],"name":"Tom"}"#;
+        let cases = [
+            ("a.b", "2", "2"),
+            ("a.c", "x", r#""x""#),
+            ("name", "Tom", r#""Tom""#),
+            ("list.1", "null", "null"),
+            ("list.#", "3", "3"),
+            ("list.-1", "[1,{}]", "[1,{}]"),
+            ("new.0.deep", "true", "true"),
+            ("a", "{}", "{}"),
+            ("@this", "5", "5"),
+        ];
         let mut opts = Options::default();
-        opts.optimistic = true;
-        let result = set_options(json, "user.name", "Jerry", Some(&opts)).unwrap();
-        // Check that the result contains the expected values, regardless of field order
-        assert!(result.contains("\"user\""));
-        assert!(result.contains("\"name\":\"Jerry\""));
-        assert!(result.contains("\"age\":37"));
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            for (path, value, text) in cases {
+                let (result, range) = set_located(json, path, value, Some(&opts)).unwrap();
+                assert_eq!(result, set_options(json, path, value, Some(&opts)).unwrap());
+                assert_eq!(&result[range], text, "{} = {}", path, value);
+            }
+        }
+
+        // Pretty printed containers are located with their indentation
+        #[cfg(feature = "std")]
+        {
+            let opts = Options { pretty: Some(PrettyOptions::default()), ..Default::default() };
+            let (result, range) = set_located(json, "a.b", r#"{"c":[1]}"#, Some(&opts)).unwrap();
+            assert!(result[range.clone()].starts_with('{') && result[range.clone()].ends_with('}'));
+            assert_eq!(serde_json::from_str::<JsonValue>(&result[range]).unwrap(), serde_json::json!({"c": [1]}));
+        }
+
+        // The default splice keeps the key order like set does
+        let unsorted = r#"{"z":1,"a":"x"}"#;
+        let (result, range) = set_located(unsorted, "z", "2", None).unwrap();
+        assert_eq!(result, set(unsorted, "z", "2").unwrap());
+        assert_eq!(&result[range], "2");
+
+        // The marker may equal the value it replaces, also when that is
+        // an error for the edit itself
+        let opts = Options { error_on_nochange: true, ..Default::default() };
+        let (result, range) = set_located(r#"{"a.b":null}"#, r"a\.b", "5", Some(&opts)).unwrap();
+        assert_eq!(result, r#"{"a.b":5}"#);
+        assert_eq!(&result[range], "5");
+
+        assert_eq!(set_located(json, "", "1", None), Err(SjsonError::EmptyPath));
+        assert_eq!(set_located(json, "list.*", "1", None), Err(SjsonError::InvalidPath));
     }
 
     #[test]
-    fn test_optimistic_array_set() {
-        let json = r#"{"items":["a","b","c"]}"#;
+    fn test_duplicate_keys() {
+        let json = r#"{"a":1,"b":0,"a":2}"#;
         let mut opts = Options::default();
-        opts.optimistic = true;
-        let result = set_options(json, "items.1", "x", Some(&opts)).unwrap();
-        assert_eq!(result, r#"{"items":["a","x","c"]}"#);
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+
+            // Editing the repeated key always goes through the parser
+            for policy in [DuplicateKeys::KeepLast, DuplicateKeys::KeepFirst] {
+                opts.on_duplicate_key = policy;
+                assert_eq!(set_options(json, "a", "3", Some(&opts)).unwrap(), r#"{"a":3,"b":0}"#);
+                assert_eq!(delete_options(json, "a", Some(&opts)).unwrap(), r#"{"b":0}"#);
+            }
+
+            opts.on_duplicate_key = DuplicateKeys::Error;
+            let duplicate = Err(SjsonError::DuplicateKey("a".to_string()));
+            assert_eq!(set_options(json, "a", "3", Some(&opts)), duplicate);
+            assert_eq!(set_options(json, "b", "3", Some(&opts)), duplicate);
+            assert_eq!(delete_options(json, "b", Some(&opts)), duplicate);
+            let nested = r#"{"x":{"c":1,"c":2},"y":1}"#;
+            assert_eq!(set_options(nested, "y", "2", Some(&opts)), Err(SjsonError::DuplicateKey("c".to_string())));
+        }
+
+        // Other edits keep the member the policy picks
+        let opts = Options::default();
+        assert_eq!(set_options(json, "b", "3", Some(&opts)).unwrap(), r#"{"a":2,"b":3}"#);
+        let opts = Options { on_duplicate_key: DuplicateKeys::KeepFirst, ..Default::default() };
+        assert_eq!(set_options(json, "b", "3", Some(&opts)).unwrap(), r#"{"a":1,"b":3}"#);
+        assert_eq!(set_options(r#"[{"k":[1],"k":[2]}]"#, "0.m", "3", Some(&opts)).unwrap(), r#"[{"k":[1],"m":3}]"#);
+
+        // The fast path splices around repeats it doesn't touch
+        #[cfg(not(feature = "lenient"))]
+        {
+            let opts = Options { optimistic: true, ..Default::default() };
+            assert_eq!(set_options(json, "b", "3", Some(&opts)).unwrap(), r#"{"a":1,"b":3,"a":2}"#);
+            let opts = Options { preserve_untouched: true, on_duplicate_key: DuplicateKeys::Error, ..Default::default() };
+            assert_eq!(set_options(json, "b", "3", Some(&opts)), Err(SjsonError::DuplicateKey("a".to_string())));
+        }
     }
 
+    #[cfg(not(feature = "lenient"))]
     #[test]
-    fn test_optimistic_set_raw() {
-        let json = r#"{"data":{"name":"Tom"}}"#;
-        let mut opts = Options::default();
-        opts.optimistic = true;
-        let complex_value = r#"{"city":"Beijing","country":"China"}"#;
-        let result = set_raw_options(json, "data.address", complex_value, Some(&opts)).unwrap();
-        assert_eq!(result, r#"{"data":{"address":{"city":"Beijing","country":"China"},"name":"Tom"}}"#);
+    fn test_verify_optimistic_result() {
+        let mut opts = Options { optimistic: true, ..Default::default() };
+
+        // Malformed json that the byte scan happily splices into
+        let json = r#"{"a":1,"b":tru}"#;
+        assert!(set_options(json, "a", "2", Some(&opts)).is_ok());
+        assert!(delete_bytes_options(json.as_bytes(), "a", Some(&opts)).is_ok());
+
+        opts.verify = true;
+        assert!(matches!(set_options(json, "a", "2", Some(&opts)), Err(SjsonError::InvalidJson(_))));
+        assert!(matches!(set_raw_options(json, "a", "2", Some(&opts)), Err(SjsonError::InvalidJson(_))));
+        assert!(matches!(delete_options(json, "a", Some(&opts)), Err(SjsonError::InvalidJson(_))));
+        assert!(matches!(delete_bytes_options(json.as_bytes(), "a", Some(&opts)), Err(SjsonError::InvalidJson(_))));
+
+        // A quote in a string value falls back to the parser's escaping
+        let json = r#"{"a":1}"#;
+        assert_eq!(set_options(json, "a", r#"say "hi""#, Some(&opts)).unwrap(), set(json, "a", r#"say "hi""#).unwrap());
+
+        // A raw value that isn't json is caught too
+        assert!(matches!(set_raw_options(json, "a", "{", Some(&opts)), Err(SjsonError::InvalidJson(_))));
+
+        // Valid results still come from the fast path
+        assert_eq!(set_options(json, "a", "2", Some(&opts)).unwrap(), r#"{"a":2}"#);
+        let mut buf = String::from(json);
+        opts.replace_in_place = true;
+        set_in_place(&mut buf, "a", "3", Some(&opts)).unwrap();
+        assert_eq!(buf, r#"{"a":3}"#);
     }
 
+    #[cfg(not(feature = "lenient"))]
     #[test]
-    fn test_optimistic_set_bool() {
-        let json = r#"{"user":{"name":"Tom"}}"#;
-        let mut opts = Options::default();
-        opts.optimistic = true;
-        let result = set_bool(json, "user.active", true, Some(&opts)).unwrap();
-        assert_eq!(result, r#"{"user":{"active":true,"name":"Tom"}}"#);
+    fn test_assume_valid_raw() {
+        let json = r#"{"a":1,"b":2}"#;
+        let mut opts = Options { optimistic: true, ..Default::default() };
+        assert!(matches!(set_raw_options(json, "a", "{oops", Some(&opts)), Err(SjsonError::InvalidJson(_))));
+
+        // The fragment is spliced verbatim, valid or not
+        opts.assume_valid_raw = true;
+        assert_eq!(set_raw_options(json, "a", r#"{"x": [1, 2]}"#, Some(&opts)).unwrap(), r#"{"a":{"x": [1, 2]},"b":2}"#);
+        assert_eq!(set_raw_options(json, "a", "{oops", Some(&opts)).unwrap(), r#"{"a":{oops,"b":2}"#);
+        assert_eq!(set_raw_bytes_options(json.as_bytes(), "c", "{oops", Some(&opts)).unwrap(), br#"{"a":1,"b":2,"c":{oops}"#);
+
+        // verify still catches it, and the parser path always parses the value
+        opts.verify = true;
+        assert!(matches!(set_raw_options(json, "a", "{oops", Some(&opts)), Err(SjsonError::InvalidJson(_))));
+        let opts = Options { assume_valid_raw: true, ..Default::default() };
+        assert!(matches!(set_raw_options(json, "a", "{oops", Some(&opts)), Err(SjsonError::InvalidJson(_))));
     }
 
     #[test]
-    fn test_optimistic_set_int() {
-        let json = r#"{"user":{"name":"Tom"}}"#;
+    fn test_optimistic_escapes_strings() {
+        let json = r#"{"a":"x","b":1}"#;
+        let opts = Options { optimistic: true, ..Default::default() };
+        for value in ["back\u{0008}space", "form\u{000C}feed", "ctl\u{0001}", r#"say "hi""#, r#"""#, r"back\slash", "\"quoted\""] {
+            let result = set_options(json, "a", value, Some(&opts)).unwrap();
+            assert_eq!(result, set(json, "a", value).unwrap(), "{:?}", value);
+            assert_eq!(get(&result, "a"), Some(JsonValue::String(value.to_string())), "{:?}", value);
+        }
+        assert_eq!(set_options(json, "a", "ctl\u{0001}", Some(&opts)).unwrap(), r#"{"a":"ctl\u0001","b":1}"#);
+
+        // Text in brackets is only spliced as is when it is valid json
+        for value in ["[foo]", "{a}", "[1,]", r#"{"k":}"#, "[1]x]", "[1,2]", r#"{"k":[1]}"#] {
+            let result = set_options(json, "a", value, Some(&opts)).unwrap();
+            assert_eq!(result, set(json, "a", value).unwrap(), "{:?}", value);
+        }
+        assert_eq!(set_options(json, "a", "[foo]", Some(&opts)).unwrap(), r#"{"a":"[foo]","b":1}"#);
+    }
+
+    #[test]
+    fn test_set_raw_scalars() {
+        let json = r#"{"a":1}"#;
         let mut opts = Options::default();
-        opts.optimistic = true;
-        let result = set_int(json, "user.age", 25, Some(&opts)).unwrap();
-        assert_eq!(result, r#"{"user":{"age":25,"name":"Tom"}}"#);
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            for path in ["a", "b"] {
+                let result = set_raw_options(json, path, r#""hi""#, Some(&opts)).unwrap();
+                assert_eq!(get(&result, path), Some(JsonValue::String("hi".to_string())));
+                let result = set_raw_options(json, path, "true", Some(&opts)).unwrap();
+                assert_eq!(get(&result, path), Some(JsonValue::Bool(true)));
+                let result = set_raw_options(json, path, "42", Some(&opts)).unwrap();
+                assert_eq!(get(&result, path), Some(serde_json::json!(42)));
+                if path == "a" {
+                    assert_eq!(set_raw_options(json, path, r#""hi""#, Some(&opts)).unwrap(), r#"{"a":"hi"}"#);
+                }
+                assert_eq!(set_raw_bytes_options(json.as_bytes(), path, "null", Some(&opts)).unwrap(),
+                    set_raw_options(json, path, "null", Some(&opts)).unwrap().into_bytes());
+
+                // Bare text isn't a json token
+                assert!(matches!(set_raw_options(json, path, "hi", Some(&opts)), Err(SjsonError::InvalidJson(_))));
+                assert!(matches!(set_raw_options(json, path, r#""unterminated"#, Some(&opts)), Err(SjsonError::InvalidJson(_))));
+                assert!(matches!(set_raw_bytes_options(json.as_bytes(), path, "1 2", Some(&opts)), Err(SjsonError::InvalidJson(_))));
+            }
+        }
     }
 
     #[test]
-    fn test_optimistic_set_float() {
-        let json = r#"{"user":{"name":"Tom"}}"#;
+    fn test_delete_negative_intermediate_index() {
+        let json = r#"{"a":[{"b":1,"c":1},{"b":2,"c":2},{"b":3,"c":3}]}"#;
         let mut opts = Options::default();
-        opts.optimistic = true;
-        let result = set_float(json, "user.score", 95.5, Some(&opts)).unwrap();
-        assert_eq!(result, r#"{"user":{"name":"Tom","score":95.5}}"#);
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            assert_eq!(delete_options(json, "a.-1.b", Some(&opts)).unwrap(), r#"{"a":[{"b":1,"c":1},{"b":2,"c":2},{"c":3}]}"#);
+            assert_eq!(delete_options(json, "a.-2.b", Some(&opts)).unwrap(), r#"{"a":[{"b":1,"c":1},{"c":2},{"b":3,"c":3}]}"#);
+            assert_eq!(delete_options(json, "a.-3.c", Some(&opts)).unwrap(), r#"{"a":[{"b":1},{"b":2,"c":2},{"b":3,"c":3}]}"#);
+            assert!(matches!(delete_options(json, "a.-4.b", Some(&opts)), Err(SjsonError::ArrayIndexOutOfBounds { index: -4, len: 3 })));
+
+            // Each delete resolves the index against the array as it is then
+            let shortened = delete_options(json, "a.-1", Some(&opts)).unwrap();
+            assert_eq!(delete_options(&shortened, "a.-1.b", Some(&opts)).unwrap(), r#"{"a":[{"b":1,"c":1},{"c":2}]}"#);
+
+            // An element key named like the index must not be matched instead
+            let json = r#"{"a":[{"b":1},{"-1":{"b":2}}]}"#;
+            assert!(matches!(delete_options(json, "a.-1.b", Some(&opts)), Err(SjsonError::NoChange)));
+            assert_eq!(delete_options(json, "a.1.-1.b", Some(&opts)).unwrap(), r#"{"a":[{"b":1},{"-1":{}}]}"#);
+        }
     }
 
+    #[cfg(feature = "std")]
     #[test]
-    fn test_optimistic_set_value() {
-        let json = r#"{"user":{"name":"Tom"}}"#;
+    fn test_to_writer() {
+        let json = r#"{"name":"Tom","tags":["a","b"]}"#;
         let mut opts = Options::default();
-        opts.optimistic = true;
-        
-        #[derive(serde::Serialize)]
-        struct Address {
-            city: String,
-            country: String,
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+
+            let mut out = Vec::new();
+            set_to_writer(&mut out, json, "name", "Jerry", Some(&opts)).unwrap();
+            assert_eq!(out, set_options(json, "name", "Jerry", Some(&opts)).unwrap().into_bytes());
+
+            let mut out = Vec::new();
+            set_to_writer(&mut out, json, "age", "37", Some(&opts)).unwrap();
+            assert_eq!(out, set_options(json, "age", "37", Some(&opts)).unwrap().into_bytes());
+
+            let mut out = Vec::new();
+            set_raw_to_writer(&mut out, json, "tags", r#"["c"]"#, Some(&opts)).unwrap();
+            assert_eq!(out, set_raw_options(json, "tags", r#"["c"]"#, Some(&opts)).unwrap().into_bytes());
+
+            let mut out = Vec::new();
+            delete_to_writer(&mut out, json, "tags.0", Some(&opts)).unwrap();
+            assert_eq!(out, delete_options(json, "tags.0", Some(&opts)).unwrap().into_bytes());
+
+            let mut out = Vec::new();
+            assert!(matches!(delete_to_writer(&mut out, json, "missing", Some(&opts)), Err(SjsonError::NoChange)));
+            assert!(out.is_empty());
+        }
+
+        opts.pretty = Some(PrettyOptions::default());
+        let mut out = Vec::new();
+        set_to_writer(&mut out, json, "name", "Jerry", Some(&opts)).unwrap();
+        assert_eq!(out, set_options(json, "name", "Jerry", Some(&opts)).unwrap().into_bytes());
+
+        // A failing writer is reported as an io error
+        struct Broken;
+        impl std::io::Write for Broken {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("broken"))
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        for optimistic in [false, true] {
+            let opts = Options { optimistic, ..Default::default() };
+            assert!(matches!(set_to_writer(&mut Broken, json, "name", "Jerry", Some(&opts)), Err(SjsonError::Io(_))));
         }
-        
-        let address = Address {
-            city: "Beijing".to_string(),
-            country: "China".to_string(),
-        };
-        
-        let result = set_value(json, "user.address", &address, Some(&opts)).unwrap();
-        assert_eq!(result, r#"{"user":{"address":{"city":"Beijing","country":"China"},"name":"Tom"}}"#);
     }
 
     #[test]
-    fn test_options_clone() {
-        let mut opts1 = Options::default();
-        opts1.optimistic = true;
-        let opts2 = opts1.clone();
-        assert_eq!(opts1.optimistic, opts2.optimistic);
+    fn test_match_segments() {
+        let json = r#"{"friends":[{"first":"Dale","last":"Murphy","age":44},{"first":"Roger","last":"Craig","age":68},{"first":"Jane","last":"Murphy","age":47}]}"#;
+
+        // The first element matching a string
+        let result = set(json, "friends.#(last=Murphy).first", "Jimmy").unwrap();
+        assert_eq!(get(&result, "friends.0.first"), Some(serde_json::json!("Jimmy")));
+        assert_eq!(get(&result, "friends.2.first"), Some(serde_json::json!("Jane")));
+        assert_eq!(get(json, r#"friends.#(last="Craig").first"#), Some(serde_json::json!("Roger")));
+
+        // A numeric match
+        let result = set(json, "friends.#(age=47).first", "Janet").unwrap();
+        assert_eq!(get(&result, "friends.2.first"), Some(serde_json::json!("Janet")));
+        assert_eq!(get(json, r#"friends.#(age="47")"#), None);
+
+        // Deleting through and at a match
+        let result = delete(json, "friends.#(first=Roger).age").unwrap();
+        assert_eq!(get(&result, "friends.1"), Some(serde_json::json!({"first":"Roger","last":"Craig"})));
+        let result = delete(json, "friends.#(first=Roger)").unwrap();
+        assert_eq!(get(&result, "friends.1.first"), Some(serde_json::json!("Jane")));
+
+        // No match changes nothing
+        assert!(matches!(set(json, "friends.#(last=Smith).first", "x"), Err(SjsonError::NoChange)));
+        assert!(matches!(delete(json, "friends.#(last=Smith)"), Err(SjsonError::NoChange)));
+        assert!(matches!(set(json, "#(last=Murphy)", "x"), Err(SjsonError::NoChange)));
+        let opts = Options { optimistic: true, ..Default::default() };
+        assert!(matches!(set_options(json, "friends.#(age=1).first", "x", Some(&opts)), Err(SjsonError::NoChange)));
+
+        // Deleting the first, a middle or the last element leaves a well-formed array
+        let json = r#"{"friends":[{"id":1},{"id":2},{"id":3}],"n":1}"#;
+        for optimistic in [false, true] {
+            let opts = Options { optimistic, ..Default::default() };
+            let delete = |path| delete_options(json, path, Some(&opts));
+            assert_eq!(delete("friends.#(id=1)").unwrap(), r#"{"friends":[{"id":2},{"id":3}],"n":1}"#);
+            assert_eq!(delete("friends.#(id=2)").unwrap(), r#"{"friends":[{"id":1},{"id":3}],"n":1}"#);
+            assert_eq!(delete("friends.#(id=3)").unwrap(), r#"{"friends":[{"id":1},{"id":2}],"n":1}"#);
+            assert!(matches!(delete("friends.#(id=4)"), Err(SjsonError::NoChange)));
+        }
+        let json = r#"{"friends":[{"id":3}]}"#;
+        assert_eq!(delete(json, "friends.#(id=3)").unwrap(), r#"{"friends":[]}"#);
+
+        // The separator is literal inside the match
+        let json = r#"{"apps":[{"version":"1.5","name":"a"}]}"#;
+        let result = set(json, "apps.#(version=1.5).name", "b").unwrap();
+        assert_eq!(get(&result, "apps.0.name"), Some(serde_json::json!("b")));
+        assert_eq!(
+            Path::parse("apps.#(version=1.5).name").unwrap().segments()[1],
+            Segment::Match { key: "version".to_string(), value: "1.5".to_string() }
+        );
+    }
+
+    #[test]
+    fn test_delete_many() {
+        let json = r#"{"a":1,"b":2,"c":3,"list":[0,1,2,3]}"#;
+
+        assert_eq!(delete_many(json, &["a", "c"], None).unwrap(), r#"{"b":2,"list":[0,1,2,3]}"#);
+
+        // Later indices see the shift of earlier deletes
+        assert_eq!(delete_many(json, &["list.0", "list.0"], None).unwrap(), r#"{"a":1,"b":2,"c":3,"list":[2,3]}"#);
+        assert_eq!(delete_many(json, &["list.1", "list.2"], None).unwrap(), r#"{"a":1,"b":2,"c":3,"list":[0,2]}"#);
+
+        // A missing path fails the batch unless it is ignored
+        match delete_many(json, &["a", "missing", "b"], None) {
+            Err(SjsonError::EditFailed { index: 1, source }) => assert!(matches!(*source, SjsonError::NoChange)),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        let opts = Options { ignore_missing: true, ..Default::default() };
+        assert_eq!(delete_many(json, &["a", "missing", "b"], Some(&opts)).unwrap(), r#"{"c":3,"list":[0,1,2,3]}"#);
+
+        // Other errors are still reported
+        assert!(matches!(
+            delete_many(json, &["a", ""], Some(&opts)),
+            Err(SjsonError::EditFailed { index: 1, .. })
+        ));
     }
 
     #[test]
-    fn test_options_default() {
-        let opts = Options::default();
-        assert_eq!(opts.optimistic, false);
+    fn test_max_depth() {
+        let deep = vec!["a"; 10_000].join(".");
+        let mut opts = Options { max_depth: Some(64), ..Default::default() };
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            assert!(matches!(set_options("{}", &deep, "1", Some(&opts)), Err(SjsonError::PathTooDeep)));
+            assert!(matches!(set_raw_options("{}", &deep, "1", Some(&opts)), Err(SjsonError::PathTooDeep)));
+            assert!(matches!(delete_options(r#"{"a":{}}"#, &deep, Some(&opts)), Err(SjsonError::PathTooDeep)));
+        }
+        let path = Path::parse(&deep).unwrap();
+        assert!(matches!(set_path("{}", &path, "1", Some(&opts)), Err(SjsonError::PathTooDeep)));
+
+        // Paths up to the limit still work
+        let path = vec!["a"; 64].join(".");
+        let result = set_options("{}", &path, "1", Some(&opts)).unwrap();
+        assert_eq!(get(&result, &path), Some(serde_json::json!(1)));
+        assert_eq!(set_options(&result, &path, "2", Some(&opts)).unwrap(), result.replace('1', "2"));
     }
 
     #[test]
-    fn test_optimistic_delete_nested() {
-        let json = r#"{"user":{"name":"Tom","age":37,"city":"Beijing"}}"#;
-        let mut opts = Options::default();
-        opts.optimistic = true;
-        let result = delete_options(json, "user.age", Some(&opts)).unwrap();
-        assert!(result.contains("\"user\""));
-        assert!(result.contains("\"name\":\"Tom\""));
-        assert!(result.contains("\"city\":\"Beijing\""));
-        assert!(!result.contains("\"age\":37"));
+    fn test_error_eq_and_clone() {
+        assert_eq!(set("{}", "", "x").unwrap_err(), SjsonError::EmptyPath);
+        assert_eq!(set("{}", "a..b", "x").unwrap_err(), SjsonError::InvalidPath);
+        assert_ne!(SjsonError::EmptyPath, SjsonError::InvalidPath);
+        assert_eq!(
+            set(r#"{"a":[1]}"#, "a.-2", "x").unwrap_err(),
+            SjsonError::ArrayIndexOutOfBounds { index: -2, len: 1 }
+        );
+        assert_eq!(
+            set_many("{}", &[("a", "1"), ("", "2")], None).unwrap_err(),
+            SjsonError::EditFailed { index: 1, source: Box::new(SjsonError::EmptyPath) }
+        );
+
+        // Errors carrying serde_json errors compare by message and clone cheaply
+        let err = set("{", "a", "1").unwrap_err();
+        assert!(matches!(err, SjsonError::InvalidJson(_)));
+        assert_eq!(err.clone(), err);
+        assert_eq!(set("{", "b", "2").unwrap_err(), err);
+        assert_ne!(set("[", "b", "2").unwrap_err(), err);
+        assert_ne!(err, SjsonError::Custom(err.to_string()));
     }
 
+    #[cfg(feature = "std")]
     #[test]
-    fn test_optimistic_delete_array_element() {
-        let json = r#"{"items":["a","b","c","d"]}"#;
-        let mut opts = Options::default();
-        opts.optimistic = true;
-        let result = delete_options(json, "items.1", Some(&opts)).unwrap();
-        assert_eq!(result, r#"{"items":["a","c","d"]}"#);
+    fn test_serde_error_conversion() {
+        use std::error::Error;
+
+        fn parse(json: &str) -> Result<JsonValue, SjsonError> {
+            Ok(serde_json::from_str(json)?)
+        }
+
+        let err = parse("{").unwrap_err();
+        assert!(matches!(err, SjsonError::InvalidJson(_)));
+        let source = err.source().unwrap();
+        assert!(source.downcast_ref::<serde_json::Error>().is_some_and(|e| e.is_eof()));
+        assert!(set("{", "a", "1").unwrap_err().source().is_some());
+        assert!(SjsonError::EmptyPath.source().is_none());
     }
 
+    #[cfg(not(feature = "lenient"))]
     #[test]
-    fn test_optimistic_set_with_special_characters() {
-        let json = r#"{"user":{"name":"Tom"}}"#;
-        let mut opts = Options::default();
-        opts.optimistic = true;
-        // Test with value containing special characters
-        let result = set_options(json, "user.description", "Hello, \"World\"!", Some(&opts)).unwrap();
-        assert!(result.contains("\"description\":\"Hello, \\\"World\\\"!\""));
+    fn test_optimistic_set_new_key_keeps_order() {
+        let json = r#"{"z":1,"a":{"y":2,"b":3}}"#;
+        let opts = Options { optimistic: true, ..Default::default() };
+
+        // Existing and new keys both keep the order of the document
+        assert_eq!(set_options(json, "a.b", "4", Some(&opts)).unwrap(), r#"{"z":1,"a":{"y":2,"b":4}}"#);
+        assert_eq!(set_options(json, "a.c", "4", Some(&opts)).unwrap(), r#"{"z":1,"a":{"y":2,"b":3,"c":4}}"#);
+        assert_eq!(set_options(json, "m", "x", Some(&opts)).unwrap(), r#"{"z":1,"a":{"y":2,"b":3},"m":"x"}"#);
+        assert_eq!(set_raw_options(json, "a.c", "[1]", Some(&opts)).unwrap(), r#"{"z":1,"a":{"y":2,"b":3,"c":[1]}}"#);
+        assert_eq!(set_options(r#"{"a":{ }}"#, "a.b", "1", Some(&opts)).unwrap(), r#"{"a":{"b":1 }}"#);
+        assert_eq!(set_options("{\n  \"a\": 1\n}", "b", "2", Some(&opts)).unwrap(), "{\n  \"a\": 1,\"b\":2\n}");
+        let opts_absent = Options { only_if_absent: true, ..opts.clone() };
+        assert_eq!(set_options(json, "n", "1", Some(&opts_absent)).unwrap(), r#"{"z":1,"a":{"y":2,"b":3},"n":1}"#);
+
+        // Keys of nested objects or later siblings are not mistaken for members
+        let json = r#"{"a":{"x":{"b":1}},"b":2}"#;
+        assert_eq!(set_options(json, "a.b", "3", Some(&opts)).unwrap(), r#"{"a":{"x":{"b":1},"b":3},"b":2}"#);
+        assert_eq!(delete_options(json, "a.b", Some(&opts)), Err(SjsonError::NoChange));
+
+        // Missing parents still go through the parser, escaped keys are decoded
+        assert_eq!(set_options(r#"{"a":1}"#, "b.c", "2", Some(&opts)).unwrap(), set(r#"{"a":1}"#, "b.c", "2").unwrap());
+        assert_eq!(set_options(r#"{"\u0062":1}"#, "b", "2", Some(&opts)).unwrap(), r#"{"\u0062":2}"#);
     }
 
     #[test]
-    fn test_optimistic_set_null_value() {
-        let json = r#"{"user":{"name":"Tom","age":37}}"#;
-        let mut opts = Options::default();
-        opts.optimistic = true;
-        let result = set_options(json, "user.age", "null", Some(&opts)).unwrap();
-        assert!(result.contains("\"age\":null"));
+    fn test_set_and_delete_in_value() {
+        let json = r#"{"name":{"first":"Tom"},"tags":["a","b"]}"#;
+        let mut value = serde_json::json!({"name": {"first": "Tom"}, "tags": ["a", "b"]});
+
+        set_in_value(&mut value, "name.last", serde_json::json!("Anderson"), None).unwrap();
+        let expected = set(json, "name.last", "Anderson").unwrap();
+        assert_eq!(value, serde_json::from_str::<JsonValue>(&expected).unwrap());
+
+        set_in_value(&mut value, "tags.-1", serde_json::json!({"c": 1}), None).unwrap();
+        assert_eq!(value["tags"], serde_json::json!(["a", {"c": 1}]));
+
+        delete_in_value(&mut value, "tags.0", None).unwrap();
+        delete_in_value(&mut value, "name.first", None).unwrap();
+        assert_eq!(value, serde_json::json!({"name": {"last": "Anderson"}, "tags": [{"c": 1}]}));
+
+        assert_eq!(delete_in_value(&mut value, "missing", None), Err(SjsonError::NoChange));
+        assert_eq!(set_in_value(&mut value, "", JsonValue::Null, None), Err(SjsonError::EmptyPath));
+        let opts = Options { only_if_absent: true, ..Default::default() };
+        assert_eq!(set_in_value(&mut value, "name.last", JsonValue::Null, Some(&opts)), Err(SjsonError::NoChange));
+
+        // A failed edit leaves the caller's value untouched
+        let mut value = serde_json::json!({"n": null});
+        assert!(set_in_value(&mut value, "a.b.-1", JsonValue::Null, None).is_err());
+        assert!(set_in_value(&mut value, "n.0.-1", JsonValue::Null, None).is_err());
+        assert_eq!(value, serde_json::json!({"n": null}));
+
+        set_in_value(&mut value, ROOT_PATH, serde_json::json!([]), None).unwrap();
+        assert_eq!(value, serde_json::json!([]));
     }
 
     #[test]
-    fn test_optimistic_set_boolean_values() {
-        let json = r#"{"user":{"name":"Tom"}}"#;
+    fn test_set_u64() {
+        let json = r#"{"id":1}"#;
         let mut opts = Options::default();
-        opts.optimistic = true;
-        
-        // Test true
-        let result = set_options(json, "user.active", "true", Some(&opts)).unwrap();
-        assert!(result.contains("\"active\":true"));
-        
-        // Test false
-        let result = set_options(result.as_str(), "user.verified", "false", Some(&opts)).unwrap();
-        assert!(result.contains("\"verified\":false"));
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            for path in ["id", "other"] {
+                let result = set_u64(json, path, u64::MAX, Some(&opts)).unwrap();
+                assert!(result.contains("18446744073709551615"), "{}", result);
+                assert_eq!(get(&result, path), Some(serde_json::json!(u64::MAX)));
+                assert_eq!(get(&result, path).unwrap().as_u64(), Some(u64::MAX));
+            }
+        }
     }
 
     #[test]
-    fn test_optimistic_set_numeric_values() {
-        let json = r#"{"user":{"name":"Tom"}}"#;
+    fn test_set_scalar() {
+        let json = r#"{"x":1}"#;
         let mut opts = Options::default();
-        opts.optimistic = true;
-        
-        // Test integer
-        let result = set_options(json, "user.age", "25", Some(&opts)).unwrap();
-        assert!(result.contains("\"age\":25"));
-        
-        // Test float
-        let result = set_options(result.as_str(), "user.score", "95.5", Some(&opts)).unwrap();
-        assert!(result.contains("\"score\":95.5"));
-        
-        // Test negative number
-        let result = set_options(result.as_str(), "user.balance", "-100.50", Some(&opts)).unwrap();
-        assert!(result.contains("\"balance\":-100.5"));
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            let set = |value| set_scalar(json, "x", value, Some(&opts)).unwrap();
+
+            assert_eq!(set(Scalar::Bool(false)), r#"{"x":false}"#);
+            assert_eq!(set(Scalar::I64(-5)), r#"{"x":-5}"#);
+            assert_eq!(set(Scalar::U64(u64::MAX)), r#"{"x":18446744073709551615}"#);
+            assert_eq!(set(Scalar::F64(1.5)), r#"{"x":1.5}"#);
+            assert_eq!(set(Scalar::F64(2.0)), r#"{"x":2.0}"#);
+            assert_eq!(set(Scalar::F64(f64::NAN)), r#"{"x":null}"#);
+            assert_eq!(set(Scalar::Str("42")), r#"{"x":"42"}"#);
+            assert_eq!(set(Scalar::Str("null")), r#"{"x":"null"}"#);
+            assert_eq!(set(Scalar::Str("say \"hi\"")), r#"{"x":"say \"hi\""}"#);
+            assert_eq!(set(Scalar::Null), r#"{"x":null}"#);
+
+            let result = set_scalar(json, "y.z", Scalar::U64(u64::MAX), Some(&opts)).unwrap();
+            assert_eq!(get(&result, "y.z").unwrap().as_u64(), Some(u64::MAX));
+        }
+
+        // No text is guessed at, so compat mode doesn't turn values into strings
+        let compat = Options { go_sjson_compat: true, ..Default::default() };
+        assert_eq!(set_scalar(json, "x", Scalar::I64(2), Some(&compat)).unwrap(), r#"{"x":2}"#);
+        assert_eq!(set_scalar(json, "x", Scalar::Null, Some(&compat)).unwrap(), r#"{"x":null}"#);
+
+        let deleting = Options { treat_null_as_delete: true, ..Default::default() };
+        assert_eq!(set_scalar(json, "x", Scalar::Null, Some(&deleting)).unwrap(), "{}");
+        assert_eq!(set_scalar(json, "x", Scalar::Str("null"), Some(&deleting)).unwrap(), r#"{"x":"null"}"#);
+        assert_eq!(set_scalar(json, "", Scalar::Null, None), Err(SjsonError::EmptyPath));
     }
 
     #[test]
-    fn test_optimistic_set_array_value() {
-        let json = r#"{"user":{"name":"Tom"}}"#;
+    fn test_set_string() {
+        let json = r#"{"x":1}"#;
         let mut opts = Options::default();
-        opts.optimistic = true;
-        let result = set_options(json, "user.hobbies", "[\"reading\",\"swimming\"]", Some(&opts)).unwrap();
-        assert!(result.contains("\"hobbies\":[\"reading\",\"swimming\"]"));
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            assert_eq!(set_string(json, "x", "true", Some(&opts)).unwrap(), r#"{"x":"true"}"#);
+            assert_eq!(set_string(json, "x", "42", Some(&opts)).unwrap(), r#"{"x":"42"}"#);
+            assert_eq!(set_string(json, "x", "null", Some(&opts)).unwrap(), r#"{"x":"null"}"#);
+            assert_eq!(set_string(json, "x", "[1]", Some(&opts)).unwrap(), r#"{"x":"[1]"}"#);
+            assert_eq!(set_string(json, "x", r#"say "hi""#, Some(&opts)).unwrap(), r#"{"x":"say \"hi\""}"#);
+        }
     }
 
+    #[cfg(feature = "arbitrary_precision")]
     #[test]
-    fn test_optimistic_set_object_value() {
-        let json = r#"{"user":{"name":"Tom"}}"#;
-        let mut opts = Options::default();
-        opts.optimistic = true;
-        let result = set_options(json, "user.address", "{\"city\":\"Beijing\",\"country\":\"China\"}", Some(&opts)).unwrap();
-        assert!(result.contains("\"address\":{\"city\":\"Beijing\",\"country\":\"China\"}"));
+    fn test_set_i128() {
+        let json = r#"{"id":1}"#;
+        let result = set_i128(json, "id", i128::MIN, None).unwrap();
+        assert_eq!(result, r#"{"id":-170141183460469231731687303715884105728}"#);
+        let result = set_u128(json, "id", u128::MAX, None).unwrap();
+        assert_eq!(result, r#"{"id":340282366920938463463374607431768211455}"#);
+        assert_eq!(get(&result, "id").unwrap().to_string(), u128::MAX.to_string());
     }
 
+    #[cfg(feature = "chrono")]
     #[test]
-    fn test_optimistic_set_empty_string() {
-        let json = r#"{"user":{"name":"Tom"}}"#;
+    fn test_set_datetime() {
+        use chrono::TimeZone;
+
+        let dt = chrono::Utc.with_ymd_and_hms(2024, 2, 29, 13, 5, 9).unwrap();
+        let json = r#"{"at":null,"event":"login"}"#;
         let mut opts = Options::default();
-        opts.optimistic = true;
-        let result = set_options(json, "user.description", "", Some(&opts)).unwrap();
-        assert!(result.contains("\"description\":\"\""));
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            let result = set_datetime(json, "at", dt, Some(&opts)).unwrap();
+            assert_eq!(get(&result, "at"), Some(JsonValue::String(dt.to_rfc3339())));
+            assert_eq!(result, r#"{"at":"2024-02-29T13:05:09+00:00","event":"login"}"#);
+        }
+
+        let dt = dt + chrono::Duration::microseconds(250);
+        let result = set_datetime("{}", "at", dt, None).unwrap();
+        assert_eq!(get(&result, "at"), Some(JsonValue::String(dt.to_rfc3339())));
+
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 2, 29).unwrap();
+        let result = set_date("{}", "day", date, None).unwrap();
+        assert_eq!(result, r#"{"day":"2024-02-29"}"#);
     }
 
     #[test]
-    fn test_optimistic_set_with_unicode() {
-        let json = r#"{"user":{"name":"Tom"}}"#;
+    fn test_created_keys_are_escaped() {
         let mut opts = Options::default();
-        opts.optimistic = true;
-        let result = set_options(json, "user.name", "张三", Some(&opts)).unwrap();
-        assert!(result.contains("\"name\":\"张三\""));
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            for key in [r#"he said "x""#, "back\\slash", "tab\there", "ctl\u{0001}"] {
+                let path = format!("a.{}", key.replace('\\', r"\\"));
+                let result = set_options(r#"{"a":{"b":1}}"#, &path, "1", Some(&opts)).unwrap();
+                let parsed: JsonValue = serde_json::from_str(&result).unwrap();
+                assert_eq!(parsed["a"][key], serde_json::json!(1), "{}", result);
+            }
+        }
+        let result = set(r#"{"a":{}}"#, r#"a.he said "x""#, "1").unwrap();
+        assert_eq!(result, r#"{"a":{"he said \"x\"":1}}"#);
     }
 
     #[test]
-    fn test_optimistic_set_deep_nested() {
-        let json = r#"{"level1":{"level2":{"level3":{"name":"Tom"}}}}"#;
+    fn test_scalar_root() {
+        let must_be_container = Err(SjsonError::JsonMustBeObjectOrArray);
         let mut opts = Options::default();
-        opts.optimistic = true;
-        let result = set_options(json, "level1.level2.level3.age", "25", Some(&opts)).unwrap();
-        assert!(result.contains("\"age\":25"));
-        assert!(result.contains("\"name\":\"Tom\""));
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            assert_eq!(set_options("42", "a", "1", Some(&opts)), must_be_container);
+            assert_eq!(set_options("\"hi\"", "a", "1", Some(&opts)), must_be_container);
+            assert_eq!(set_options("null", "a.0", "1", Some(&opts)), must_be_container);
+            assert_eq!(delete_options("42", "a", Some(&opts)), must_be_container);
+            assert_eq!(delete_options("true", "a.b", Some(&opts)), must_be_container);
+
+            // Replacing the whole document doesn't descend
+            assert_eq!(set_raw_options("42", ROOT_PATH, "[1]", Some(&opts)).unwrap(), "[1]");
+        }
+
+        opts.allow_scalar_root_replace = true;
+        assert_eq!(set_options("42", "a", "1", Some(&opts)).unwrap(), r#"{"a":1}"#);
+        assert_eq!(set_options("null", "0", "1", Some(&opts)).unwrap(), "[1]");
+        assert_eq!(
+            set_options("\"hi\"", "0", "1", Some(&opts)),
+            Err(SjsonError::PathTypeMismatch { segment: "0".to_string(), found: "string" })
+        );
+        assert_eq!(delete_options("42", "a", Some(&opts)), must_be_container);
     }
 
     #[test]
-    fn test_optimistic_set_array_deep_nested() {
-        let json = r#"{"data":{"items":[{"name":"item1"},{"name":"item2"}]}}"#;
+    fn test_index_into_scalar() {
+        let mismatch = |found| Err(SjsonError::PathTypeMismatch { segment: "0".to_string(), found });
         let mut opts = Options::default();
-        opts.optimistic = true;
-        let result = set_options(json, "data.items.0.price", "100", Some(&opts)).unwrap();
-        assert!(result.contains("\"price\":100"));
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            let set = |json: &str, path: &str| set_options(json, path, "x", Some(&opts));
+
+            assert_eq!(set(r#"{"a":"hello"}"#, "a.0"), mismatch("string"));
+            assert_eq!(set(r#"{"a":5}"#, "a.0"), mismatch("number"));
+            assert_eq!(set(r#"{"a":true}"#, "a.0"), mismatch("bool"));
+            assert_eq!(set(r#"{"a":"hello"}"#, "a.0.b"), mismatch("string"));
+            assert_eq!(
+                set_raw_options(r#"{"a":"hello"}"#, "a.0:1", "[1]", Some(&opts)),
+                Err(SjsonError::PathTypeMismatch { segment: "0:1".to_string(), found: "string" })
+            );
+
+            // Null stands in for a missing value, and keys still replace scalars
+            assert_eq!(set(r#"{"a":null}"#, "a.0").unwrap(), r#"{"a":["x"]}"#);
+            assert_eq!(set(r#"{"a":"hello"}"#, "a.b").unwrap(), r#"{"a":{"b":"x"}}"#);
+            assert_eq!(set(r#"{"a":"hello"}"#, "a").unwrap(), r#"{"a":"x"}"#);
+        }
+
+        opts.allow_scalar_index_replace = true;
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            let set = |json: &str, path: &str| set_options(json, path, "x", Some(&opts));
+
+            assert_eq!(set(r#"{"a":"hello"}"#, "a.0").unwrap(), r#"{"a":["x"]}"#);
+            assert_eq!(set(r#"{"a":5}"#, "a.1").unwrap(), r#"{"a":[null,"x"]}"#);
+            assert_eq!(set(r#"{"a":true}"#, "a.0.b").unwrap(), r#"{"a":[{"b":"x"}]}"#);
+        }
+
+        // No clobbering refuses every scalar either way
+        opts.no_clobber = true;
+        assert_eq!(set_options(r#"{"a":"hello"}"#, "a.0", "x", Some(&opts)), mismatch("string"));
     }
 
     #[test]
-    fn test_optimistic_delete_array_deep_nested() {
-        let json = r#"{"data":{"items":[{"name":"item1","price":100},{"name":"item2","price":200}]}}"#;
-        let mut opts = Options::default();
-        opts.optimistic = true;
-        let result = delete_options(json, "data.items.0.price", Some(&opts)).unwrap();
-        assert!(result.contains("\"name\":\"item1\""));
-        assert!(!result.contains("\"price\":100"));
+    fn test_wildcard() {
+        // Objects and arrays fan out the same way
+        let json = r#"{"s":{"a":{"n":1},"b":{"n":2}}}"#;
+        assert_eq!(set(json, "s.*.on", "true").unwrap(), r#"{"s":{"a":{"n":1,"on":true},"b":{"n":2,"on":true}}}"#);
+        let json = r#"{"s":[{"n":1},{"n":2}]}"#;
+        assert_eq!(set(json, "s.*.on", "true").unwrap(), r#"{"s":[{"n":1,"on":true},{"n":2,"on":true}]}"#);
+        assert_eq!(set(json, "s.*.n", "0").unwrap(), r#"{"s":[{"n":0},{"n":0}]}"#);
+        assert_eq!(delete(json, "s.*.n").unwrap(), r#"{"s":[{},{}]}"#);
+        assert_eq!(delete_with_old(json, "s.*.n", None).unwrap().1, Some(serde_json::json!([1, 2])));
+
+        // A final wildcard replaces or removes every child
+        assert_eq!(set("[1,2,3]", "*", "0").unwrap(), "[0,0,0]");
+        assert_eq!(delete(r#"{"a":[1,2],"b":1}"#, "a.*").unwrap(), r#"{"a":[],"b":1}"#);
+        assert_eq!(delete(r#"{"a":[],"b":1}"#, "a.*"), Err(SjsonError::NoChange));
+
+        // A scalar has no children, so a final wildcard leaves it alone
+        assert_eq!(delete(r#"{"a":5}"#, "a.*"), Err(SjsonError::NoChange));
+        assert_eq!(delete(r#"{"a":"x","b":1}"#, "a.*"), Err(SjsonError::NoChange));
+
+        // Wildcards nest, and numeric segments still index arrays under them
+        let json = r#"[[1,2],[3]]"#;
+        assert_eq!(set(json, "*.*", "0").unwrap(), "[[0,0],[0]]");
+        assert_eq!(set(json, "*.0", "0").unwrap(), "[[0,2],[0]]");
+
+        // Nothing to fan out to leaves the document alone
+        assert_eq!(set("{}", "*.a", "1"), Err(SjsonError::NoChange));
+        assert_eq!(set("{}", "a.*.b", "1"), Err(SjsonError::NoChange));
+        assert_eq!(set("[]", "0.*", "1"), Err(SjsonError::NoChange));
+        assert_eq!(delete(r#"{"a":{"b":1}}"#, "a.*.c"), Err(SjsonError::NoChange));
+
+        // Pruning stops at the children of a wildcard
+        let opts = Options { prune_empty: true, ..Default::default() };
+        let json = r#"{"s":{"x":{"a":{"b":1}}}}"#;
+        assert_eq!(delete_options(json, "s.*.a.b", Some(&opts)).unwrap(), r#"{"s":{"x":{}}}"#);
+
+        // Children the rest of the path can't step into are skipped...
+        let json = r#"{"s":{"a":{"n":1},"b":5}}"#;
+        assert_eq!(set(json, "s.*.on", "true").unwrap(), r#"{"s":{"a":{"n":1,"on":true},"b":5}}"#);
+        assert_eq!(set("[[1],5]", "*.0", "2").unwrap(), "[[2],5]");
+        assert_eq!(set(r#"[[1],{"a":1}]"#, "*.a", "2").unwrap(), r#"[[1],{"a":2}]"#);
+
+        // ...unless they are refused
+        let opts = Options { strict_wildcards: true, ..Default::default() };
+        let mismatch = |found| Err(SjsonError::PathTypeMismatch { segment: "*".to_string(), found });
+        assert_eq!(set_options(json, "s.*.on", "true", Some(&opts)), mismatch("number"));
+        assert_eq!(delete_options(json, "s.*.n", Some(&opts)), mismatch("number"));
+        assert_eq!(set_options(r#"[[1],{"a":1}]"#, "*.a", "2", Some(&opts)), mismatch("array"));
+        assert_eq!(set_options(r#"{"a":1}"#, "a.*", "2", Some(&opts)), mismatch("number"));
+        assert_eq!(delete_options(r#"{"a":5}"#, "a.*", Some(&opts)), mismatch("number"));
+        assert_eq!(delete_options(r#"{"a":"x","b":1}"#, "a.*", Some(&opts)), mismatch("string"));
+
+        // There is no single value to report on
+        let json = r#"{"s":[{"n":1},{"n":2}]}"#;
+        assert_eq!(set_with_old(json, "s.*.n", "0", None), Err(SjsonError::InvalidPath));
+        assert_eq!(set_reporting(json, "s.*.n", "0", None), Err(SjsonError::InvalidPath));
+        assert_eq!(set_reporting(json, "s.0.n", "0", None).unwrap().1, ChangeKind::Updated);
+
+        // Only a whole segment is a wildcard
+        assert_eq!(set(r#"{"a*":1}"#, "a*", "2").unwrap(), r#"{"a*":2}"#);
+        assert_eq!(set(r#"{"*":1}"#, "\\*", "2").unwrap(), r#"{"*":2}"#);
     }
 
     #[test]
-    fn test_optimistic_set_with_existing_array() {
-        let json = r#"{"items":["a","b","c"]}"#;
-        let mut opts = Options::default();
-        opts.optimistic = true;
-        let result = set_options(json, "items.3", "d", Some(&opts)).unwrap();
-        assert_eq!(result, r#"{"items":["a","b","c","d"]}"#);
+    fn test_nullify() {
+        let json = r#"{"name":"Tom","age":37,"tags":["a"]}"#;
+        for path in ["name", "tags.0", "tags"] {
+            assert_eq!(get(&nullify(json, path, None).unwrap(), path), Some(serde_json::Value::Null));
+        }
+
+        // The key stays where it was
+        #[cfg(not(feature = "lenient"))]
+        {
+            assert_eq!(nullify(json, "name", None).unwrap(), r#"{"name":null,"age":37,"tags":["a"]}"#);
+            assert_eq!(nullify(json, "tags.0", None).unwrap(), r#"{"name":"Tom","age":37,"tags":[null]}"#);
+        }
+
+        // Nothing is created, unlike setting null
+        assert_eq!(nullify(json, "missing", None), Err(SjsonError::NoChange));
+        assert_eq!(nullify(json, "tags.1", None), Err(SjsonError::NoChange));
+        assert_eq!(nullify(json, "name.first", None), Err(SjsonError::NoChange));
+        assert_eq!(nullify(json, "", None), Err(SjsonError::EmptyPath));
+
+        // An existing null is only a no-op when asked to report it
+        let json = r#"{"a":1,"z":null}"#;
+        assert_eq!(nullify(json, "z", None).unwrap(), json);
+        let opts = Options { error_on_nochange: true, ..Default::default() };
+        assert_eq!(nullify(json, "z", Some(&opts)), Err(SjsonError::NoChange));
+
+        // Treating null as a delete doesn't apply, the key stays
+        let opts = Options { treat_null_as_delete: true, ..Default::default() };
+        assert_eq!(nullify(r#"{"a":1,"z":1}"#, "z", Some(&opts)).unwrap(), r#"{"a":1,"z":null}"#);
     }
 
     #[test]
-    fn test_optimistic_set_with_large_array_index() {
-        let json = r#"{"items":["a","b"]}"#;
-        let mut opts = Options::default();
-        opts.optimistic = true;
-        let result = set_options(json, "items.5", "f", Some(&opts)).unwrap();
-        assert_eq!(result, r#"{"items":["a","b",null,null,null,"f"]}"#);
+    fn test_no_clobber() {
+        // By default scalars on the way are replaced
+        assert_eq!(set(r#"{"a":5}"#, "a.b", "1").unwrap(), r#"{"a":{"b":1}}"#);
+
+        let mut opts = Options { no_clobber: true, ..Default::default() };
+        for optimistic in [false, true] {
+            opts.optimistic = optimistic;
+            assert_eq!(
+                set_options(r#"{"a":5}"#, "a.b", "1", Some(&opts)),
+                Err(SjsonError::PathTypeMismatch { segment: "b".to_string(), found: "number" })
+            );
+            assert_eq!(
+                set_options(r#"{"a":{"b":"x"}}"#, "a.b.0.c", "1", Some(&opts)),
+                Err(SjsonError::PathTypeMismatch { segment: "0".to_string(), found: "string" })
+            );
+            assert_eq!(
+                set_options(r#"{"a":null}"#, "a.b", "1", Some(&opts)),
+                Err(SjsonError::PathTypeMismatch { segment: "b".to_string(), found: "null" })
+            );
+            let root_opts = Options { allow_scalar_root_replace: true, ..opts.clone() };
+            assert_eq!(
+                set_options("true", "a", "1", Some(&root_opts)),
+                Err(SjsonError::PathTypeMismatch { segment: "a".to_string(), found: "bool" })
+            );
+
+            // Missing parents are still created
+            assert_eq!(set_options(r#"{"a":{}}"#, "a.b.c", "1", Some(&opts)).unwrap(), r#"{"a":{"b":{"c":1}}}"#);
+            assert_eq!(set_options(r#"{"a":5}"#, "a", "6", Some(&opts)).unwrap(), r#"{"a":6}"#);
+        }
+        assert_eq!(
+            SjsonError::PathTypeMismatch { segment: "b".to_string(), found: "number" }.to_string(),
+            "path segment 'b' steps into a number"
+        );
     }
 
+    #[cfg(feature = "lenient")]
     #[test]
-    fn test_optimistic_set_raw_with_complex_json() {
-        let json = r#"{"data":{"user":{"name":"Tom"}}}"#;
-        let mut opts = Options::default();
-        opts.optimistic = true;
-        let complex_value = r#"{"address":{"street":"123 Main St","city":"Beijing","country":"China"},"phone":"+86-123-4567","active":true,"scores":[95,87,92]}"#;
-        let result = set_raw_options(json, "data.user.profile", complex_value, Some(&opts)).unwrap();
-        // Check that all expected fields are present, regardless of order
-        assert!(result.contains("\"profile\""));
-        assert!(result.contains("\"active\":true"));
-        assert!(result.contains("\"phone\":\"+86-123-4567\""));
-        assert!(result.contains("\"scores\":[95,87,92]"));
-        assert!(result.contains("\"street\":\"123 Main St\""));
-        assert!(result.contains("\"city\":\"Beijing\""));
-        assert!(result.contains("\"country\":\"China\""));
+    fn test_lenient_comments_and_trailing_commas() {
+        let json = "{\n  // the name\n  \"name\": \"tom\",\n  \"tags\": [1, 2,],\n}";
+        let result = set(json, "zip", "7").unwrap();
+        assert_eq!(result, r#"{"name":"tom","tags":[1,2],"zip":7}"#);
+        assert!(serde_json::from_str::<JsonValue>(&result).is_ok());
+
+        let json = r#"{"a": /* one */ 1, "s": ",}", "url": "http://x/*y*/"}"#;
+        let result = set(json, "z", "2").unwrap();
+        assert_eq!(result, r#"{"a":1,"s":",}","url":"http://x/*y*/","z":2}"#);
     }
 
+    #[cfg(feature = "lenient")]
     #[test]
-    fn test_optimistic_fallback_to_parser() {
-        let json = r#"{"user":{"name":"Tom"}}"#;
-        let mut opts = Options::default();
-        opts.optimistic = true;
-        // Test with a path that contains special characters (should fall back to parser)
-        let result = set_options(json, "user.name", "Jerry", Some(&opts)).unwrap();
-        assert!(result.contains("\"name\":\"Jerry\""));
+    fn test_lenient_disables_optimistic() {
+        let opts = Options { optimistic: true, ..Default::default() };
+        let json = "{\"a\": 1, // note\n \"b\": 2,}";
+        assert_eq!(set_options(json, "b", "3", Some(&opts)).unwrap(), r#"{"a":1,"b":3}"#);
+        assert_eq!(delete_options(json, "a", Some(&opts)).unwrap(), r#"{"b":2}"#);
     }
 
+    #[cfg(not(feature = "lenient"))]
     #[test]
-    fn test_optimistic_set_multiple_operations() {
-        let json = r#"{"user":{"name":"Tom"}}"#;
-        let mut opts = Options::default();
-        opts.optimistic = true;
-        
-        // Multiple set operations
-        let result1 = set_options(json, "user.age", "25", Some(&opts)).unwrap();
-        let result2 = set_options(result1.as_str(), "user.city", "Beijing", Some(&opts)).unwrap();
-        let result3 = set_options(result2.as_str(), "user.active", "true", Some(&opts)).unwrap();
-        
-        assert!(result3.contains("\"age\":25"));
-        assert!(result3.contains("\"city\":\"Beijing\""));
-        assert!(result3.contains("\"active\":true"));
-        assert!(result3.contains("\"name\":\"Tom\""));
+    fn test_preserve_untouched() {
+        let opts = Options { preserve_untouched: true, ..Default::default() };
+        let json = r#"{"price":1.10,"big":1e3,"zero":-0,"name":"a"}"#;
+
+        assert_eq!(
+            set_options(json, "name", "x", Some(&opts)).unwrap(),
+            r#"{"price":1.10,"big":1e3,"zero":-0,"name":"x"}"#
+        );
+        assert_eq!(
+            set_options(json, "tag", "x", Some(&opts)).unwrap(),
+            r#"{"price":1.10,"big":1e3,"zero":-0,"name":"a","tag":"x"}"#
+        );
+        assert_eq!(
+            delete_options(json, "name", Some(&opts)).unwrap(),
+            r#"{"price":1.10,"big":1e3,"zero":-0}"#
+        );
+        assert_eq!(
+            set_bytes_options(json.as_bytes(), "zero", "0", Some(&opts)).unwrap(),
+            br#"{"price":1.10,"big":1e3,"zero":0,"name":"a"}"#
+        );
+
+        // Targets the byte scan can't reach are refused instead of reparsed
+        assert_eq!(set_options(json, "a.b", "x", Some(&opts)), Err(SjsonError::SpliceUnavailable));
+        assert_eq!(set_options(json, "na\\.me", "x", Some(&opts)), Err(SjsonError::SpliceUnavailable));
+        assert_eq!(set_many(json, &[("name", "x")], Some(&opts)), Err(SjsonError::SpliceUnavailable));
+        assert_eq!(set_with_old(json, "name", "x", Some(&opts)), Err(SjsonError::SpliceUnavailable));
     }
 
+    #[cfg(not(feature = "arbitrary_precision"))]
     #[test]
-    fn test_optimistic_delete_multiple_operations() {
-        let json = r#"{"user":{"name":"Tom","age":25,"city":"Beijing","active":true}}"#;
-        let mut opts = Options::default();
-        opts.optimistic = true;
-        
-        // Multiple delete operations
-        let result1 = delete_options(json, "user.age", Some(&opts)).unwrap();
-        let result2 = delete_options(result1.as_str(), "user.city", Some(&opts)).unwrap();
-        let result3 = delete_options(result2.as_str(), "user.active", Some(&opts)).unwrap();
-        
-        assert!(result3.contains("\"name\":\"Tom\""));
-        assert!(!result3.contains("\"age\":25"));
-        assert!(!result3.contains("\"city\":\"Beijing\""));
-        assert!(!result3.contains("\"active\":true"));
+    fn test_parser_path_reformats_numbers() {
+        let json = r#"{"a":1.10,"b":1e3,"c":-0,"d":"a"}"#;
+        // Replacing a scalar splices it and leaves the other numbers alone,
+        // unless the lenient feature sends the edit through the parser
+        let expected = if cfg!(feature = "lenient") {
+            r#"{"a":1.1,"b":1000.0,"c":-0.0,"d":"x"}"#
+        } else {
+            r#"{"a":1.10,"b":1e3,"c":-0,"d":"x"}"#
+        };
+        assert_eq!(set(json, "d", "x").unwrap(), expected);
+        assert_eq!(set(json, "e", "x").unwrap(), r#"{"a":1.1,"b":1000.0,"c":-0.0,"d":"a","e":"x"}"#);
     }
 
     #[test]
-    fn test_optimistic_set_with_escaped_quotes() {
-        let json = r#"{"user":{"name":"Tom"}}"#;
-        let mut opts = Options::default();
-        opts.optimistic = true;
-        let result = set_options(json, "user.quote", "He said \"Hello World\"", Some(&opts)).unwrap();
-        assert!(result.contains("\"quote\":\"He said \\\"Hello World\\\"\""));
+    fn test_append_segment() {
+        let json = set("{}", "log.#", "a").unwrap();
+        assert_eq!(json, r#"{"log":["a"]}"#);
+        let json = set(&json, "log.#", "b").unwrap();
+        assert_eq!(json, r#"{"log":["a","b"]}"#);
+        assert_eq!(set_raw(&json, "log.#", r#"{"c":1}"#).unwrap(), r#"{"log":["a","b",{"c":1}]}"#);
+        assert_eq!(set(&json, "log.#.msg", "c").unwrap(), r#"{"log":["a","b",{"msg":"c"}]}"#);
+        assert_eq!(set(r#"{"log":null}"#, "log.#", "a").unwrap(), r#"{"log":["a"]}"#);
+        assert_eq!(set("[]", "#", "a").unwrap(), r#"["a"]"#);
+
+        // `-1` still overwrites the last element
+        assert_eq!(set(&json, "log.-1", "c").unwrap(), r#"{"log":["a","c"]}"#);
+
+        for (target, found) in [(r#"{"log":{}}"#, "object"), (r#"{"log":"a"}"#, "string"), (r#"{"log":1}"#, "number")] {
+            assert_eq!(
+                set(target, "log.#", "b"),
+                Err(SjsonError::PathTypeMismatch { segment: "#".to_string(), found })
+            );
+        }
+        assert_eq!(
+            set(r#"{"log":{}}"#, "log.#", "b").unwrap_err().to_string(),
+            "path segment '#' steps into an object"
+        );
+
+        assert_eq!(delete(&json, "log.#"), Err(SjsonError::NoChange));
+        assert_eq!(get(&json, "log.#"), None);
+        assert_eq!(Path::parse("log.#").unwrap().segments()[1], Segment::Append);
+        assert_eq!(Segment::Append.to_string(), "#");
     }
 
     #[test]
-    fn test_optimistic_set_with_newlines() {
-        let json = r#"{"user":{"name":"Tom"}}"#;
-        let mut opts = Options::default();
-        opts.optimistic = true;
-        let result = set_options(json, "user.description", "Line 1\nLine 2", Some(&opts)).unwrap();
-        assert!(result.contains("\"description\":\"Line 1\\nLine 2\""));
+    fn test_optimistic_never_panics() {
+        let mut inputs: Vec<String> = [
+            "", " ", "{", "}", "\"", "{\"a\"", "{\"a\":", "{\"a\":}", "{\"a\":1", "{\"a\":\"é",
+            "{\"a\":\"\\", "{\"a\":1,}", "{,}", "{\"a\"1}", "{\"a\":[", "{\"a\":[1,", "{\"a\":[,]}",
+            "{\"a\":[1,2,]}", "{\"a\":é}", "{\"a\":1é}", "{\"a\":{\"b\":\"é\"} é}", "{\"é\":1,\"a\":[1,",
+            "😀", "{\"a\":😀}", "[\"a\",", "{\"a\":[null,é]}", "{\"a\":[1 2]}", "{\"a\":{\"b\":}}",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        // Random documents made of json punctuation and multibyte characters
+        const PIECES: [&str; 18] = ["{", "}", "[", "]", "\"", ":", ",", "\\", " ", "a", "b", "0", "1", "-", "null", "é", "😀", "\n"];
+        let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize
+        };
+        for _ in 0..2000 {
+            let len = next() % 24;
+            inputs.push((0..len).map(|_| PIECES[next() % PIECES.len()]).collect());
+        }
+
+        let optimistic = Options { optimistic: true, ..Default::default() };
+        let variants = [
+            optimistic.clone(),
+            Options { go_compatible_append: true, ..optimistic.clone() },
+            Options { trim_trailing_nulls: true, ..optimistic.clone() },
+            Options { replace_in_place: true, ..optimistic.clone() },
+            Options { error_on_nochange: true, verify: true, ..optimistic.clone() },
+        ];
+        for json in &inputs {
+            for path in ["a", "a.b", "a.0", "a.-1", "b.a.1"] {
+                for opts in &variants {
+                    let _ = set_options(json, path, "x", Some(opts));
+                    let _ = set_raw_options(json, path, "[1]", Some(opts));
+                    let _ = delete_options(json, path, Some(opts));
+                    let mut buf = json.clone();
+                    let _ = set_in_place(&mut buf, path, "é", Some(opts));
+                }
+            }
+
+            // Cutting the bytes anywhere also produces invalid utf-8
+            let bytes = &json.as_bytes()[..next() % (json.len() + 1)];
+            let _ = set_bytes_options(bytes, "a", "x", Some(&optimistic));
+            let _ = delete_bytes_options(bytes, "a.0", Some(&optimistic));
+        }
     }
 
     #[test]
-    fn test_optimistic_set_with_tabs() {
-        let json = r#"{"user":{"name":"Tom"}}"#;
-        let mut opts = Options::default();
-        opts.optimistic = true;
-        let result = set_options(json, "user.description", "Tab\there", Some(&opts)).unwrap();
-        assert!(result.contains("\"description\":\"Tab\\there\""));
+    fn test_dollar_root() {
+        let json = r#"{"a":{"b":1},"c":2}"#;
+        assert_eq!(set(json, "$.a.b", "3").unwrap(), r#"{"a":{"b":3},"c":2}"#);
+        assert_eq!(set(json, "$c", "3").unwrap(), r#"{"a":{"b":1},"c":3}"#);
+        assert_eq!(delete(json, "$.a.b").unwrap(), r#"{"a":{},"c":2}"#);
+        assert_eq!(get(json, "$.a.b"), Some(serde_json::json!(1)));
+
+        // A bare `$` is the root, like `@this`
+        assert_eq!(set(json, "$", "[]").unwrap(), "[]");
+        assert_eq!(set(json, "$", "2").unwrap(), set(json, ROOT_PATH, "2").unwrap());
+        assert_eq!(set_raw(json, "$", r#"{"d":4}"#).unwrap(), r#"{"d":4}"#);
+        assert_eq!(delete(json, "$"), Err(SjsonError::InvalidPath));
+        assert_eq!(get(json, "$"), get(json, ROOT_PATH));
+        assert_eq!(Path::parse("$.a.b").unwrap(), Path::parse("a.b").unwrap());
+        assert_eq!(set(json, "$.", "x"), Err(SjsonError::InvalidPath));
+
+        // Keys starting with `$` are reached by escaping it
+        let json = r#"{"$":1,"$ref":"a"}"#;
+        assert_eq!(set(json, "\\$", "2").unwrap(), r#"{"$":2,"$ref":"a"}"#);
+        assert_eq!(set(json, "\\$ref", "b").unwrap(), r#"{"$":1,"$ref":"b"}"#);
+        assert_eq!(set(json, "$.\\$", "2").unwrap(), r#"{"$":2,"$ref":"a"}"#);
+
+        let opts = Options { separator: '/', ..Default::default() };
+        assert_eq!(set_options(r#"{"a":{"b":1}}"#, "$/a/b", "2", Some(&opts)).unwrap(), r#"{"a":{"b":2}}"#);
     }
 }